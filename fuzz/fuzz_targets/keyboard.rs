@@ -0,0 +1,111 @@
+//! Feeds well-formed button sequences, with their timing, through the
+//! mappings of the keyboard (taps, holds, chords, macros, groups and
+//! profiles), checking that every key it presses is released once
+//! the buttons are.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use std::time::Duration;
+use tokio::time::Instant;
+use wiinote_core::config::Config;
+use wiinote_core::keyboard::{Keyboard, Recorder};
+use wiinote_core::mapping;
+use xwiimote::event::{Key, KeyState};
+
+const CONFIG: &str = r#"
+[bindings]
+Plus = { macro = ["LeftControl+C", { delay_ms = 50 }, "LeftControl+V"] }
+Minus = { group = ["LeftShift", "X", { profile = "media" }] }
+Down = { group = ["LeftAlt", { profile = "missing" }, "Tab"], on_error = "abort" }
+
+[holds]
+A = "Space"
+Up = "Pointer"
+
+[[chords]]
+buttons = ["A", "B"]
+action = "Esc"
+
+[[chords]]
+buttons = ["Left", "Right", "Up"]
+action = { macro = [{ text = "Hi!" }] }
+
+[[profiles]]
+name = "media"
+
+[profiles.bindings]
+A = "PlayPause"
+B = { group = ["LeftControl", { profile = "default" }] }
+"#;
+
+/// The buttons of the Wii Remote, but One and Two, handled by the
+/// connection.
+const BUTTONS: [Key; 9] = [
+    Key::Up,
+    Key::Down,
+    Key::Left,
+    Key::Right,
+    Key::A,
+    Key::B,
+    Key::Plus,
+    Key::Minus,
+    Key::Home,
+];
+
+#[derive(Arbitrary, Debug)]
+struct Step {
+    /// The index of the button in [`BUTTONS`].
+    button: u8,
+    /// Presses the button if released, or either repeats or releases it.
+    repeat: bool,
+    /// The time since the previous step.
+    delay_ms: u16,
+}
+
+fuzz_target!(|steps: Vec<Step>| {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .unwrap();
+    runtime.block_on(run(steps));
+});
+
+async fn run(steps: Vec<Step>) {
+    let config: Config = toml::from_str(CONFIG).unwrap();
+    let recorder = Recorder::new();
+    let mut keyboard =
+        Keyboard::with_emitter(mapping::profiles(&config), Box::new(recorder.clone()));
+    let mut held = [false; BUTTONS.len()];
+    for step in steps {
+        wait(&mut keyboard, Duration::from_millis(step.delay_ms.into())).await;
+        let ix = step.button as usize % BUTTONS.len();
+        let state = match (held[ix], step.repeat) {
+            (false, _) => KeyState::Down,
+            (true, true) => KeyState::AutoRepeat,
+            (true, false) => KeyState::Up,
+        };
+        held[ix] = state != KeyState::Up;
+        keyboard.update(&BUTTONS[ix], &state).unwrap();
+        keyboard.take_remote_actions();
+    }
+    for (ix, _) in held.iter().enumerate().filter(|(_, held)| **held) {
+        keyboard.update(&BUTTONS[ix], &KeyState::Up).unwrap();
+    }
+    // Let the macros finish.
+    wait(&mut keyboard, Duration::from_secs(10)).await;
+    assert_eq!(recorder.held(), [], "pressed keys left once released");
+}
+
+/// Runs the timers of the keyboard expiring within the given duration.
+async fn wait(keyboard: &mut Keyboard, duration: Duration) {
+    let until = Instant::now() + duration;
+    while tokio::time::timeout_at(until, keyboard.timer_elapsed())
+        .await
+        .is_ok()
+    {
+        keyboard.run_timers().unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+# Spanish translation of the user-facing messages of wiinote, enabled
+# with the `l10n` feature and a Spanish locale (e.g. LANG=es_ES.UTF-8).
+
+## Notifications
+
+battery-low-summary = Batería del Wii Remote baja
+battery-low-body = Jugador { $player }: queda un { $percent } %
+
+## wiinote doctor
+
+doctor-uinput-writable = se puede escribir en /dev/uinput
+doctor-uinput-missing = falta /dev/uinput
+doctor-uinput-failed = no se puede abrir /dev/uinput: { $err }
+doctor-uinput-hint =
+    carga el módulo con `modprobe uinput` y permite al usuario escribir en /dev/uinput:
+    añade la regla de udev de abajo a /lib/udev/rules.d/40-input.rules y ejecuta
+    `groupadd -f uinput && gpasswd -a $USER uinput` (luego vuelve a iniciar sesión)
+    {"    "}{ $rule }
+doctor-driver-loaded = el controlador hid-wiimote está cargado
+doctor-driver-missing = el controlador hid-wiimote no está cargado
+doctor-driver-hint = cárgalo con `modprobe hid-wiimote`
+doctor-adapter-missing = no se encontró ningún adaptador Bluetooth, o está bloqueado
+doctor-adapter-hint = comprueba `rfkill list bluetooth` y desbloquéalo con `rfkill unblock bluetooth`
+doctor-adapter-powered = el adaptador Bluetooth está encendido
+doctor-adapter-off = el adaptador Bluetooth está apagado, o BlueZ no lo encuentra
+doctor-adapter-off-hint = enciéndelo con `bluetoothctl power on`
+doctor-bluez-failed = no se puede consultar el servicio de Bluetooth
+doctor-bluez-hint = inícialo con `systemctl enable --now bluetooth.service`
+doctor-bluetoothctl-failed = no se puede ejecutar bluetoothctl: { $err }
+doctor-bluetoothctl-hint = instala BlueZ para emparejar los Wii Remotes con `--pair` y listarlos con `list`
+doctor-no-remotes = no hay ningún Wii Remote conectado, no se comprobaron sus permisos
+doctor-unknown-address = de dirección desconocida
+doctor-remote-opened = se puede abrir el Wii Remote { $name }
+doctor-remote-failed = no se puede abrir el Wii Remote { $name }: { $err }
+doctor-device-hint =
+    permite al usuario leer los dispositivos de entrada del Wii Remote: ejecuta
+    `gpasswd -a $USER input` (luego vuelve a iniciar sesión), o añade la regla de udev
+    de abajo a /lib/udev/rules.d/70-wiimote.rules
+    {"    "}{ $rule }
+doctor-kernel-exposed = el escritorio recibe los botones de los dispositivos del kernel ({ $devices })
+doctor-kernel-hint =
+    ocúltalos con la regla de udev de abajo en /lib/udev/rules.d/70-wiimote.rules
+    {"    "}{ $rule }
+doctor-failed = { $failed } comprobación(es) fallida(s)
+doctor-log-hint =
+    Para solucionarlo, { $hint }
+    Ejecuta `wiinote doctor` para comprobar la configuración.
+
+## wiinote calibrate
+
+calibrate-top-left = Apunta a la esquina superior izquierda de la pantalla y pulsa A
+calibrate-top-right = Apunta a la esquina superior derecha de la pantalla y pulsa A
+calibrate-bottom-right = Apunta a la esquina inferior derecha de la pantalla y pulsa A
+calibrate-bottom-left = Apunta a la esquina inferior izquierda de la pantalla y pulsa A
+calibrate-out-of-sight = La barra de sensores no está a la vista, inténtalo de nuevo
+
+## wiinote simulate
+
+scenario-passed = El escenario { $path } pasó
@@ -0,0 +1,92 @@
+/// The events announced with a sound.
+#[derive(Copy, Clone, Debug)]
+pub enum Cue {
+    Connected,
+    Disconnected,
+    LowBattery,
+    /// The click of a button press, see [`Click::Host`](wiinote_core::config::Click::Host).
+    Click,
+}
+
+/// Plays the cues on the host speakers, for setups where the logs
+/// aren't visible (e.g. a TV).
+///
+/// Without the `audio` feature, the player is always silent.
+pub struct AudioCues {
+    #[cfg(feature = "audio")]
+    output: Option<(rodio::OutputStream, rodio::OutputStreamHandle)>,
+    /// Whether the cues other than clicks are played.
+    #[cfg(feature = "audio")]
+    cues: bool,
+}
+
+impl AudioCues {
+    /// Creates a player that doesn't make any sound.
+    pub fn disabled() -> Self {
+        Self {
+            #[cfg(feature = "audio")]
+            output: None,
+            #[cfg(feature = "audio")]
+            cues: false,
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+impl AudioCues {
+    pub fn play(&self, _cue: Cue) {}
+}
+
+#[cfg(feature = "audio")]
+impl AudioCues {
+    /// Opens the default audio output device, playing the clicks and,
+    /// if `cues` is set, the other cues.
+    pub fn try_default(cues: bool) -> anyhow::Result<Self> {
+        Ok(Self {
+            output: Some(rodio::OutputStream::try_default()?),
+            cues,
+        })
+    }
+
+    /// Starts playing the cue, without waiting for it to finish.
+    ///
+    /// Playback errors are logged and otherwise ignored.
+    pub fn play(&self, cue: Cue) {
+        use rodio::source::{SineWave, Source, Zero};
+        use std::time::Duration;
+        use tracing::warn;
+
+        let handle = match &self.output {
+            Some((_, handle)) if self.cues || matches!(cue, Cue::Click) => handle,
+            _ => return,
+        };
+        let sink = match rodio::Sink::try_new(handle) {
+            Ok(sink) => sink,
+            Err(err) => {
+                warn!("Cannot play audio cue: {}", err);
+                return;
+            }
+        };
+
+        // Pairs of frequency (in Hz, 0 for silence) and duration (in ms).
+        let tones: &[(f32, u64)] = match cue {
+            Cue::Connected => &[(440.0, 120), (660.0, 180)],
+            Cue::Disconnected => &[(660.0, 120), (440.0, 180)],
+            Cue::LowBattery => &[(880.0, 80), (0.0, 80), (880.0, 80), (0.0, 80), (880.0, 80)],
+            Cue::Click => &[(1200.0, 25)],
+        };
+        for &(frequency, millis) in tones {
+            let duration = Duration::from_millis(millis);
+            if frequency == 0.0 {
+                sink.append(Zero::<f32>::new(1, 48000).take_duration(duration));
+            } else {
+                sink.append(
+                    SineWave::new(frequency)
+                        .take_duration(duration)
+                        .amplify(0.2),
+                );
+            }
+        }
+        sink.detach();
+    }
+}
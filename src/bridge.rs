@@ -0,0 +1,544 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use futures_util::stream::{self, LocalBoxStream, StreamExt};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use wiinote_core::bridge::{self, Frame, Role, MAGIC, NONCE_LEN, PROOF_LEN};
+use wiinote_core::remote::Remote;
+use xwiimote::event::{Event, EventKind};
+use xwiimote::Led;
+
+/// How long the other side has to prove it knows the secret.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many connections can prove they know the secret at once, the
+/// next ones are closed meanwhile.
+const MAX_HANDSHAKES: usize = 4;
+/// How many frames can wait for a slow client. The motion events
+/// beyond are dropped, superseded by the next ones.
+const FRAME_QUEUE: usize = 256;
+/// How long the client has to receive a frame before it's dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state of a remote connected to the server: its kind and last
+/// battery level, sent again to the clients that connect later.
+type Announced = (String, Option<u8>);
+
+/// The server side of the bridge, which forwards the events of the
+/// Wii Remotes connected to this machine to a client (see
+/// [`BridgeClient`]) on another one, e.g. a Raspberry Pi near the
+/// couch handling the Bluetooth connection of an HTPC.
+///
+/// Only one client is served at a time, a new one replaces it. The
+/// clients must prove they know the shared secret, see
+/// [`bridge::proof`]. The frames sent while no client is connected
+/// are dropped, and so are the motion events a slow client can't keep
+/// up with; it's disconnected if it misses any other frame.
+#[derive(Clone)]
+pub struct BridgeServer {
+    frames: mpsc::Sender<Vec<u8>>,
+    /// Whether a frame other than a motion event didn't fit in the
+    /// queue.
+    overflowed: Arc<AtomicBool>,
+    /// The frames sent by the client, the light and rumble changes.
+    commands: broadcast::Sender<Frame>,
+    remotes: Arc<Mutex<HashMap<u8, Announced>>>,
+}
+
+impl BridgeServer {
+    /// Listens for the client knowing the secret at the given address.
+    pub async fn bind(address: SocketAddr, secret: &str) -> Result<Self> {
+        let listener = TcpListener::bind(address)
+            .await
+            .with_context(|| format!("cannot listen on {}", address))?;
+        info!("Bridge server listening on {}", address);
+        let (frames, mut queue) = mpsc::channel::<Vec<u8>>(FRAME_QUEUE);
+        let server = Self {
+            frames,
+            overflowed: Arc::default(),
+            commands: broadcast::channel(16).0,
+            remotes: Arc::default(),
+        };
+        let (clients, mut accepted) = mpsc::unbounded_channel();
+        let commands = server.commands.clone();
+        let secret: Arc<[u8]> = secret.as_bytes().into();
+        let handshakes = Arc::new(Semaphore::new(MAX_HANDSHAKES));
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, peer) = match listener.accept().await {
+                    Ok(client) => client,
+                    Err(err) => {
+                        warn!("Cannot accept a bridge client: {}", err);
+                        continue;
+                    }
+                };
+                let permit = match handshakes.clone().try_acquire_owned() {
+                    Ok(permit) => permit,
+                    Err(_) => {
+                        warn!("Rejected bridge client {}: too many handshakes", peer);
+                        continue;
+                    }
+                };
+                let (clients, secret) = (clients.clone(), secret.clone());
+                tokio::spawn(async move {
+                    let handshake =
+                        tokio::time::timeout(HANDSHAKE_TIMEOUT, accept(&mut stream, &secret));
+                    let handshake = handshake.await;
+                    drop(permit);
+                    match handshake {
+                        Ok(Ok(())) => {}
+                        Ok(Err(err)) => return warn!("Rejected bridge client {}: {:#}", peer, err),
+                        Err(_) => {
+                            return warn!("Rejected bridge client {}: handshake timed out", peer)
+                        }
+                    }
+                    let _ = clients.send((stream, peer));
+                });
+            }
+        });
+        let remotes = server.remotes.clone();
+        let overflowed = server.overflowed.clone();
+        tokio::spawn(async move {
+            // The writer, address and command reading task of the client.
+            let mut client: Option<(OwnedWriteHalf, SocketAddr, JoinHandle<()>)> = None;
+            loop {
+                let frame = tokio::select! {
+                    Some((stream, peer)) = accepted.recv() => {
+                        match client.take() {
+                            Some((_, previous, reading)) => {
+                                // Closes the connection once both halves are dropped.
+                                reading.abort();
+                                info!("Bridge client {} replaced by {}", previous, peer);
+                            }
+                            None => info!("Bridge client {} connected", peer),
+                        }
+                        // The new client starts from the greeting.
+                        overflowed.store(false, Ordering::Relaxed);
+                        let (reader, writer) = stream.into_split();
+                        let reading = tokio::spawn(read_commands(reader, peer, commands.clone()));
+                        client = Some((writer, peer, reading));
+                        let mut greeting = Vec::new();
+                        for (remote, (kind, level)) in remotes.lock().unwrap().iter() {
+                            greeting.extend(announce(*remote, kind, *level));
+                        }
+                        greeting
+                    }
+                    frame = queue.recv() => match frame {
+                        Some(frame) => frame,
+                        None => return,
+                    },
+                };
+                if let Some((writer, peer, reading)) = client.as_mut() {
+                    let written = if overflowed.swap(false, Ordering::Relaxed) {
+                        Err(anyhow!("too slow, frames were dropped"))
+                    } else {
+                        match tokio::time::timeout(WRITE_TIMEOUT, writer.write_all(&frame)).await {
+                            Ok(written) => written.map_err(anyhow::Error::from),
+                            Err(_) => Err(anyhow!("stalled")),
+                        }
+                    };
+                    if let Err(err) = written {
+                        warn!("Bridge client {} disconnected: {:#}", peer, err);
+                        reading.abort();
+                        client = None;
+                    }
+                }
+            }
+        });
+        Ok(server)
+    }
+
+    /// Announces the remote of the given kind to the client, returning
+    /// the handle forwarding its events until dropped.
+    pub fn connect(&self, remote: u8, kind: &str) -> BridgedRemote {
+        let announced = (kind.to_owned(), None);
+        self.remotes.lock().unwrap().insert(remote, announced);
+        self.send(Frame::Connected {
+            remote,
+            kind: kind.to_owned(),
+        });
+        BridgedRemote {
+            remote,
+            server: self.clone(),
+            commands: self.commands.subscribe(),
+        }
+    }
+
+    fn send(&self, frame: Frame) {
+        let motion = matches!(
+            &frame,
+            Frame::Event { event, .. } if !matches!(event, EventKind::Key(..))
+        );
+        if let Some(frame) = frame.encode() {
+            let full = matches!(
+                self.frames.try_send(frame),
+                Err(mpsc::error::TrySendError::Full(_))
+            );
+            if full && !motion {
+                self.overflowed.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Sends the challenge of the server to the client, and proves the
+/// server knows the secret once the client did.
+async fn accept(stream: &mut TcpStream, secret: &[u8]) -> Result<()> {
+    let server_nonce = bridge::nonce()?;
+    stream.write_all(MAGIC).await?;
+    stream.write_all(&server_nonce).await?;
+    let mut reply = [0; NONCE_LEN + PROOF_LEN];
+    stream.read_exact(&mut reply).await?;
+    let (client_nonce, proof) = reply.split_at(NONCE_LEN);
+    ensure!(
+        bridge::verify(secret, Role::Client, &server_nonce, client_nonce, proof),
+        "wrong secret"
+    );
+    let proof = bridge::proof(secret, Role::Server, &server_nonce, client_nonce);
+    stream.write_all(&proof).await?;
+    Ok(())
+}
+
+/// Returns the frames announcing a connected remote.
+fn announce(remote: u8, kind: &str, level: Option<u8>) -> Vec<u8> {
+    let mut frames = Frame::Connected {
+        remote,
+        kind: kind.to_owned(),
+    }
+    .encode()
+    .unwrap_or_default();
+    if let Some(level) = level {
+        frames.extend(
+            Frame::Battery { remote, level }
+                .encode()
+                .unwrap_or_default(),
+        );
+    }
+    frames
+}
+
+/// Forwards the frames of the client until it disconnects.
+async fn read_commands(
+    mut reader: OwnedReadHalf,
+    peer: SocketAddr,
+    commands: broadcast::Sender<Frame>,
+) {
+    loop {
+        match read_frame(&mut reader).await {
+            Ok(Some(frame)) => {
+                let _ = commands.send(frame);
+            }
+            Ok(None) => break,
+            Err(err) => {
+                warn!("Bridge client {} error: {:#}", peer, err);
+                break;
+            }
+        }
+    }
+    info!("Bridge client {} disconnected", peer);
+}
+
+/// A Wii Remote connected to the [`BridgeServer`], disconnected from
+/// the client when dropped.
+pub struct BridgedRemote {
+    remote: u8,
+    server: BridgeServer,
+    commands: broadcast::Receiver<Frame>,
+}
+
+impl BridgedRemote {
+    /// Forwards the event, if it's of a forwarded kind.
+    pub fn event(&self, event: &EventKind) {
+        self.server.send(Frame::Event {
+            remote: self.remote,
+            event: event.clone(),
+        });
+    }
+
+    /// Forwards the battery level if it changed.
+    pub fn battery(&self, level: u8) {
+        let mut remotes = self.server.remotes.lock().unwrap();
+        if let Some((_, last)) = remotes.get_mut(&self.remote) {
+            if last.replace(level) != Some(level) {
+                self.server.send(Frame::Battery {
+                    remote: self.remote,
+                    level,
+                });
+            }
+        }
+    }
+
+    /// Waits for the next light, rumble or power off request of the
+    /// client for this remote.
+    pub async fn command(&mut self) -> Frame {
+        loop {
+            match self.commands.recv().await {
+                Ok(frame) if frame.remote() == self.remote => return frame,
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                // The server lives as long as the process.
+                Err(broadcast::error::RecvError::Closed) => {
+                    futures_util::future::pending::<()>().await
+                }
+            }
+        }
+    }
+}
+
+impl Drop for BridgedRemote {
+    fn drop(&mut self) {
+        self.server.remotes.lock().unwrap().remove(&self.remote);
+        self.server.send(Frame::Disconnected {
+            remote: self.remote,
+        });
+    }
+}
+
+/// The client side of the bridge, which receives the Wii Remotes
+/// connected to a [`BridgeServer`] on another machine.
+pub struct BridgeClient {
+    reader: OwnedReadHalf,
+    /// The frames sent to the server.
+    commands: mpsc::UnboundedSender<Frame>,
+    /// The frames of each connected remote.
+    remotes: HashMap<u8, mpsc::UnboundedSender<Frame>>,
+}
+
+impl BridgeClient {
+    /// Connects to the server knowing the secret at the given address
+    /// (e.g. `raspberrypi.local:7744`).
+    pub async fn connect(address: &str, secret: &str) -> Result<Self> {
+        let mut stream = TcpStream::connect(address).await?;
+        stream.set_nodelay(true)?;
+        tokio::time::timeout(
+            HANDSHAKE_TIMEOUT,
+            authenticate(&mut stream, secret.as_bytes()),
+        )
+        .await
+        .map_err(|_| anyhow!("handshake timed out"))??;
+        let (reader, mut writer) = stream.into_split();
+        let (commands, mut queue) = mpsc::unbounded_channel::<Frame>();
+        tokio::spawn(async move {
+            while let Some(frame) = queue.recv().await {
+                let frame = frame.encode().expect("commands are always encoded");
+                if let Err(err) = writer.write_all(&frame).await {
+                    debug!("Cannot send to the bridge server: {}", err);
+                    return;
+                }
+            }
+        });
+        Ok(Self {
+            reader,
+            commands,
+            remotes: HashMap::new(),
+        })
+    }
+
+    /// Waits for the next remote to connect, forwarding the events of
+    /// the others meanwhile. Returns `None` once the server closes the
+    /// connection, which disconnects all the remotes.
+    pub async fn next_remote(&mut self) -> Result<Option<(NetworkRemote, String)>> {
+        loop {
+            let frame = match read_frame(&mut self.reader).await? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+            let remote = frame.remote();
+            match frame {
+                // Already announced by the greeting.
+                Frame::Connected { .. } if self.remotes.contains_key(&remote) => {}
+                Frame::Connected { kind, .. } => {
+                    let (frames, events) = mpsc::unbounded_channel();
+                    self.remotes.insert(remote, frames);
+                    let network_remote = NetworkRemote {
+                        remote,
+                        events: Rc::new(tokio::sync::Mutex::new(events)),
+                        commands: self.commands.clone(),
+                        battery: Cell::new(0),
+                        lights: Cell::new(0),
+                    };
+                    return Ok(Some((network_remote, kind)));
+                }
+                Frame::Disconnected { .. } => {
+                    // Ends the events of the remote.
+                    self.remotes.remove(&remote);
+                }
+                frame @ (Frame::Event { .. } | Frame::Battery { .. }) => {
+                    if let Some(frames) = self.remotes.get(&remote) {
+                        let _ = frames.send(frame);
+                    }
+                }
+                frame => debug!("Unexpected bridge frame: {:?}", frame),
+            }
+        }
+    }
+}
+
+/// Answers the challenge of the server, and checks the server knows
+/// the secret too.
+async fn authenticate(stream: &mut TcpStream, secret: &[u8]) -> Result<()> {
+    let mut magic = [0; MAGIC.len()];
+    stream.read_exact(&mut magic).await?;
+    ensure!(
+        &magic == MAGIC,
+        "not a wiinote bridge server, or another version"
+    );
+    let mut server_nonce = [0; NONCE_LEN];
+    stream.read_exact(&mut server_nonce).await?;
+    let client_nonce = bridge::nonce()?;
+    let mut reply = client_nonce.to_vec();
+    reply.extend(bridge::proof(
+        secret,
+        Role::Client,
+        &server_nonce,
+        &client_nonce,
+    ));
+    stream.write_all(&reply).await?;
+    let mut proof = [0; PROOF_LEN];
+    stream
+        .read_exact(&mut proof)
+        .await
+        .context("the server rejected the secret")?;
+    ensure!(
+        bridge::verify(secret, Role::Server, &server_nonce, &client_nonce, &proof),
+        "the server doesn't know the secret"
+    );
+    Ok(())
+}
+
+/// A Wii Remote connected to a [`BridgeServer`], whose lights and
+/// rumble motor are set through the [`BridgeClient`].
+pub struct NetworkRemote {
+    remote: u8,
+    events: Rc<tokio::sync::Mutex<mpsc::UnboundedReceiver<Frame>>>,
+    commands: mpsc::UnboundedSender<Frame>,
+    /// The last battery level received.
+    battery: Cell<u8>,
+    /// The state of the lights, as last set.
+    lights: Cell<u8>,
+}
+
+impl NetworkRemote {
+    /// Asks the server to disconnect the remote, turning it off.
+    pub fn power_off(&self) {
+        self.send(Frame::PowerOff {
+            remote: self.remote,
+        });
+    }
+
+    fn send(&self, frame: Frame) {
+        // Once disconnected, the events end too.
+        let _ = self.commands.send(frame);
+    }
+}
+
+impl Remote for NetworkRemote {
+    fn events(&self) -> Result<LocalBoxStream<'_, Result<Event>>> {
+        let events = stream::unfold(self.events.clone(), move |events| async move {
+            loop {
+                let frame = events.lock().await.recv().await?;
+                match frame {
+                    Frame::Event { event, .. } => {
+                        let event = Event {
+                            time: SystemTime::now(),
+                            kind: event,
+                        };
+                        return Some((Ok(event), events));
+                    }
+                    Frame::Battery { level, .. } => self.battery.set(level),
+                    _ => {}
+                }
+            }
+        });
+        Ok(events.boxed_local())
+    }
+
+    fn battery(&self) -> Result<u8> {
+        Ok(self.battery.get())
+    }
+
+    fn led(&self, light: Led) -> Result<bool> {
+        Ok(self.lights.get() & (1 << (light as u8 - 1)) != 0)
+    }
+
+    fn set_led(&self, light: Led, enabled: bool) -> Result<()> {
+        let bit = 1 << (light as u8 - 1);
+        let lights = self.lights.get();
+        self.lights
+            .set(if enabled { lights | bit } else { lights & !bit });
+        self.send(Frame::Led {
+            remote: self.remote,
+            light: light as u8,
+            enabled,
+        });
+        Ok(())
+    }
+
+    fn set_rumble(&self, enabled: bool) -> Result<()> {
+        self.send(Frame::Rumble {
+            remote: self.remote,
+            enabled,
+        });
+        Ok(())
+    }
+}
+
+/// Reads the next frame, or returns `None` once the connection closes.
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> Result<Option<Frame>> {
+    let len = match reader.read_u16_le().await {
+        Ok(len) => len,
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let mut frame = vec![0; len as usize];
+    reader.read_exact(&mut frame).await?;
+    match Frame::decode(&frame) {
+        Some(frame) => Ok(Some(frame)),
+        None => bail!("malformed bridge frame"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xwiimote::event::{Key, KeyState};
+
+    #[test]
+    fn drops_motion_events_when_the_queue_is_full() {
+        let (frames, mut queue) = mpsc::channel(FRAME_QUEUE);
+        let server = BridgeServer {
+            frames,
+            overflowed: Arc::default(),
+            commands: broadcast::channel(16).0,
+            remotes: Arc::default(),
+        };
+        let motion = EventKind::Accelerometer { x: 0, y: 0, z: 0 };
+        for _ in 0..FRAME_QUEUE + 10 {
+            server.send(Frame::Event {
+                remote: 0,
+                event: motion.clone(),
+            });
+        }
+        assert!(!server.overflowed.load(Ordering::Relaxed));
+        server.send(Frame::Event {
+            remote: 0,
+            event: EventKind::Key(Key::A, KeyState::Down),
+        });
+        assert!(server.overflowed.load(Ordering::Relaxed));
+
+        let mut queued = 0;
+        while queue.try_recv().is_ok() {
+            queued += 1;
+        }
+        assert_eq!(queued, FRAME_QUEUE);
+    }
+}
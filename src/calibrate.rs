@@ -0,0 +1,109 @@
+use anyhow::{bail, Context, Result};
+use futures_util::stream::TryStreamExt;
+use std::path::PathBuf;
+use tracing::info;
+use wiinote_core::calibration::{self, Homography};
+use wiinote_core::config::Config;
+use wiinote_core::monitor;
+use xwiimote::event::{EventKind, Key, KeyState};
+use xwiimote::{Address, Channels, Device};
+
+/// Returns the message asking to point at a corner of the screen.
+type Prompt = fn() -> String;
+
+/// The prompts of the corners of the screen, in the order they are
+/// pointed at, and their position in fractions of the screen size.
+const CORNERS: [(Prompt, (f64, f64)); 4] = [
+    (
+        || {
+            tr!(
+                "calibrate-top-left",
+                "Point at the top left corner of the screen and press A"
+            )
+        },
+        (0.0, 0.0),
+    ),
+    (
+        || {
+            tr!(
+                "calibrate-top-right",
+                "Point at the top right corner of the screen and press A"
+            )
+        },
+        (1.0, 0.0),
+    ),
+    (
+        || {
+            tr!(
+                "calibrate-bottom-right",
+                "Point at the bottom right corner of the screen and press A"
+            )
+        },
+        (1.0, 1.0),
+    ),
+    (
+        || {
+            tr!(
+                "calibrate-bottom-left",
+                "Point at the bottom left corner of the screen and press A"
+            )
+        },
+        (0.0, 1.0),
+    ),
+];
+
+/// Asks the user to point the Wii Remote at the given path (by default,
+/// the first connected one) at each corner of the screen, then stores
+/// the transformation from the IR camera to the screen in the
+/// configuration file.
+pub async fn calibrate(path: Option<PathBuf>, config: Option<PathBuf>) -> Result<()> {
+    let config = config
+        .or_else(Config::default_path)
+        .context("cannot find the configuration directory, use --config")?;
+    let path = match path {
+        Some(path) => path,
+        None => monitor::connected_devices()?
+            .into_iter()
+            .next()
+            .context("no connected devices found")?,
+    };
+    let mut device = Device::connect(&Address::from(path))?;
+    device.open(Channels::CORE | Channels::IR, false)?;
+    let mut events = device.events()?;
+
+    let mut corners = [(0.0, 0.0); 4];
+    let mut position = None;
+    for (corner, (prompt, _)) in corners.iter_mut().zip(CORNERS) {
+        println!("{}", prompt());
+        loop {
+            match events.try_next().await?.map(|event| event.kind) {
+                Some(EventKind::Ir(sources)) => {
+                    let sources = sources.iter().flatten().map(|s| (s.x, s.y));
+                    position = calibration::ir_position(sources);
+                }
+                Some(EventKind::Key(Key::A, KeyState::Down)) => match position {
+                    Some(position) => {
+                        *corner = position;
+                        break;
+                    }
+                    None => println!(
+                        "{}",
+                        tr!(
+                            "calibrate-out-of-sight",
+                            "The sensor bar is out of sight, try again"
+                        )
+                    ),
+                },
+                Some(_) => {}
+                None => bail!("the Wii Remote disconnected"),
+            }
+        }
+    }
+
+    let screen = CORNERS.map(|(_, position)| position);
+    let homography = Homography::from_points(corners, screen)
+        .context("the corners are aligned, point at them more precisely")?;
+    calibration::store(&config, &homography)?;
+    info!("Calibration saved to {}", config.display());
+    Ok(())
+}
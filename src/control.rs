@@ -0,0 +1,323 @@
+use anyhow::{anyhow, bail, ensure, Context, Error, Result};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+use tracing::error;
+use wiinote_core::monitor::parse_duration;
+use wiinote_core::output::{EVENTS, SCHEMA_VERSION};
+
+/// How long the connections are given to reply to a request. Released
+/// devices only handle the requests once reacquired.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long a sync window stays open by default.
+pub const SYNC_WINDOW: Duration = Duration::from_secs(30);
+
+/// The version of the protocol of the control socket and the WebSocket
+/// server, increased when a request or an event changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The requests understood by this version, listed in the handshake.
+pub const REQUESTS: [&str; 6] = ["battery", "status", "profile", "rumble", "lights", "sync"];
+
+/// Negotiates the protocol version if the line is a `hello <version>`
+/// handshake, returning `None` for the other requests.
+///
+/// Clients send the newest version they speak, and get the one both
+/// ends speak with the supported requests, so that newer clients can
+/// check for a request before sending it. Clients skipping the
+/// handshake speak the first version.
+pub fn handshake(line: &str) -> Option<Result<u32>> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("hello") {
+        return None;
+    }
+    let version = match (words.next().map(str::parse::<u32>), words.next()) {
+        (Some(Ok(version)), None) if version > 0 => version,
+        _ => {
+            return Some(Err(anyhow!(
+                "invalid handshake `{}`, expected `hello <version>` with versions 1 to {}",
+                line,
+                PROTOCOL_VERSION
+            )))
+        }
+    };
+    Some(Ok(version.min(PROTOCOL_VERSION)))
+}
+
+/// Formats the reply to the handshake, with the negotiated version, the
+/// version of the events format and the supported requests and events.
+pub fn hello(version: u32) -> String {
+    format!(
+        "version {} schema {} requests {} events {}",
+        version,
+        SCHEMA_VERSION,
+        REQUESTS.join(","),
+        EVENTS.join(",")
+    )
+}
+
+/// Returns the requests listed in the reply to the handshake.
+fn supported_requests(reply: &str) -> Option<Vec<&str>> {
+    let words: Vec<_> = reply.split_whitespace().collect();
+    if words.first() != Some(&"version") {
+        return None;
+    }
+    // The reply is made of key and value pairs.
+    let requests = words.chunks(2).find(|pair| pair[0] == "requests")?.get(1)?;
+    Some(requests.split(',').collect())
+}
+
+/// A request sent to every connected Wii Remote through the control socket.
+#[derive(Clone, Debug)]
+pub enum Request {
+    /// Reports the battery level.
+    Battery,
+    /// Reports the battery level, the active profile and whether the
+    /// keyboard output is muted.
+    Status,
+    /// Activates the profile with the given name.
+    Profile(String),
+    /// Pulses the rumble motor for the given duration.
+    Rumble(Duration),
+    /// Turns on the given lights (the first one is the least significant
+    /// bit) instead of the displayed metric, or displays it again if `None`.
+    Lights(Option<u8>),
+    /// Accepts new Wii Remotes for the given duration, blinking the
+    /// lights of the connected ones. Handled by the discovery too.
+    Sync(Duration),
+}
+
+impl FromStr for Request {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut words = s.split_whitespace();
+        let request = match (words.next(), words.next()) {
+            (Some("battery"), None) => Self::Battery,
+            (Some("status"), None) => Self::Status,
+            (Some("profile"), Some(name)) => Self::Profile(name.to_owned()),
+            (Some("rumble"), Some(duration)) => Self::Rumble(parse_duration(duration)?),
+            (Some("lights"), Some(lights)) => Self::Lights(parse_lights(lights)?),
+            (Some("sync"), None) => Self::Sync(SYNC_WINDOW),
+            (Some("sync"), Some(duration)) => Self::Sync(parse_duration(duration)?),
+            _ => bail!(
+                "unknown request `{}`, expected `battery`, `status`, `profile <name>`, \
+                 `rumble <duration>`, `lights <lights>` or `sync [duration]`",
+                s
+            ),
+        };
+        if words.next().is_some() {
+            bail!("too many arguments in request `{}`", s);
+        }
+        Ok(request)
+    }
+}
+
+/// Parses the lights to turn on, e.g. `14` for the first and the last
+/// ones, `off` for none, or `auto` to display the metric again.
+fn parse_lights(s: &str) -> Result<Option<u8>> {
+    match s {
+        "auto" => return Ok(None),
+        "off" => return Ok(Some(0)),
+        _ => {}
+    }
+    let mut pattern = 0;
+    for c in s.chars() {
+        match c {
+            '1'..='4' => pattern |= 1 << (c as u8 - b'1'),
+            _ => bail!(
+                "invalid lights `{}`, expected `auto`, `off` or numbers 1 to 4",
+                s
+            ),
+        }
+    }
+    Ok(Some(pattern))
+}
+
+/// A request, and where each connection sends its reply. The discovery
+/// replies too, with an empty line to the requests it doesn't handle.
+#[derive(Clone, Debug)]
+pub struct Command {
+    pub request: Request,
+    pub reply: mpsc::UnboundedSender<String>,
+}
+
+/// Returns the path of the control socket of the instance,
+/// `$XDG_RUNTIME_DIR/wiinote[-instance].sock`.
+pub fn socket_path(instance: Option<&str>) -> Result<PathBuf> {
+    let dir = dirs::runtime_dir().context("XDG_RUNTIME_DIR is not set")?;
+    Ok(match instance {
+        Some(instance) => dir.join(format!("wiinote-{}.sock", instance)),
+        None => dir.join("wiinote.sock"),
+    })
+}
+
+/// Accepts the requests sent to the socket at the given path, forwarding
+/// them to the connections.
+pub async fn listen(path: &Path, commands: broadcast::Sender<Command>) -> Result<()> {
+    if UnixStream::connect(path).await.is_ok() {
+        bail!(
+            "another instance is listening on {}, set a different --instance",
+            path.display()
+        );
+    }
+    // Remove the socket left by a previous instance.
+    let _ = std::fs::remove_file(path);
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("cannot listen on {}", path.display()))?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let commands = commands.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve_client(stream, &commands).await {
+                error!("Control client error: {}", err);
+            }
+        });
+    }
+}
+
+/// Reads a request line, then writes a line per connection reply. The
+/// request may follow a handshake, replied with [`hello`], e.g.
+/// `version 1 schema 1 requests battery,status events connected,button`.
+async fn serve_client(stream: UnixStream, commands: &broadcast::Sender<Command>) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    if let Some(version) = handshake(&line) {
+        let reply = match version {
+            Ok(version) => format!("{}\n", hello(version)),
+            Err(err) => {
+                writer
+                    .write_all(format!("error: {}\n", err).as_bytes())
+                    .await?;
+                return Ok(());
+            }
+        };
+        writer.write_all(reply.as_bytes()).await?;
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+    }
+    let request = match line.trim().parse() {
+        Ok(request) => request,
+        Err(err) => {
+            writer
+                .write_all(format!("error: {}\n", err).as_bytes())
+                .await?;
+            return Ok(());
+        }
+    };
+
+    for reply in forward(request, commands).await {
+        writer.write_all(format!("{}\n", reply).as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// Sends the request to the connections, returning their replies
+/// (or an error message if no device is connected).
+pub async fn forward(request: Request, commands: &broadcast::Sender<Command>) -> Vec<String> {
+    let (reply, mut replies) = mpsc::unbounded_channel();
+    // Each connection replies once.
+    let mut pending = match commands.send(Command { request, reply }) {
+        Ok(receivers) => receivers,
+        Err(_) => return vec!["error: no connected devices".to_owned()],
+    };
+    let mut received = Vec::new();
+    let mut skipped = 0;
+    let deadline = tokio::time::sleep(REPLY_TIMEOUT);
+    tokio::pin!(deadline);
+    while pending > 0 {
+        tokio::select! {
+            Some(reply) = replies.recv() => {
+                if reply.is_empty() {
+                    skipped += 1;
+                } else {
+                    received.push(reply);
+                }
+                pending -= 1;
+            }
+            _ = &mut deadline => break,
+        }
+    }
+    if received.is_empty() && pending == 0 && skipped > 0 {
+        received.push("error: no connected devices".to_owned());
+    }
+    received
+}
+
+/// Sends the request to the running instance after negotiating the
+/// version (unless the request is the handshake), printing the replies.
+pub async fn send(path: &Path, request: &str) -> Result<()> {
+    let stream = UnixStream::connect(path)
+        .await
+        .with_context(|| format!("cannot connect to {}, is wiinote running?", path.display()))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    if handshake(request).is_none() {
+        writer
+            .write_all(format!("hello {}\n", PROTOCOL_VERSION).as_bytes())
+            .await?;
+        let reply = lines
+            .next_line()
+            .await?
+            .context("the running instance closed the connection")?;
+        let requests = supported_requests(&reply).with_context(|| {
+            format!(
+                "unexpected handshake reply `{}`, restart wiinote to update it",
+                reply
+            )
+        })?;
+        let name = request.split_whitespace().next().unwrap_or_default();
+        ensure!(
+            requests.contains(&name),
+            "the running instance doesn't support `{}`, only {}",
+            name,
+            requests.join(", ")
+        );
+    }
+    writer
+        .write_all(format!("{}\n", request).as_bytes())
+        .await?;
+    // Nothing else is sent, e.g. after a handshake.
+    writer.shutdown().await?;
+    while let Some(line) = lines.next_line().await? {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiates_the_version() {
+        assert!(handshake("status").is_none());
+        assert_eq!(handshake("hello 1").unwrap().unwrap(), 1);
+        // Newer clients fall back to the version of the daemon.
+        assert_eq!(handshake("hello 7").unwrap().unwrap(), PROTOCOL_VERSION);
+        assert!(handshake("hello").unwrap().is_err());
+        assert!(handshake("hello 0").unwrap().is_err());
+        assert!(handshake("hello 1 2").unwrap().is_err());
+    }
+
+    #[test]
+    fn lists_the_requests_and_events() {
+        let reply = hello(1);
+        assert_eq!(
+            reply,
+            "version 1 schema 1 \
+             requests battery,status,profile,rumble,lights,sync \
+             events connected,button,accelerometer,battery,disconnected"
+        );
+        assert_eq!(supported_requests(&reply).unwrap(), REQUESTS);
+        assert!(supported_requests("error: unknown request `hello`").is_none());
+    }
+}
@@ -0,0 +1,266 @@
+use anyhow::{bail, Result};
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::path::Path;
+use tokio::process::Command;
+use tracing::error;
+use wiinote_core::error::{ConnectionError, UinputError};
+use wiinote_core::{adapter, kernel, monitor, remote};
+use xwiimote::{Address, Channels};
+
+/// The udev rule allowing the `uinput` group to create virtual devices.
+const UINPUT_RULE: &str =
+    "KERNEL==\"uinput\", SUBSYSTEM==\"misc\", GROUP=\"uinput\", MODE=\"0660\"";
+
+/// The udev rule allowing the `input` group to read the Wii Remotes.
+const DEVICE_RULE: &str =
+    "SUBSYSTEM==\"input\", ATTRS{name}==\"Nintendo Wii Remote*\", GROUP=\"input\", MODE=\"0660\"";
+
+/// How to create the virtual devices as a regular user.
+fn uinput_hint() -> String {
+    tr!(
+        "doctor-uinput-hint",
+        "load the module with `modprobe uinput`, then allow the user to write to /dev/uinput: \
+         add the udev rule below to /lib/udev/rules.d/40-input.rules and run \
+         `groupadd -f uinput && gpasswd -a $USER uinput` (then log in again)\n    {rule}",
+        rule = UINPUT_RULE,
+    )
+}
+
+/// How to open the input devices of the Wii Remotes as a regular user.
+fn device_hint() -> String {
+    tr!(
+        "doctor-device-hint",
+        "allow the user to read the Wii Remote input devices: run `gpasswd -a $USER input` \
+         (then log in again), or add the udev rule below to /lib/udev/rules.d/70-wiimote.rules\n    {rule}",
+        rule = DEVICE_RULE,
+    )
+}
+
+/// Logs how to fix the error, if it comes from missing access rights
+/// to the uinput or Wii Remote devices.
+pub fn log_hint(err: &anyhow::Error) {
+    let hint = if err.chain().any(|cause| cause.is::<UinputError>()) {
+        uinput_hint()
+    } else if err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<ConnectionError>(),
+            Some(ConnectionError::PermissionDenied(_))
+        )
+    }) {
+        device_hint()
+    } else {
+        return;
+    };
+    error!(
+        "{}",
+        tr!(
+            "doctor-log-hint",
+            "To fix this, {hint}\nRun `wiinote doctor` to check the setup.",
+            hint = hint,
+        )
+    );
+}
+
+/// The outcome of a check.
+enum Status {
+    Ok,
+    /// The program works, with a caveat.
+    Warning,
+    Failed,
+}
+
+/// Checks that the kernel modules are loaded, the user can access the
+/// uinput and Wii Remote devices, and Bluetooth is working, printing
+/// how to fix the problems found.
+pub async fn doctor() -> Result<()> {
+    let mut failed = 0;
+    let mut report = |status: Status, message: String, hint: Option<String>| {
+        let label = match status {
+            Status::Ok => "ok",
+            Status::Warning => "warn",
+            Status::Failed => {
+                failed += 1;
+                "FAIL"
+            }
+        };
+        println!("{:<6}{}", label, message);
+        if let Some(hint) = hint {
+            for line in hint.lines() {
+                println!("{:<6}{}", "", line);
+            }
+        }
+    };
+
+    // Both modules may also be built into the kernel.
+    match OpenOptions::new().write(true).open("/dev/uinput") {
+        Ok(_) => report(
+            Status::Ok,
+            tr!("doctor-uinput-writable", "/dev/uinput is writable"),
+            None,
+        ),
+        Err(err) if err.kind() == ErrorKind::NotFound => report(
+            Status::Failed,
+            tr!("doctor-uinput-missing", "/dev/uinput is missing"),
+            Some(uinput_hint()),
+        ),
+        Err(err) => report(
+            Status::Failed,
+            tr!(
+                "doctor-uinput-failed",
+                "cannot open /dev/uinput: {err}",
+                err = err
+            ),
+            Some(uinput_hint()),
+        ),
+    }
+    if Path::new("/sys/bus/hid/drivers/wiimote").exists() {
+        report(
+            Status::Ok,
+            tr!("doctor-driver-loaded", "the hid-wiimote driver is loaded"),
+            None,
+        );
+    } else {
+        report(
+            Status::Failed,
+            tr!(
+                "doctor-driver-missing",
+                "the hid-wiimote driver is not loaded"
+            ),
+            Some(tr!(
+                "doctor-driver-hint",
+                "load it with `modprobe hid-wiimote`"
+            )),
+        );
+    }
+
+    if !adapter::is_available() {
+        report(
+            Status::Failed,
+            tr!(
+                "doctor-adapter-missing",
+                "no Bluetooth adapter found, or it is blocked"
+            ),
+            Some(tr!(
+                "doctor-adapter-hint",
+                "check `rfkill list bluetooth`, and unblock it with `rfkill unblock bluetooth`"
+            )),
+        );
+    }
+    match Command::new("bluetoothctl").arg("show").output().await {
+        Ok(output) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if stdout.contains("Powered: yes") {
+                report(
+                    Status::Ok,
+                    tr!(
+                        "doctor-adapter-powered",
+                        "the Bluetooth adapter is powered on"
+                    ),
+                    None,
+                );
+            } else {
+                report(
+                    Status::Failed,
+                    tr!(
+                        "doctor-adapter-off",
+                        "the Bluetooth adapter is powered off, or not found by BlueZ"
+                    ),
+                    Some(tr!(
+                        "doctor-adapter-off-hint",
+                        "power it on with `bluetoothctl power on`"
+                    )),
+                );
+            }
+        }
+        Ok(_) => report(
+            Status::Failed,
+            tr!("doctor-bluez-failed", "cannot query the Bluetooth daemon"),
+            Some(tr!(
+                "doctor-bluez-hint",
+                "start it with `systemctl enable --now bluetooth.service`"
+            )),
+        ),
+        Err(err) => report(
+            Status::Warning,
+            tr!(
+                "doctor-bluetoothctl-failed",
+                "cannot run bluetoothctl: {err}",
+                err = err
+            ),
+            Some(tr!(
+                "doctor-bluetoothctl-hint",
+                "install BlueZ to pair Wii Remotes with `--pair`, and list them with `list`"
+            )),
+        ),
+    }
+
+    let devices = monitor::connected_devices()?;
+    if devices.is_empty() {
+        report(
+            Status::Warning,
+            tr!(
+                "doctor-no-remotes",
+                "no Wii Remote connected, their access rights weren't checked"
+            ),
+            None,
+        );
+    }
+    for path in devices {
+        let address = monitor::bluetooth_address(&path);
+        let name = address.unwrap_or_else(|| tr!("doctor-unknown-address", "with unknown address"));
+        let opened = remote::connect(&Address::from(path))
+            .and_then(|(mut device, _)| remote::open(&mut device, Channels::CORE, false));
+        match opened {
+            Ok(()) => report(
+                Status::Ok,
+                tr!(
+                    "doctor-remote-opened",
+                    "Wii Remote {name} can be opened",
+                    name = name
+                ),
+                None,
+            ),
+            Err(err) => {
+                let hint = matches!(err, ConnectionError::PermissionDenied(_)).then(device_hint);
+                report(
+                    Status::Failed,
+                    tr!(
+                        "doctor-remote-failed",
+                        "cannot open Wii Remote {name}: {err}",
+                        name = name,
+                        err = format!("{:#}", anyhow::Error::from(err)),
+                    ),
+                    hint,
+                )
+            }
+        }
+    }
+
+    let exposed = kernel::exposed_devices();
+    if !exposed.is_empty() {
+        report(
+            Status::Warning,
+            tr!(
+                "doctor-kernel-exposed",
+                "the desktop receives the buttons of the kernel devices ({devices})",
+                devices = exposed.join(", "),
+            ),
+            Some(tr!(
+                "doctor-kernel-hint",
+                "hide them with the udev rule below in /lib/udev/rules.d/70-wiimote.rules\n    {rule}",
+                rule = "SUBSYSTEM==\"input\", ATTRS{name}==\"Nintendo Wii Remote*\", \
+                        ENV{LIBINPUT_IGNORE_DEVICE}=\"1\"",
+            )),
+        );
+    }
+
+    if failed > 0 {
+        bail!(tr!(
+            "doctor-failed",
+            "{failed} check(s) failed",
+            failed = failed
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use tracing::warn;
+use wiinote_core::import::{Format, Import};
+
+/// Prints the configuration sections translated from the GlovePIE
+/// script or WiinUSoft profile, in the given format or the one of its
+/// extension. The assignments without an equivalent are logged too.
+pub fn import(file: &Path, format: Option<Format>) -> Result<()> {
+    let format = format
+        .or_else(|| Format::from_extension(&file.to_string_lossy()))
+        .context("unknown file format, set --format")?;
+    let contents =
+        std::fs::read_to_string(file).with_context(|| format!("cannot read {}", file.display()))?;
+    let import = Import::parse(format, &contents)?;
+    for skipped in import.skipped() {
+        warn!("Skipped {}", skipped);
+    }
+    print!("{}", import.to_toml());
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use crate::webhook::escape;
+use anyhow::{Context, Result};
+use futures_util::stream::TryStreamExt;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+use tracing::info;
+use wiinote_core::monitor;
+use xwiimote::event::{Event, EventKind};
+use xwiimote::{Address, Device};
+
+/// Prints every event of the device at the given path (by default,
+/// the first connected one) until it disconnects, either as text
+/// with the time since the first event, or as JSON lines.
+pub async fn inspect(path: Option<PathBuf>, json: bool) -> Result<()> {
+    let path = match path {
+        Some(path) => path,
+        None => monitor::connected_devices()?
+            .into_iter()
+            .next()
+            .context("no connected devices found")?,
+    };
+    let mut device = Device::connect(&Address::from(path.clone()))?;
+    info!("Monitoring {} ({})", path.display(), device.kind()?);
+
+    let mut start = None;
+    loop {
+        // Also opens the channel of a newly plugged extension.
+        let channels = device.available() - device.opened();
+        device.open(channels, false)?;
+        let mut event_stream = device.events()?;
+        let mut plugged = false;
+        while let Some(event) = event_stream.try_next().await? {
+            let start = *start.get_or_insert(event.time);
+            if json {
+                println!("{}", to_json(&event));
+            } else {
+                let elapsed = event.time.duration_since(start).unwrap_or_default();
+                println!("{:>10.3}  {:?}", elapsed.as_secs_f64(), event.kind);
+            }
+            if let EventKind::Watch = event.kind {
+                plugged = true;
+                break;
+            }
+        }
+        if !plugged {
+            return Ok(()); // disconnected
+        }
+    }
+}
+
+/// Formats the event as a JSON object, with the UNIX time in seconds.
+/// The values of the events without a specific format are given by
+/// their debug representation.
+fn to_json(event: &Event) -> String {
+    let time = event
+        .time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let debug = format!("{:?}", event.kind);
+    // The variant name, e.g. `Key` in `Key(A, Down)`.
+    let name = debug.split(['(', ' ']).next().unwrap_or_default();
+    let values = match &event.kind {
+        EventKind::Key(key, state)
+        | EventKind::NunchukKey(key, state)
+        | EventKind::ClassicControllerKey(key, state)
+        | EventKind::ProControllerKey(key, state)
+        | EventKind::DrumsKey(key, state)
+        | EventKind::GuitarKey(key, state) => {
+            format!(r#""key":"{:?}","state":"{:?}""#, key, state)
+        }
+        EventKind::Accelerometer { x, y, z } | EventKind::MotionPlus { x, y, z } => {
+            format!(r#""x":{},"y":{},"z":{}"#, x, y, z)
+        }
+        EventKind::Ir(sources) => {
+            let sources: Vec<_> = sources
+                .iter()
+                .map(|source| match source {
+                    Some(source) => format!("[{},{}]", source.x, source.y),
+                    None => "null".to_owned(),
+                })
+                .collect();
+            format!(r#""sources":[{}]"#, sources.join(","))
+        }
+        EventKind::BalanceBoard(cells) => format!(
+            r#""cells":[{},{},{},{}]"#,
+            cells[0], cells[1], cells[2], cells[3]
+        ),
+        _ => format!(r#""value":"{}""#, escape(&debug)),
+    };
+    format!(r#"{{"time":{:.3},"event":"{}",{}}}"#, time, name, values)
+}
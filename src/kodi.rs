@@ -0,0 +1,324 @@
+use crate::webhook::escape;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use wiinote_core::config::KodiConfig;
+use wiinote_core::mapping::Button;
+use xwiimote::event::{EventKind, KeyState};
+
+/// The maximum time a single request may take.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The default actions of the buttons: the navigation of the Kodi
+/// remote, and the volume on Plus and Minus.
+const DEFAULT_ACTIONS: [(Button, &str); 9] = [
+    (Button::Up, "up"),
+    (Button::Down, "down"),
+    (Button::Left, "left"),
+    (Button::Right, "right"),
+    (Button::A, "select"),
+    (Button::B, "back"),
+    (Button::Plus, "volumeup"),
+    (Button::Minus, "volumedown"),
+    (Button::Home, "Input.Home"),
+];
+
+/// Controls Kodi with the buttons of a Wii Remote, sending JSON-RPC
+/// requests over HTTP, so that Kodi may run on another machine than
+/// the Bluetooth adapter.
+///
+/// A button runs its action when pressed and as it auto-repeats. The
+/// requests are sent in order, in the background, over a connection
+/// kept alive between them. Failures are logged and the request
+/// dropped.
+pub struct Kodi {
+    actions: HashMap<Button, String>,
+    queue: mpsc::UnboundedSender<String>,
+    /// The identifier of the next request.
+    id: u64,
+}
+
+impl Kodi {
+    pub fn new(config: KodiConfig) -> Result<Self> {
+        let endpoint = Endpoint::parse(&config)?;
+        let mut actions: HashMap<_, _> = DEFAULT_ACTIONS
+            .iter()
+            .map(|(button, action)| (*button, action.to_string()))
+            .collect();
+        actions.extend(config.actions.clone());
+        let (queue, mut requests) = mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            let mut connection = None;
+            // Only warn once until a request goes through.
+            let mut failing = false;
+            while let Some(request) = requests.recv().await {
+                match endpoint.send(&mut connection, &request).await {
+                    Ok(()) => failing = false,
+                    Err(err) if failing => debug!("Cannot send the Kodi request: {}", err),
+                    Err(err) => {
+                        warn!("Cannot send the Kodi request to {}: {}", config.url, err);
+                        failing = true;
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            actions,
+            queue,
+            id: 0,
+        })
+    }
+
+    /// Sends the request of the button pressed by the event, if any.
+    pub fn update(&mut self, event: &EventKind) {
+        let action = match event {
+            EventKind::Key(key, KeyState::Down | KeyState::AutoRepeat) => {
+                match Button::from_key(key).and_then(|button| self.actions.get(&button)) {
+                    Some(action) => action,
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+        let (method, params) = if action.starts_with("Input.") {
+            (action.as_str(), "{}".to_owned())
+        } else {
+            (
+                "Input.ExecuteAction",
+                format!(r#"{{"action":"{}"}}"#, escape(action)),
+            )
+        };
+        self.id += 1;
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#,
+            self.id,
+            escape(method),
+            params
+        );
+        let _ = self.queue.send(request);
+    }
+}
+
+/// The JSON-RPC endpoint of Kodi, only reachable over plain HTTP.
+pub struct Endpoint {
+    /// The host and port to connect to.
+    authority: String,
+    path: String,
+    /// The value of the `Authorization` header, if any.
+    authorization: Option<String>,
+}
+
+impl Endpoint {
+    /// Parses the URL and credentials of the configuration.
+    pub fn parse(config: &KodiConfig) -> Result<Self> {
+        let rest = config
+            .url
+            .strip_prefix("http://")
+            .ok_or_else(|| anyhow!("the Kodi URL must start with http://"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(ix) => rest.split_at(ix),
+            None => (rest, "/"),
+        };
+        ensure!(!authority.is_empty(), "the Kodi URL has no host");
+        let authority = match authority.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => authority.to_owned(),
+            // IPv6 addresses are in brackets.
+            _ if authority.ends_with(']') || !authority.contains(':') => {
+                format!("{}:80", authority)
+            }
+            _ => bail!("invalid Kodi URL port"),
+        };
+        let authorization = config.username.as_ref().map(|username| {
+            let password = config.password.as_deref().unwrap_or_default();
+            format!(
+                "Basic {}",
+                base64(format!("{}:{}", username, password).as_bytes())
+            )
+        });
+        Ok(Self {
+            authority,
+            path: path.to_owned(),
+            authorization,
+        })
+    }
+
+    /// Posts the request, reusing the connection if still open. A
+    /// reused connection may have been closed by Kodi meanwhile, so
+    /// the request is retried once on a new one.
+    async fn send(
+        &self,
+        connection: &mut Option<BufReader<TcpStream>>,
+        request: &str,
+    ) -> Result<()> {
+        let mut response = None;
+        if let Some(stream) = connection.as_mut() {
+            match tokio::time::timeout(TIMEOUT, self.post(stream, request)).await {
+                Ok(Ok(answer)) => response = Some(answer),
+                Ok(Err(err)) => debug!("Kodi connection closed, reconnecting: {:#}", err),
+                Err(_) => debug!("Kodi connection timed out, reconnecting"),
+            }
+        }
+        let (status, keep_alive) = match response {
+            Some(response) => response,
+            None => {
+                *connection = None;
+                let exchange = async {
+                    let stream = TcpStream::connect(&self.authority).await?;
+                    stream.set_nodelay(true)?;
+                    let mut stream = BufReader::new(stream);
+                    let response = self.post(&mut stream, request).await?;
+                    Ok::<_, anyhow::Error>((stream, response))
+                };
+                let (stream, response) = tokio::time::timeout(TIMEOUT, exchange)
+                    .await
+                    .map_err(|_| anyhow!("timed out"))??;
+                *connection = Some(stream);
+                response
+            }
+        };
+        if !keep_alive {
+            *connection = None;
+        }
+        ensure!((200..300).contains(&status), "Kodi answered {}", status);
+        Ok(())
+    }
+
+    /// Posts the request and reads the response, returning its status
+    /// and whether the connection may be reused.
+    async fn post(&self, stream: &mut BufReader<TcpStream>, request: &str) -> Result<(u16, bool)> {
+        let mut head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+            self.path,
+            self.authority,
+            request.len()
+        );
+        if let Some(authorization) = &self.authorization {
+            head.push_str(&format!("Authorization: {}\r\n", authorization));
+        }
+        head.push_str("\r\n");
+        head.push_str(request);
+        stream.get_mut().write_all(head.as_bytes()).await?;
+
+        let mut line = String::new();
+        ensure!(stream.read_line(&mut line).await? > 0, "connection closed");
+        let status = line
+            .split(' ')
+            .nth(1)
+            .and_then(|status| status.parse::<u16>().ok())
+            .context("malformed HTTP response")?;
+        let (mut length, mut chunked, mut keep_alive) = (None, false, true);
+        loop {
+            line.clear();
+            ensure!(stream.read_line(&mut line).await? > 0, "connection closed");
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                let value = value.trim();
+                match name.to_ascii_lowercase().as_str() {
+                    "content-length" => length = value.parse::<u64>().ok(),
+                    "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+                    "connection" => keep_alive = !value.eq_ignore_ascii_case("close"),
+                    _ => {}
+                }
+            }
+        }
+        // The body is discarded, Kodi reports errors in the status.
+        if chunked {
+            loop {
+                line.clear();
+                stream.read_line(&mut line).await?;
+                let size = line.trim_end().split(';').next().unwrap_or_default();
+                let size = u64::from_str_radix(size, 16).context("malformed HTTP chunk")?;
+                // The chunk and its line break, or the (empty) trailer.
+                skip(stream, size).await?;
+                line.clear();
+                stream.read_line(&mut line).await?;
+                if size == 0 {
+                    break;
+                }
+            }
+        } else if let Some(length) = length {
+            skip(stream, length).await?;
+        } else {
+            // Delimited by the end of the connection.
+            skip(stream, u64::MAX).await?;
+            keep_alive = false;
+        }
+        Ok((status, keep_alive))
+    }
+}
+
+/// Reads and discards up to `len` bytes.
+async fn skip(stream: &mut BufReader<TcpStream>, len: u64) -> Result<()> {
+    tokio::io::copy(&mut stream.take(len), &mut tokio::io::sink()).await?;
+    Ok(())
+}
+
+/// Encodes the bytes in Base64, with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0, |group, (ix, &byte)| {
+            group | (byte as u32) << (16 - 8 * ix)
+        });
+        for ix in 0..4 {
+            if ix <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * ix) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(url: &str) -> Result<Endpoint> {
+        let config = format!(
+            "url = {:?}\nusername = \"kodi\"\npassword = \"secret\"",
+            url
+        );
+        Endpoint::parse(&toml::from_str(&config).unwrap())
+    }
+
+    #[test]
+    fn parses_the_endpoint() {
+        let parsed = endpoint("http://htpc.local:8080/jsonrpc").unwrap();
+        assert_eq!(parsed.authority, "htpc.local:8080");
+        assert_eq!(parsed.path, "/jsonrpc");
+        assert_eq!(
+            parsed.authorization.as_deref(),
+            Some("Basic a29kaTpzZWNyZXQ=")
+        );
+        let parsed = endpoint("http://[::1]").unwrap();
+        assert_eq!(
+            (parsed.authority.as_str(), parsed.path.as_str()),
+            ("[::1]:80", "/")
+        );
+        assert!(endpoint("https://htpc.local/jsonrpc").is_err());
+        assert!(endpoint("http://htpc.local:http/jsonrpc").is_err());
+    }
+
+    #[test]
+    fn encodes_base64_with_padding() {
+        for (bytes, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+        ] {
+            assert_eq!(base64(bytes.as_bytes()), encoded);
+        }
+    }
+}
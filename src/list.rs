@@ -0,0 +1,136 @@
+use crate::webhook::escape;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use wiinote_core::monitor;
+use wiinote_core::pairing;
+use xwiimote::{Address, Device};
+
+/// A Wii Remote (or another Nintendo device) found by [`list`].
+struct Entry {
+    /// The Bluetooth address, if known.
+    address: Option<String>,
+    /// The sysfs path, if bound to the `hid-wiimote` driver.
+    path: Option<PathBuf>,
+    kind: &'static str,
+    /// The battery percentage, if connected.
+    battery: Option<u8>,
+    /// The plugged extension, if connected.
+    extension: Option<String>,
+}
+
+/// Prints the connected devices, and the ones known to BlueZ (paired,
+/// or found by a recent scan) that aren't connected, as a table or
+/// as a JSON array.
+pub async fn list(json: bool) -> Result<()> {
+    let mut entries = Vec::new();
+    for path in monitor::connected_devices()? {
+        let device = Device::connect(&Address::from(path.clone()))?;
+        entries.push(Entry {
+            address: monitor::bluetooth_address(&path),
+            kind: kind_name(&device.kind()?),
+            battery: Some(device.battery()?),
+            extension: Some(device.extension()?),
+            path: Some(path),
+        });
+    }
+
+    // The devices discovered by BlueZ, if available.
+    match pairing::known_remotes().await {
+        Ok(remotes) => {
+            for (address, name) in remotes {
+                let connected = entries.iter().any(|entry| {
+                    entry
+                        .address
+                        .as_ref()
+                        .is_some_and(|known| known.eq_ignore_ascii_case(&address))
+                });
+                if !connected {
+                    entries.push(Entry {
+                        address: Some(address),
+                        path: None,
+                        kind: model_name(&name),
+                        battery: None,
+                        extension: None,
+                    });
+                }
+            }
+        }
+        Err(err) => debug!("Cannot list the BlueZ devices: {}", err),
+    }
+
+    if json {
+        print_json(&entries);
+    } else {
+        print_table(&entries);
+    }
+    Ok(())
+}
+
+fn print_table(entries: &[Entry]) {
+    println!(
+        "{:<17}  {:<15}  {:<7}  {:<10}  PATH",
+        "ADDRESS", "TYPE", "BATTERY", "EXTENSION"
+    );
+    for entry in entries {
+        println!(
+            "{:<17}  {:<15}  {:<7}  {:<10}  {}",
+            entry.address.as_deref().unwrap_or("-"),
+            entry.kind,
+            entry
+                .battery
+                .map_or_else(|| "-".to_owned(), |level| format!("{}%", level)),
+            entry.extension.as_deref().unwrap_or("-"),
+            entry
+                .path
+                .as_ref()
+                .map_or_else(|| "not connected".into(), |path| path.to_string_lossy()),
+        );
+    }
+}
+
+fn print_json(entries: &[Entry]) {
+    let string = |value: Option<&str>| match value {
+        Some(value) => format!(r#""{}""#, escape(value)),
+        None => "null".to_owned(),
+    };
+    let objects: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                r#"{{"address":{},"path":{},"type":"{}","battery":{},"extension":{}}}"#,
+                string(entry.address.as_deref()),
+                string(entry.path.as_deref().map(Path::to_string_lossy).as_deref()),
+                entry.kind,
+                entry
+                    .battery
+                    .map_or_else(|| "null".to_owned(), |level| level.to_string()),
+                string(entry.extension.as_deref()),
+            )
+        })
+        .collect();
+    println!("[{}]", objects.join(","));
+}
+
+/// Returns the name of the device type reported by xwiimote.
+fn kind_name(kind: &str) -> &'static str {
+    match kind {
+        "gen10" => "Wii Remote",
+        "gen20" => "Wii Remote Plus",
+        "balanceboard" => "Balance Board",
+        "procontroller" => "Pro Controller",
+        _ => "unknown",
+    }
+}
+
+/// Returns the device type from its Bluetooth name, e.g.
+/// `Nintendo RVL-CNT-01-TR`.
+fn model_name(name: &str) -> &'static str {
+    match name.trim_start_matches("Nintendo ") {
+        "RVL-CNT-01" => "Wii Remote",
+        "RVL-CNT-01-TR" => "Wii Remote Plus",
+        "RVL-WBC-01" => "Balance Board",
+        "RVL-CNT-01-UC" => "Pro Controller",
+        _ => "unknown",
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt::Display;
+
+/// Formats a user-facing message in the language of the user, falling
+/// back to its English text, e.g.
+/// `tr!("doctor-open-failed", "cannot open {path}: {err}", path = path, err = err)`.
+///
+/// The identifier names the message in the catalogs of `locales/`, and
+/// its arguments are the variables of both the catalogs and the text.
+macro_rules! tr {
+    ($id:literal, $text:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        // Evaluates each argument once, like `format!`.
+        match ($(&$value,)*) {
+            ($($name,)*) => $crate::locale::translate(
+                $id,
+                &[$((stringify!($name), $name as &dyn std::fmt::Display)),*],
+            )
+            .unwrap_or_else(|| format!($text $(, $name = $name)*)),
+        }
+    };
+}
+
+/// The translations, by language.
+#[cfg(feature = "l10n")]
+const CATALOGS: [(&str, &str); 1] = [("es", include_str!("../locales/es.ftl"))];
+
+/// Returns the message in the language of the user, or `None` if it
+/// has no translation.
+#[cfg(feature = "l10n")]
+pub fn translate(id: &str, args: &[(&str, &dyn Display)]) -> Option<String> {
+    use fluent_bundle::{FluentArgs, FluentBundle, FluentResource};
+
+    // The bundles are not `Sync`, build one per thread.
+    thread_local! {
+        static BUNDLE: Option<FluentBundle<FluentResource>> = language().and_then(bundle);
+    }
+    BUNDLE.with(|bundle| {
+        let bundle = bundle.as_ref()?;
+        let pattern = bundle.get_message(id)?.value()?;
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, value.to_string());
+        }
+        let mut errors = Vec::new();
+        let message = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+        errors.is_empty().then(|| message.into_owned())
+    })
+}
+
+#[cfg(not(feature = "l10n"))]
+pub fn translate(_id: &str, _args: &[(&str, &dyn Display)]) -> Option<String> {
+    None
+}
+
+/// Returns the language of the messages, from the locale environment
+/// variables in the order of precedence of gettext.
+#[cfg(feature = "l10n")]
+fn language() -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())?;
+    // e.g. `es_ES.UTF-8`, `C` and `POSIX` are English.
+    let language = locale.split(['_', '.', '@']).next()?;
+    (language != "C" && language != "POSIX").then(|| language.to_owned())
+}
+
+/// Returns the catalog of the language, if it's translated.
+#[cfg(feature = "l10n")]
+fn bundle(language: String) -> Option<fluent_bundle::FluentBundle<fluent_bundle::FluentResource>> {
+    use fluent_bundle::{FluentBundle, FluentResource};
+
+    let (_, source) = CATALOGS.iter().find(|(name, _)| *name == language)?;
+    let resource = FluentResource::try_new(source.to_string()).ok()?;
+    let mut bundle = FluentBundle::new(vec![language.parse().ok()?]);
+    // The Unicode isolation marks show up in terminals.
+    bundle.set_use_isolating(false);
+    bundle.add_resource(resource).ok()?;
+    Some(bundle)
+}
+
+#[cfg(all(test, feature = "l10n"))]
+mod tests {
+    use super::*;
+    use fluent_bundle::FluentResource;
+
+    #[test]
+    fn parses_the_catalogs() {
+        for (language, source) in CATALOGS {
+            if let Err((_, errors)) = FluentResource::try_new(source.to_owned()) {
+                panic!("invalid {} catalog: {:?}", language, errors);
+            }
+            assert!(bundle(language.to_owned()).is_some(), "{}", language);
+        }
+    }
+
+    #[test]
+    fn formats_the_arguments() {
+        let bundle = bundle("es".to_owned()).unwrap();
+        let pattern = bundle
+            .get_message("doctor-failed")
+            .unwrap()
+            .value()
+            .unwrap();
+        let mut args = fluent_bundle::FluentArgs::new();
+        args.set("failed", 2.to_string());
+        let message = bundle.format_pattern(pattern, Some(&args), &mut Vec::new());
+        assert_eq!(message, "2 comprobación(es) fallida(s)");
+    }
+}
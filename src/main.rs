@@ -1,175 +1,2575 @@
-mod keyboard;
+#[macro_use]
+mod locale;
 
-use crate::keyboard::Keyboard;
-use anyhow::Result;
+mod audio;
+mod bridge;
+mod calibrate;
+mod control;
+mod doctor;
+mod import;
+mod inspect;
+mod kodi;
+mod list;
+#[cfg(feature = "midi")]
+mod midi;
+mod mqtt;
+mod notification;
+mod opentrack;
+mod realtime;
+mod record;
+mod reload;
+mod speaker;
+mod systemd;
+mod webhook;
+#[cfg(feature = "websocket")]
+mod websocket;
+
+use crate::audio::{AudioCues, Cue};
+use crate::bridge::{BridgeClient, BridgeServer, NetworkRemote};
+use crate::control::Request;
+use crate::kodi::Kodi;
+use crate::mqtt::Mqtt;
+use crate::opentrack::Opentrack;
+use crate::webhook::Webhooks;
+use anyhow::{anyhow, Result};
 use clap::Parser;
 use futures_util::stream::TryStreamExt;
 use num_traits::FromPrimitive;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
 use std::time::Duration;
-use xwiimote::event::{Event, EventKind, Key};
-use xwiimote::{Address, Channels, Device, Led, Monitor};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::{JoinHandle, LocalSet};
+use tokio::time::Instant;
+use tracing::{debug, error, field, info, info_span, warn, Instrument, Span};
+use tracing_subscriber::EnvFilter;
+use uinput::event::controller::Mouse;
+use wiinote_core::absolute::AbsolutePointer;
+use wiinote_core::adapter::AdapterWatch;
+use wiinote_core::balance::{BalanceBoard, BalanceOutput};
+use wiinote_core::battery::DischargeModel;
+use wiinote_core::bridge::Frame;
+use wiinote_core::budget::Budget;
+use wiinote_core::combinator::Combinator;
+use wiinote_core::config::{Click, Config, WebhookEvent};
+use wiinote_core::display::{LightDisplay, LightsMetric};
+use wiinote_core::dolphin::DolphinDevice;
+use wiinote_core::error::ConnectionError;
+use wiinote_core::extension::{Extension, ExtensionDevice};
+use wiinote_core::fall::FallDetector;
+use wiinote_core::feedback::Feedback;
+use wiinote_core::gesture::{GestureRecognizer, ShakeDetector};
+use wiinote_core::idle::{IdleState, IdleTimer};
+use wiinote_core::keyboard::{Keyboard, Recorder, RemoteAction};
+use wiinote_core::mapping::Button;
+#[cfg(feature = "midi")]
+use wiinote_core::midi::MidiMapper;
+use wiinote_core::monitor::{DeviceWatcher, MonitorKind};
+use wiinote_core::numpad::Numpad;
+use wiinote_core::output::{Events, Output};
+use wiinote_core::players::{Player, Players};
+use wiinote_core::pointer::Pointer;
+use wiinote_core::remote::{Remote, ScriptedRemote};
+use wiinote_core::scenario::{Outcome, Scenario};
+use wiinote_core::speaker::{Sound, SoundPlayer};
+use wiinote_core::stationary::StationaryGuard;
+use wiinote_core::stats::{EventStats, IgnoreReason};
+use wiinote_core::steering::{Steering, Turn};
+use wiinote_core::stick::{Nudge, Stick};
+use wiinote_core::{
+    adapter, feedback, idle, kernel, mapping, monitor, output, pairing, remote, screen,
+};
+use xwiimote::event::{Event, EventKind, Key, KeyState};
+use xwiimote::{Address, Channels, Device, Led};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    /// Searches and connects to a Wii Remote placed in discoverable
-    /// mode after failing to connect to an already plugged-in
-    /// Wii Remote.
-    ///
-    /// If the connection is dropped, the program restarts the
-    /// discovery session until a new Wii Remote is found.
+    /// Keeps searching for Wii Remotes placed in discoverable mode
+    /// after connecting to the plugged-in ones, so more players can
+    /// join at any time. Each Wii Remote gets its own virtual keyboard.
     ///
-    /// When not set, the program exits if no plugged-in Wii Remote
-    /// is found.
+    /// When not set, the program connects to the plugged-in Wii Remotes
+    /// and exits once all of them disconnect.
     #[clap(long, takes_value = false)]
     discover: bool,
+    /// Runs as a service: implies `--discover`, and tells systemd once
+    /// ready to connect to Wii Remotes (for `Type=notify` units).
+    /// `SIGTERM` and `SIGINT` (Ctrl+C) disconnect the Wii Remotes
+    /// gracefully in any mode, turning their lights off and releasing
+    /// the pressed keys.
+    #[clap(long, takes_value = false)]
+    daemon: bool,
+    /// The mechanism used to find Wii Remotes, either `udev` or
+    /// `poll:<interval>` (e.g. `poll:2s`).
+    ///
+    /// Polling scans the sysfs entries of the `hid-wiimote` driver
+    /// periodically, for systems where udev is not available
+    /// (containers, some embedded distributions).
+    #[clap(long, default_value = "udev", value_name = "KIND")]
+    monitor: MonitorKind,
     /// Opens the Wii Remote device at the given location.
     ///
-    /// If not present, connects to the first Wii Remote found;
+    /// If not present, connects to every Wii Remote found;
     /// see the `--discover` option for more.
     #[clap(parse(from_os_str), value_name = "FILE")]
     device: Option<PathBuf>,
+    /// Reads the configuration from the given file.
+    ///
+    /// Defaults to `$XDG_CONFIG_HOME/wiinote/config.toml`, if present.
+    #[clap(long, parse(from_os_str), value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Only connects to the Wii Remote with the given Bluetooth address
+    /// (e.g. `00:1F:32:AA:BB:CC`) when discovering or pairing. Can be
+    /// repeated, and adds to the `allow` list of the configuration.
+    #[clap(long, value_name = "ADDRESS", multiple_occurrences = true)]
+    allow: Vec<String>,
+    /// Never connects to the Wii Remote with the given Bluetooth address
+    /// when discovering or pairing. Can be repeated, and adds to the
+    /// `ignore` list of the configuration.
+    #[clap(long, value_name = "ADDRESS", multiple_occurrences = true)]
+    ignore: Vec<String>,
+    /// Locks the process memory and handles input with real-time
+    /// priority, reducing the latency jitter of the emitted keys.
+    ///
+    /// Requires the `CAP_IPC_LOCK` and `CAP_SYS_NICE` capabilities
+    /// (or the matching resource limits); if missing, the program
+    /// continues with the default scheduling policy.
+    #[clap(long, takes_value = false)]
+    realtime: bool,
+    /// Exposes the plugged extension (Nunchuk or Classic Controller)
+    /// as a separate virtual device named after it, as expected by
+    /// some emulators and remapping tools.
+    #[clap(long, takes_value = false)]
+    extension_devices: bool,
+    /// Sends the media Pause key when the Wii Remote disconnects or
+    /// the connection drops (e.g. if the batteries die), so playback
+    /// doesn't continue without a controller.
+    #[clap(long, takes_value = false)]
+    pause_on_disconnect: bool,
+    /// Retries connecting to a Wii Remote and opening its channels
+    /// with exponential backoff when it fails, instead of giving up;
+    /// e.g. right after pairing, when the device isn't ready yet.
+    #[clap(long, takes_value = false)]
+    retry: bool,
+    /// The number of retries of each step with `--retry`.
+    #[clap(long, default_value = "5", value_name = "COUNT")]
+    max_retries: u32,
+    /// Pairs the Wii Remotes put in pairing mode with the red sync
+    /// button (using `bluetoothctl`) before connecting, so they
+    /// reconnect on their own when any button is pressed afterwards.
+    #[clap(long, takes_value = false)]
+    pair: bool,
+    /// Detects when the Wii Remote is dropped (a free fall followed
+    /// by an impact) from the accelerometer readings. Drops are logged
+    /// and counted in the connection summary.
+    #[clap(long, takes_value = false)]
+    drop_detection: bool,
+    /// Adapts the shake detection to how hard the user shakes the
+    /// Wii Remote, over the connection.
+    #[clap(long, takes_value = false)]
+    adaptive_gestures: bool,
+    /// Binds the buttons like a presentation clicker: A and Right go to
+    /// the next slide, B and Left to the previous one, and holding B
+    /// moves the pointer with the IR camera (pointed at a sensor bar)
+    /// to highlight parts of the slide.
+    #[clap(long, takes_value = false)]
+    presentation: bool,
+    /// Pulses the rumble motor when the Wii Remote connects, runs low
+    /// on battery, or switches the mouse mode. Switching profiles plays
+    /// one pulse per profile number, e.g. three for the third profile.
+    #[clap(long, takes_value = false)]
+    rumble: bool,
+    /// Tracks the IR LEDs of a head tracking cap or clip with the
+    /// camera of the Wii Remotes (placed on the monitor, facing the
+    /// user), sending the head pose to the opentrack "UDP over network"
+    /// input at the given address (e.g. `127.0.0.1:4242`).
+    #[clap(long, value_name = "ADDRESS")]
+    head_tracking: Option<SocketAddr>,
+    /// Where the measurements of a connected Balance Board are
+    /// published: `axes` exposes a virtual device with the center of
+    /// mass on the X/Y axes and the total weight (in 10g units) on the
+    /// Z axis, `json` prints an object per measurement.
+    #[clap(long, default_value = "axes", value_name = "OUTPUT")]
+    balance_board: BalanceOutput,
+    /// Where the input of the Wii Remotes goes: `keyboard` emits the
+    /// mapped keys from virtual devices, `json` prints an object per
+    /// button press, battery level change or extension change instead,
+    /// for other programs to consume, and `dolphin` exposes the
+    /// unmapped buttons, accelerometer and IR camera as a virtual
+    /// gamepad for the Dolphin emulator. `midi` sends the buttons as
+    /// notes and the tilt and IR pointer as continuous controllers from
+    /// an ALSA sequencer port, as set in the `[midi]` configuration
+    /// section (requires the `midi` feature). `kodi` sends the button
+    /// presses to the Kodi instance of the `[kodi]` configuration
+    /// section, possibly on another machine.
+    #[clap(long, default_value = "keyboard", value_name = "OUTPUT")]
+    output: Output,
+    /// Scopes the names of the virtual devices to the given instance
+    /// name, so that several wiinote instances (e.g. with different
+    /// configurations) can run on the same machine without colliding.
+    #[clap(long, value_name = "NAME")]
+    instance: Option<String>,
+    /// Handles the events of a script file or a recording (see
+    /// `wiinote record`) instead of a Wii Remote, e.g. to try out a
+    /// configuration. The keys are emitted as usual.
+    #[clap(long, value_name = "SCRIPT", conflicts_with = "device")]
+    replay: Option<PathBuf>,
+    /// Forwards the buttons and motion of the Wii Remotes connected to
+    /// this machine to the `--remote` client connecting to the given
+    /// address (e.g. `192.168.1.20:7744`), instead of mapping them. The
+    /// accelerometer, IR camera and Motion Plus are always opened, for
+    /// the client to use. The client must know the secret of the
+    /// `[bridge]` configuration section; the traffic isn't encrypted.
+    #[clap(long, value_name = "ADDRESS", conflicts_with = "remote")]
+    serve: Option<SocketAddr>,
+    /// Handles the Wii Remotes connected to the `--serve` server at the
+    /// given address (e.g. `raspberrypi.local:7744`) instead of the
+    /// local ones, reconnecting when the connection drops.
+    #[clap(long, value_name = "HOST:PORT", conflicts_with_all = &["device", "replay"])]
+    remote: Option<String>,
+    /// Logs more details: once for every event that doesn't produce
+    /// any output, with the reason it was ignored (a summary is always
+    /// logged when the Wii Remote disconnects), twice for everything.
+    /// `RUST_LOG` takes precedence, e.g. `RUST_LOG=wiinote=debug`.
+    #[clap(short, long, parse(from_occurrences))]
+    verbose: u8,
+    /// Only logs warnings and errors.
+    #[clap(short, long, takes_value = false, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Plays sounds on the host speakers when a Wii Remote connects,
+    /// disconnects or runs low on battery.
+    #[cfg(feature = "audio")]
+    #[clap(long, takes_value = false)]
+    audio_cues: bool,
+    /// Runs a WebSocket server at the given address (e.g.
+    /// `ws://0.0.0.0:8765`), sending the button presses, motion and
+    /// battery level of the Wii Remotes to the clients as JSON, and
+    /// accepting the requests of `wiinote ctl` (e.g. `rumble 200ms`).
+    #[cfg(feature = "websocket")]
+    #[clap(long, value_name = "URL")]
+    listen: Option<websocket::ListenAddress>,
+    #[clap(subcommand)]
+    command: Option<Subcommand>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Subcommand {
+    /// Sends a request to the running instance (selected by `--instance`)
+    /// through its control socket: `battery`, `status`, `profile <name>`,
+    /// `rumble <duration>` (e.g. `rumble 500ms`) or `lights <lights>`
+    /// (e.g. `lights 14`, `lights off` or `lights auto`).
+    Ctl {
+        #[clap(required = true)]
+        request: Vec<String>,
+    },
+    /// Prints the battery level, active profile and muted state of each
+    /// Wii Remote connected to the running instance.
+    Status,
+    /// Lists the connected Wii Remotes with their battery level and
+    /// plugged extension, and the ones known to BlueZ (paired, or found
+    /// by a recent scan) that aren't connected.
+    List {
+        /// Prints a JSON array instead of a table.
+        #[clap(long, takes_value = false)]
+        json: bool,
+    },
+    /// Checks the setup (kernel modules, access rights to the uinput
+    /// and Wii Remote devices, Bluetooth state), telling how to fix the
+    /// problems found.
+    Doctor,
+    /// Prints every event of a Wii Remote (keys, motion, IR, extensions)
+    /// with its time, to debug a mapping or check the hardware. Other
+    /// programs (e.g. a running wiinote instance) keep receiving them.
+    Monitor {
+        /// The Wii Remote device to monitor, by default the first
+        /// connected one.
+        #[clap(parse(from_os_str), value_name = "FILE")]
+        device: Option<PathBuf>,
+        /// Prints a JSON object per line instead of text.
+        #[clap(long, takes_value = false)]
+        json: bool,
+    },
+    /// Calibrates the `ir-absolute` mouse mode: asks to point the Wii
+    /// Remote at each corner of the screen and press A, then saves the
+    /// result to the configuration file.
+    Calibrate {
+        /// The Wii Remote device, by default the first connected one.
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        device: Option<PathBuf>,
+    },
+    /// Plays a WAV file (8 or 16-bit PCM, at most 4000 Hz) on the speaker
+    /// of a Wii Remote.
+    PlaySound {
+        /// The sound file.
+        #[clap(parse(from_os_str), value_name = "FILE")]
+        sound: PathBuf,
+        /// The Wii Remote device, by default the first connected one.
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        device: Option<PathBuf>,
+    },
+    /// Records the button, motion and IR events of a Wii Remote until it
+    /// disconnects or Ctrl+C is pressed, to replay them later.
+    Record {
+        /// The recording file.
+        #[clap(parse(from_os_str), value_name = "FILE")]
+        file: PathBuf,
+        /// The Wii Remote device, by default the first connected one.
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        device: Option<PathBuf>,
+    },
+    /// Translates the button assignments of a GlovePIE script or a
+    /// WiinUSoft profile into `[bindings]`, `[holds]` and `[[chords]]`
+    /// sections, printed to add to the configuration file. Those without
+    /// an equivalent are listed in comments.
+    Import {
+        /// The script or profile.
+        #[clap(parse(from_os_str), value_name = "FILE")]
+        file: PathBuf,
+        /// The format, `glovepie` or `wiinusoft`, by default guessed
+        /// from the extension (`.PIE`, `.wsp`).
+        #[clap(long)]
+        format: Option<wiinote_core::import::Format>,
+    },
+    /// Replays a recording through the mappings with the configuration,
+    /// like `--replay`.
+    Replay {
+        /// The recording (or script) file.
+        #[clap(parse(from_os_str), value_name = "FILE")]
+        file: PathBuf,
+    },
+    /// Replays the script or recording of a scenario through the
+    /// mappings, without emitting the keys, and checks the outputs
+    /// it expects. Fails if they differ.
+    Simulate {
+        /// The scenario file.
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        scenario: PathBuf,
+    },
+    /// Replays the scenarios over and over, each as a new connection,
+    /// checking their outputs and that the memory use and the number of
+    /// tasks don't grow, e.g. to gate a release. Prints a summary.
+    Soak {
+        /// How long to run, in hours.
+        #[clap(long, default_value = "24", value_name = "HOURS")]
+        hours: f64,
+        /// The scenario files (see `simulate`), using the same configuration.
+        #[clap(
+            long = "scenario",
+            required = true,
+            parse(from_os_str),
+            value_name = "FILE"
+        )]
+        scenarios: Vec<PathBuf>,
+    },
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
-    let args: Args = Args::parse();
+    let mut args: Args = Args::parse();
+    init_logging(&args);
+    if let Some(Subcommand::Replay { file }) = &args.command {
+        args.replay = Some(file.clone());
+        args.command = None;
+    }
+    let mut scenarios = Vec::new();
+    let mut soak_duration = None;
+    match &args.command {
+        Some(Subcommand::Simulate { scenario }) => scenarios.push(Scenario::load(scenario)?),
+        Some(Subcommand::Soak {
+            hours,
+            scenarios: paths,
+        }) => {
+            if !hours.is_finite() || *hours <= 0.0 {
+                return Err(anyhow!(
+                    "the soak test must last a positive number of hours"
+                ));
+            }
+            soak_duration = Some(Duration::from_secs_f64(hours * 3600.0));
+            for path in paths {
+                scenarios.push(Scenario::load(path)?);
+            }
+        }
+        _ => {}
+    }
+    if let Some(first) = scenarios.first() {
+        let config = first.config();
+        if scenarios.iter().any(|scenario| scenario.config() != config) {
+            return Err(anyhow!("the scenarios must use the same configuration"));
+        }
+        if let Some(config) = config {
+            args.config = Some(config.to_owned());
+        }
+        args.command = None;
+    }
+    if let Some(command) = &args.command {
+        let request = match command {
+            Subcommand::List { json } => return list::list(*json).await,
+            Subcommand::Doctor => return doctor::doctor().await,
+            Subcommand::Monitor { device, json } => {
+                return inspect::inspect(device.clone(), *json).await
+            }
+            Subcommand::Calibrate { device } => {
+                return calibrate::calibrate(device.clone(), args.config.clone()).await
+            }
+            Subcommand::PlaySound { sound, device } => {
+                return speaker::play(device.clone(), sound).await
+            }
+            Subcommand::Record { file, device } => {
+                return record::record(device.clone(), file).await
+            }
+            Subcommand::Import { file, format } => return import::import(file, *format),
+            Subcommand::Replay { .. } => unreachable!("handled as --replay"),
+            Subcommand::Simulate { .. } | Subcommand::Soak { .. } => {
+                unreachable!("handled before")
+            }
+            Subcommand::Ctl { request } => request.join(" "),
+            Subcommand::Status => "status".to_owned(),
+        };
+        let path = control::socket_path(args.instance.as_deref())?;
+        return control::send(&path, &request).await;
+    }
 
-    println!("Opening keyboard device");
-    let mut keyboard = Keyboard::try_default()?;
+    #[cfg(not(feature = "midi"))]
+    if args.output == Output::Midi {
+        return Err(anyhow!("built without the `midi` feature"));
+    }
+
+    if args.realtime {
+        // The runtime is single-threaded, so this also covers the
+        // thread emitting the keyboard events.
+        realtime::enable();
+    }
 
-    if let Some(path) = args.device {
-        let address = Address::from(path);
-        connect(&address, &mut keyboard).await?
+    let overrides = args.config_overrides();
+    let mut config = Config::load(args.config.as_deref())?;
+    overrides(&mut config);
+    if args.output == Output::Kodi {
+        let kodi = config
+            .kodi
+            .as_ref()
+            .ok_or_else(|| anyhow!("`--output kodi` requires a `[kodi]` configuration section"))?;
+        kodi::Endpoint::parse(kodi)?;
+    }
+    if (args.serve.is_some() || args.remote.is_some()) && config.bridge.is_none() {
+        return Err(anyhow!(
+            "`--serve` and `--remote` require a `[bridge]` configuration section with the shared secret"
+        ));
+    }
+    let config = Arc::new(config);
+    let (configs, reloads) = watch::channel(config.clone());
+    tokio::spawn(reload::watch(args.config.clone(), overrides, configs));
+    // Clicks are enabled by the profiles at startup, the ones enabled
+    // by a reload are only played if the output is already open.
+    let host_clicks = mapping::profiles(&config)
+        .iter()
+        .any(|profile| profile.click == Click::Host);
+    #[cfg(feature = "audio")]
+    let cues = if args.audio_cues || host_clicks {
+        AudioCues::try_default(args.audio_cues)?
     } else {
-        while let Some(address) = find_device(args.discover).await? {
-            connect(&address, &mut keyboard).await?;
+        AudioCues::disabled()
+    };
+    #[cfg(not(feature = "audio"))]
+    let cues = {
+        if host_clicks {
+            warn!("Built without the `audio` feature, the host clicks are silent");
+        }
+        AudioCues::disabled()
+    };
+    #[cfg(feature = "websocket")]
+    let events = Events::new(args.listen.is_some());
+    #[cfg(not(feature = "websocket"))]
+    let events = Events::new(false);
+    let opentrack = args.head_tracking.map(Opentrack::new).transpose()?;
+    let bridge = match args.serve {
+        Some(address) => {
+            let secret = &config
+                .bridge
+                .as_ref()
+                .expect("no bridge configuration")
+                .secret;
+            Some(BridgeServer::bind(address, secret).await?)
+        }
+        None => None,
+    };
+    let context = Rc::new(Context {
+        opentrack,
+        bridge,
+        combinator: (!config.cross_chords.is_empty())
+            .then(|| Rc::new(Combinator::new(config.cross_chords.clone()))),
+        budget: config
+            .budget
+            .as_ref()
+            .map(|budget| Rc::new(Budget::new(budget))),
+        webhooks: Webhooks::new(config.webhooks.clone()),
+        mqtt: Mqtt::new(config.mqtt.clone()),
+        shutdown: watch::channel(false).0,
+        commands: broadcast::channel(16).0,
+        adapter: AdapterWatch::spawn(),
+        events,
+        args,
+        config,
+        cues,
+        players: Players::new(),
+        reloads,
+    });
+
+    if let Some(duration) = soak_duration {
+        return soak(&scenarios, duration, &context).await;
+    }
+    if let Some(scenario) = scenarios.first() {
+        return simulate(scenario, &context).await;
+    }
+
+    let instance = context.args.instance.clone();
+    let commands = context.commands.clone();
+    tokio::spawn(async move {
+        let result = match control::socket_path(instance.as_deref()) {
+            Ok(path) => control::listen(&path, commands).await,
+            Err(err) => Err(err),
+        };
+        // The socket is optional, keep running without it.
+        if let Err(err) = result {
+            error!("Control socket error: {:#}", err);
+        }
+    });
+
+    #[cfg(feature = "websocket")]
+    if let (Some(address), Some(events)) = (context.args.listen, context.events.sender()) {
+        let commands = context.commands.clone();
+        tokio::spawn(async move {
+            if let Err(err) = websocket::serve(address, events, commands).await {
+                error!("WebSocket server error: {:#}", err);
+            }
+        });
+    }
+
+    if context.args.pair {
+        let accept = |address: &str| context.config.accepts(Some(address));
+        pairing::pair(accept, pairing::SCAN_TIME).await?;
+    }
+
+    let result = if let Some(path) = &context.args.replay {
+        let remote = ScriptedRemote::load(path)?;
+        match Keyboard::try_new(
+            mapping::profiles(&context.reloads.borrow()),
+            context.args.instance.as_deref(),
+        ) {
+            Ok(mut keyboard) => replay(&remote, &mut keyboard, &context).await,
+            Err(err) => Err(err.into()),
+        }
+    } else if let Some(path) = &context.args.device {
+        let address = Address::from(path.clone());
+        let mut termination = Termination::new()?;
+        let connection = run_remote(&address, &context).instrument(remote_span());
+        tokio::pin!(connection);
+        tokio::select! {
+            res = &mut connection => res,
+            _ = termination.recv() => {
+                info!("Terminating, disconnecting the device");
+                context.shutdown.send_replace(true);
+                connection.await
+            }
+        }
+    } else if context.args.remote.is_some() {
+        return LocalSet::new().run_until(serve_bridged(context)).await;
+    } else {
+        // The devices are not `Send`, run the connections on this thread.
+        // Their errors are logged with the hints.
+        return LocalSet::new().run_until(serve(context)).await;
+    };
+    if let Err(err) = &result {
+        doctor::log_hint(err);
+    }
+    result
+}
+
+impl Args {
+    /// Returns the number of times a failed connection step is retried.
+    fn retries(&self) -> u32 {
+        if self.retry {
+            self.max_retries
+        } else {
+            0
+        }
+    }
+
+    /// Returns the changes of the options to a loaded configuration:
+    /// the `--allow` and `--ignore` lists and the `--presentation`
+    /// bindings.
+    fn config_overrides(&self) -> impl Fn(&mut Config) + Send + 'static {
+        let (allow, ignore) = (self.allow.clone(), self.ignore.clone());
+        let presentation = self.presentation;
+        move |config| {
+            config.allow.extend(allow.iter().cloned());
+            config.ignore.extend(ignore.iter().cloned());
+            if presentation {
+                config.apply_presentation();
+            }
         }
-        // The monitor never returns `None` in discovery mode.
-        eprintln!("No connected devices found");
     }
-    Ok(())
 }
 
-async fn find_device(discover: bool) -> Result<Option<Address>> {
+/// Logs to stderr, keeping stdout for the Balance Board measurements.
+fn init_logging(args: &Args) {
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => "warn",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    let logger = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(false);
+    if args.daemon {
+        // journald already timestamps the lines.
+        logger.without_time().init();
+    } else {
+        logger.init();
+    }
+}
+
+/// Returns the span of a connection, whose fields are recorded once
+/// the device is identified, so that the logs of several Wii Remotes
+/// can be told apart.
+fn remote_span() -> Span {
+    info_span!("remote", kind = field::Empty, player = field::Empty)
+}
+
+/// The state shared by all the connections.
+struct Context {
+    args: Args,
+    /// The configuration loaded on startup.
+    config: Arc<Config>,
+    /// The configuration last loaded, whose mappings replace the ones
+    /// of the running connections. The other settings are only read
+    /// on startup.
+    reloads: watch::Receiver<Arc<Config>>,
+    cues: AudioCues,
+    players: Rc<Players>,
+    /// The event budget shared by all the connections, if configured.
+    budget: Option<Rc<Budget>>,
+    /// Resolves the cross-device chords, if configured.
+    combinator: Option<Rc<Combinator>>,
+    webhooks: Webhooks,
+    mqtt: Mqtt,
+    /// Sends the head pose to opentrack, if enabled.
+    opentrack: Option<Opentrack>,
+    /// Forwards the remotes to a bridge client, with `--serve`.
+    bridge: Option<BridgeServer>,
+    /// Set to `true` when the process is terminating, so that the
+    /// connections end gracefully.
+    shutdown: watch::Sender<bool>,
+    /// Forwards the requests of the control socket to the connections.
+    commands: broadcast::Sender<control::Command>,
+    /// Whether the Bluetooth adapter is on.
+    adapter: AdapterWatch,
+    /// Publishes the input of the connections, e.g. to WebSocket clients.
+    events: Events,
+}
+
+/// Receives the signals asking the process to terminate, `SIGTERM`
+/// and `SIGINT` (Ctrl+C).
+struct Termination {
+    terminate: Signal,
+    interrupt: Signal,
+}
+
+impl Termination {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+        })
+    }
+
+    async fn recv(&mut self) {
+        tokio::select! {
+            _ = self.terminate.recv() => {}
+            _ = self.interrupt.recv() => {}
+        }
+    }
+}
+
+/// How long to wait for a Wii Remote in discovery mode before
+/// reminding how to make it discoverable.
+const DISCOVERY_HINT: Duration = Duration::from_secs(20);
+
+/// Connects to every Wii Remote found, each in its own task.
+async fn serve(context: Rc<Context>) -> Result<()> {
+    let discover = context.args.discover || context.args.daemon;
     if discover {
-        println!("Discovering devices");
+        info!("Discovering devices");
+    } else {
+        info!("Enumerating connected devices");
+    }
+
+    let mut watcher = DeviceWatcher::new(context.args.monitor, discover)?;
+    let mut termination = Termination::new()?;
+    if context.args.daemon {
+        systemd::notify("READY=1");
+    }
+    let mut remotes = Vec::new();
+    let mut connected_any = false;
+    let mut terminating = false;
+    let mut adapter = context.adapter.clone();
+    let mut available = adapter.is_available();
+    if !available {
+        info!("Bluetooth adapter is off, waiting for it to return");
+    }
+    let mut requests = context.commands.subscribe();
+    let mut sync_window = None;
+    loop {
+        let next = loop {
+            tokio::select! {
+                next = watcher.next() => break next?,
+                Ok(command) = requests.recv() => sync(command, &mut sync_window, &context),
+                _ = adapter.wait_until(!available) => {
+                    available = !available;
+                    if available {
+                        info!("Bluetooth adapter is back");
+                    } else {
+                        info!("Bluetooth adapter is off, waiting for it to return");
+                    }
+                }
+                _ = tokio::time::sleep(DISCOVERY_HINT), if discover => info!(
+                    "Still discovering, press the red sync button of the Wii Remote \
+                     (or 1 and 2) until its lights blink"
+                ),
+                _ = termination.recv() => {
+                    terminating = true;
+                    break None;
+                }
+            }
+        };
+        let address = match next {
+            Some(address) => address,
+            None => break,
+        };
+        let bluetooth_address = monitor::bluetooth_address(address.as_ref());
+        if !context.config.accepts(bluetooth_address.as_deref()) {
+            info!(
+                "Ignoring device {}",
+                bluetooth_address
+                    .as_deref()
+                    .unwrap_or("with unknown address")
+            );
+            continue;
+        }
+        info!("Device found, connecting");
+        let context = context.clone();
+        let remote = tokio::task::spawn_local(
+            async move {
+                if let Err(err) = run_remote(&address, &context).await {
+                    if context.adapter.is_available() {
+                        error!("Connection error: {:#}", err);
+                        doctor::log_hint(&err);
+                    } else {
+                        // The adapter was powered off or blocked mid-connection,
+                        // the device is found again once it returns.
+                        error!("Connection lost, the Bluetooth adapter is off: {:#}", err);
+                    }
+                }
+            }
+            .instrument(remote_span()),
+        );
+        // Forget the ended connections, discovery may run for days.
+        remotes.retain(|remote: &JoinHandle<()>| !remote.is_finished());
+        remotes.push(remote);
+        connected_any = true;
+    }
+    // Stop counting on the discovery to reply to the requests.
+    drop(requests);
+
+    if !discover && !connected_any {
+        warn!("No connected devices found");
+    }
+    // Wait for the connections to end; on SIGTERM or SIGINT, end them first.
+    let mut connections = futures_util::future::try_join_all(remotes);
+    if !terminating {
+        tokio::select! {
+            res = &mut connections => {
+                res?;
+                return Ok(());
+            }
+            _ = termination.recv() => {}
+        }
+    }
+    info!("Terminating, disconnecting the devices");
+    if context.args.daemon {
+        systemd::notify("STOPPING=1");
+    }
+    context.shutdown.send_replace(true);
+    connections.await?;
+    Ok(())
+}
+
+/// Opens a sync window on a [`Request::Sync`], pairing the new Wii
+/// Remotes in the background until it closes, unless one is open.
+/// Replies with an empty line to the other requests, handled by the
+/// connections.
+fn sync(command: control::Command, window: &mut Option<JoinHandle<()>>, context: &Rc<Context>) {
+    let duration = match command.request {
+        Request::Sync(duration) => duration,
+        _ => {
+            let _ = command.reply.send(String::new());
+            return;
+        }
+    };
+    let reply = if window.as_ref().is_some_and(|window| !window.is_finished()) {
+        "discovery: sync window already open".to_owned()
     } else {
-        println!("Enumerating connected devices");
+        info!("Sync window open for {}s", duration.as_secs());
+        let context = context.clone();
+        *window = Some(tokio::task::spawn_local(async move {
+            let accept = |address: &str| context.config.accepts(Some(address));
+            match pairing::pair(accept, duration).await {
+                Ok(()) => info!("Sync window closed"),
+                Err(err) => info!("Sync window closed: {:#}", err),
+            }
+        }));
+        format!("discovery: sync window open for {}s", duration.as_secs())
+    };
+    let _ = command.reply.send(reply);
+}
+
+/// The delay before the first retry of a connection step, doubled
+/// after each attempt up to [`MAX_BACKOFF`].
+const BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+/// Runs the connection step, retrying it up to `retries` times with
+/// exponential backoff while it fails with a transient error. Missing
+/// permissions are not retried, they won't be granted meanwhile.
+async fn retry<T>(
+    what: &str,
+    retries: u32,
+    mut step: impl FnMut() -> Result<T, ConnectionError>,
+) -> Result<T> {
+    let mut backoff = BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match step() {
+            Err(err) if attempt < retries && err.is_transient() => {
+                warn!(
+                    "Cannot {}, retrying in {:?}: {:#}",
+                    what,
+                    backoff,
+                    anyhow!(err)
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                attempt += 1;
+            }
+            result => return Ok(result?),
+        }
     }
+}
 
-    let mut monitor = Monitor::new(discover)?;
-    monitor.try_next().await.map_err(|err| err.into())
+/// Connects to the device at the given address, returning it with its
+/// kind; see [`retry`].
+async fn connect_device(address: &Address, retries: u32) -> Result<(Device, String)> {
+    retry("connect", retries, || remote::connect(address)).await
+}
+
+/// Creates the virtual keyboard of a Wii Remote and connects to it.
+async fn run_remote(address: &Address, context: &Context) -> Result<()> {
+    if let Some(bridge) = &context.bridge {
+        return connect_bridged(address, bridge, context).await;
+    }
+    if context.args.output != Output::Keyboard {
+        return connect_unmapped(address, context).await;
+    }
+    info!("Opening keyboard device");
+    let mut keyboard = Keyboard::try_new(
+        mapping::profiles(&context.reloads.borrow()),
+        context.args.instance.as_deref(),
+    )?;
+    connect(address, &mut keyboard, context).await
 }
 
 /// Initiates the connection to the given address.
 ///
+/// Sending `SIGUSR2` to the process releases all the devices, so that
+/// another program (e.g. a game, or another wiinote instance) can
+/// open them exclusively. A second `SIGUSR2` takes them back.
+///
+/// If `--extension-devices` is set, the plugged extension is exposed
+/// as a separate virtual device; see [`ExtensionDevice`].
+///
 /// # Returns
 /// On success, the function blocks until the device is disconnected
 /// gracefully, returning `Ok`. Otherwise, an error is raised.
-async fn connect(address: &Address, keyboard: &mut Keyboard) -> Result<()> {
-    let mut device = Device::connect(address)?;
-    let name = device.kind()?;
+async fn connect(address: &Address, keyboard: &mut Keyboard, context: &Context) -> Result<()> {
+    let Context {
+        args, config, cues, ..
+    } = context;
+    let retries = args.retries();
+    let (mut device, name) = connect_device(address, retries).await?;
+    if name == "balanceboard" {
+        let budget = context.budget.as_deref();
+        let instance = args.instance.as_deref();
+        let output = args.balance_board;
+        return connect_balance_board(device, &name, output, instance, budget, retries).await;
+    }
+    let mut session = Session::new(context, &name)?;
+    session.speaker = Some(SoundPlayer::new(address.as_ref()));
+    let bluetooth_address = monitor::bluetooth_address(address.as_ref());
+    session.address = bluetooth_address.clone();
+    session.adapter = Some(context.adapter.clone());
+    let device_config = bluetooth_address
+        .as_deref()
+        .and_then(|address| config.device(address));
+    if let Some(profile) = device_config.and_then(|device| device.profile.as_deref()) {
+        match keyboard.select_profile(profile) {
+            Some((ix, _)) => info!("Using profile {}: {} for this device", ix + 1, profile),
+            None => warn!("Unknown profile `{}` for this device", profile),
+        }
+    }
+    let span = Span::current();
+    span.record("kind", name.as_str());
+    span.record("player", session.player.number());
+    let mut channels = match &session.pointer {
+        Some(pointer) => Channels::CORE | pointer.channel(),
+        None => Channels::CORE,
+    };
+    if session.fall.is_some()
+        || session.stationary.is_some()
+        || session.steering.is_some()
+        || session.shake.is_some()
+        || session.gestures.is_some()
+        || session.events.is_enabled()
+    {
+        channels |= Channels::ACCELEROMETER;
+    }
+    if session.head_tracking.is_some() {
+        channels |= Channels::IR;
+    }
+    // The channels opened once available, e.g. when a Motion Plus is
+    // plugged later. The gestures use the gyroscope if plugged, but
+    // don't require it.
+    let mut wanted = channels;
+    if session.gestures.is_some() {
+        wanted |= Channels::MOTION_PLUS;
+    }
+    if session.stick.is_some() {
+        wanted |= Channels::NUNCHUK;
+    }
+    if channels.contains(Channels::MOTION_PLUS) {
+        if device.available().contains(Channels::MOTION_PLUS) {
+            session.pointer.as_mut().unwrap().recalibrate();
+        } else {
+            // Without the gyroscope fallback, the IR pointers still work.
+            let consequence = if channels.contains(Channels::IR) {
+                "the pointer will stop while the sensor bar is out of sight"
+            } else {
+                "the mouse mode won't move the pointer"
+            };
+            warn!("No Motion Plus found, {} until one is plugged", consequence);
+            channels.remove(Channels::MOTION_PLUS);
+        }
+    }
+    channels |= wanted & device.available();
 
-    device.open(Channels::CORE, true)?;
-    println!("Device connected: {}", name);
+    let open = || remote::open(&mut device, channels, true);
+    retry("open the channels", retries, open).await?;
+    info!(
+        "Device connected: {} (player {})",
+        name,
+        session.player.number()
+    );
+    let exposed = kernel::exposed_devices();
+    if !exposed.is_empty() {
+        warn!(
+            "The desktop also receives the buttons from the kernel devices ({}), \
+             add the udev rule from the README to avoid duplicate key presses",
+            exposed.join(", ")
+        );
+    }
+    cues.play(Cue::Connected);
+    session.feedback.rumble(&device, feedback::CONNECTED)?;
+    session.webhooks.notify(WebhookEvent::Connected, "");
+    session.mqtt.connected(&name);
+    let kind = format!(r#","kind":"{}""#, name);
+    session.events.publish("connected", || kind);
 
-    handle(&mut device, keyboard).await?;
-    println!("Device disconnected: {}", name);
+    // The profile active before the one of the plugged extension.
+    let mut previous_profile = None;
+    let result: Result<()> = async {
+        loop {
+            let extension = device.extension()?;
+            if extension == "unknown" {
+                // e.g. the uDraw and Drawsome tablets, whose data the
+                // driver doesn't report.
+                warn!("Extension plugged, but not supported by the hid-wiimote driver");
+            }
+            sync_profile(keyboard, &extension, &mut previous_profile);
+            // The Nunchuk may be gone, don't repeat its last direction.
+            if let Some(stick) = session.stick.as_mut() {
+                stick.release();
+            }
+            if args.extension_devices {
+                let instance = args.instance.as_deref();
+                sync_extension(&mut device, &extension, &mut session.extension, instance)?;
+            }
+            let missing = (wanted - device.opened()) & device.available();
+            if !missing.is_empty() {
+                device.open(missing, true)?;
+                channels |= missing;
+                if missing.contains(Channels::MOTION_PLUS) {
+                    info!("Motion Plus plugged");
+                    if let Some(pointer) = session.pointer.as_mut() {
+                        pointer.recalibrate();
+                    }
+                }
+            }
+            match handle(&device, keyboard, cues, config, &mut session).await? {
+                Exit::Disconnected => return Ok(()),
+                Exit::AdapterOff => {
+                    // The device is found again once the adapter returns.
+                    info!("Bluetooth adapter turned off, pausing until the device reconnects");
+                    return Ok(());
+                }
+                Exit::Shutdown => {
+                    // Leave the Wii Remote as if it was just turned on.
+                    for ix in 1..=4 {
+                        device.set_led(Led::from_u8(ix).unwrap(), false)?;
+                    }
+                    session.feedback.stop(&device)?;
+                    return Ok(());
+                }
+                Exit::ExtensionChanged => continue,
+                Exit::Idle => {
+                    let minutes = config.idle.as_ref().map_or(0, |idle| idle.timeout);
+                    info!("No button pressed for {} minutes, disconnecting", minutes);
+                    session.feedback.stop(&device)?;
+                    // On failure, keep handling the events and restart the timer.
+                    if power_off(bluetooth_address.as_deref()).await {
+                        wait_removal(&device, &mut session.shutdown).await;
+                        return Ok(());
+                    }
+                    continue;
+                }
+                Exit::PowerOff => {
+                    info!("Turning the device off");
+                    session.feedback.stop(&device)?;
+                    if power_off(bluetooth_address.as_deref()).await {
+                        wait_removal(&device, &mut session.shutdown).await;
+                        return Ok(());
+                    }
+                    continue;
+                }
+                Exit::Released => {}
+            }
+
+            // The other program receives the button releases.
+            keyboard.release_all()?;
+            // Remove the extension device, it is recreated on reacquisition.
+            session.extension = None;
+            session.feedback.stop(&device)?;
+            device.close(device.opened())?;
+            info!("Device released: {}", name);
+
+            tokio::select! {
+                _ = session.handoff.recv() => {}
+                _ = session.shutdown.changed() => return Ok(()),
+            }
+            if let Err(err) = device.open(channels, true) {
+                // The device was probably disconnected in the meantime.
+                warn!("Cannot reacquire device: {}", err);
+                return Ok(());
+            }
+            info!("Device reacquired: {}", name);
+        }
+    }
+    .await;
+
+    // Pause even if the connection dropped with an error.
+    report_disconnection(keyboard, &session, context)?;
+    result?;
+    info!("Device disconnected: {}", name);
+    Ok(())
+}
+
+/// Releases the keys held on a Wii Remote whose connection ended,
+/// pausing the playback with `--pause-on-disconnect`, and reports the
+/// disconnection.
+fn report_disconnection(
+    keyboard: &mut Keyboard,
+    session: &Session,
+    context: &Context,
+) -> Result<()> {
+    let args = &context.args;
+    // The buttons held when the connection dropped are never released.
+    if let Err(err) = keyboard.release_all() {
+        warn!("Cannot release the pressed keys: {}", err);
+    }
+    if args.pause_on_disconnect {
+        keyboard.pause_playback()?;
+    }
+    context.cues.play(Cue::Disconnected);
+    session.webhooks.notify(WebhookEvent::Disconnected, "");
+    session.mqtt.disconnected();
+    session.events.publish("disconnected", String::new);
+    info!("Connection summary: {}", session.stats);
+    if let Some(fall) = &session.fall {
+        info!("Device dropped {} times", fall.drops());
+    }
+    if let Some(shake) = session.shake.as_ref().filter(|_| args.adaptive_gestures) {
+        info!("Shake threshold adapted to {}", shake.threshold());
+    }
+    Ok(())
+}
+
+/// Connects to the Wii Remotes of the bridge server, handling each in
+/// its own task, and reconnects when the connection drops.
+async fn serve_bridged(context: Rc<Context>) -> Result<()> {
+    let server = context.args.remote.as_deref().unwrap_or_default();
+    let bridge = context.config.bridge.as_ref();
+    let secret = &bridge.expect("no bridge configuration").secret;
+    let mut termination = Termination::new()?;
+    let mut remotes = Vec::new();
+    let connections = async {
+        let mut backoff = BACKOFF;
+        loop {
+            let mut client = match BridgeClient::connect(server, secret).await {
+                Ok(client) => client,
+                Err(err) => {
+                    warn!(
+                        "Cannot connect to the bridge server {}, retrying in {:?}: {:#}",
+                        server, backoff, err
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            };
+            info!("Connected to the bridge server {}", server);
+            backoff = BACKOFF;
+            loop {
+                let (remote, kind) = match client.next_remote().await {
+                    Ok(Some(remote)) => remote,
+                    Ok(None) => {
+                        warn!("Bridge server closed the connection, reconnecting");
+                        break;
+                    }
+                    Err(err) => {
+                        warn!("Bridge connection lost, reconnecting: {:#}", err);
+                        break;
+                    }
+                };
+                let context = context.clone();
+                let remote = tokio::task::spawn_local(
+                    async move {
+                        if let Err(err) = run_bridged(&remote, &kind, &context).await {
+                            error!("Connection error: {:#}", err);
+                        }
+                    }
+                    .instrument(remote_span()),
+                );
+                remotes.retain(|remote: &JoinHandle<()>| !remote.is_finished());
+                remotes.push(remote);
+            }
+        }
+    };
+    tokio::select! {
+        _ = connections => {}
+        _ = termination.recv() => {}
+    }
+    info!("Terminating, disconnecting the devices");
+    context.shutdown.send_replace(true);
+    futures_util::future::try_join_all(remotes).await?;
     Ok(())
 }
 
-/// The metrics that can be displayed in a [`LightDisplay`].
-enum LightsMetric {
-    /// Display the battery level.
-    Battery,
-    /// Display the connection strength level.
-    Connection,
+/// Emits the mapped keys of a Wii Remote connected to the bridge
+/// server until it disconnects; see [`connect`].
+async fn run_bridged(remote: &NetworkRemote, kind: &str, context: &Context) -> Result<()> {
+    let Context { config, cues, .. } = context;
+    info!("Opening keyboard device");
+    let mut keyboard = Keyboard::try_new(
+        mapping::profiles(&context.reloads.borrow()),
+        context.args.instance.as_deref(),
+    )?;
+    let mut session = Session::new(context, kind)?;
+    let span = Span::current();
+    span.record("kind", kind);
+    span.record("player", session.player.number());
+    info!(
+        "Device connected: {} (player {})",
+        kind,
+        session.player.number()
+    );
+    cues.play(Cue::Connected);
+    session.feedback.rumble(remote, feedback::CONNECTED)?;
+    session.webhooks.notify(WebhookEvent::Connected, "");
+    session.mqtt.connected(kind);
+    let fields = format!(r#","kind":"{}""#, kind);
+    session.events.publish("connected", || fields);
+
+    let result = loop {
+        match handle(remote, &mut keyboard, cues, config, &mut session).await {
+            // The adapter of the server isn't watched.
+            Ok(Exit::Disconnected | Exit::Shutdown | Exit::AdapterOff) => break Ok(()),
+            // The extensions aren't forwarded.
+            Ok(Exit::ExtensionChanged) => {}
+            Ok(Exit::Idle | Exit::PowerOff) => {
+                // The server disconnects it, ending the events.
+                info!("Turning the device off");
+                session.feedback.stop(remote)?;
+                remote.power_off();
+            }
+            Ok(Exit::Released) => {
+                keyboard.release_all()?;
+                session.feedback.stop(remote)?;
+                info!("Device released: {}", kind);
+                tokio::select! {
+                    _ = session.handoff.recv() => {}
+                    _ = session.shutdown.changed() => break Ok(()),
+                }
+                info!("Device reacquired: {}", kind);
+            }
+            Err(err) => break Err(err),
+        }
+    };
+    report_disconnection(&mut keyboard, &session, context)?;
+    result?;
+    info!("Device disconnected: {}", kind);
+    Ok(())
 }
 
-/// The set of lights of a Wii Remote, used as a display.
-struct LightDisplay<'a> {
-    device: &'a Device,
-    metric: LightsMetric,
-    interval: tokio::time::Interval,
+/// Handles the events of a scripted remote, then prints the state
+/// it was left in.
+async fn replay(remote: &ScriptedRemote, keyboard: &mut Keyboard, context: &Context) -> Result<()> {
+    let mut session = Session::new(context, "replay")?;
+    info!("Replaying script");
+    handle(
+        remote,
+        keyboard,
+        &context.cues,
+        &context.config,
+        &mut session,
+    )
+    .await?;
+    info!("Replay summary: {}", session.stats);
+    info!(
+        "Lights: {:04b}, rumble {}",
+        remote.lights(),
+        if remote.is_rumbling() { "on" } else { "off" }
+    );
+    Ok(())
 }
 
-impl<'a> LightDisplay<'a> {
-    pub fn new(device: &'a Device) -> Self {
-        Self {
-            device,
-            // Default to battery level, the connection strength is
-            // probably high immediately after pairing.
-            metric: LightsMetric::Battery,
-            interval: tokio::time::interval(Duration::from_secs(20)),
+/// Replays the script of a scenario through the mappings, recording the
+/// keys instead of emitting them, then checks the outputs it expects.
+async fn simulate(scenario: &Scenario, context: &Context) -> Result<()> {
+    info!("Simulating {}", scenario.path().display());
+    run_scenario(scenario, context).await?;
+    println!(
+        "{}",
+        tr!(
+            "scenario-passed",
+            "Scenario {path} passed",
+            path = scenario.path().display()
+        )
+    );
+    Ok(())
+}
+
+/// Replays the script of a scenario in a new session and checks its
+/// outputs, see [`simulate`].
+async fn run_scenario(scenario: &Scenario, context: &Context) -> Result<()> {
+    let remote = ScriptedRemote::load(scenario.script())?;
+    let recorder = Recorder::new();
+    let mut keyboard = Keyboard::with_emitter(
+        mapping::profiles(&context.reloads.borrow()),
+        Box::new(recorder.clone()),
+    );
+    let mut session = Session::new(context, "simulation")?;
+    let exit = handle(
+        &remote,
+        &mut keyboard,
+        &context.cues,
+        &context.config,
+        &mut session,
+    )
+    .await?;
+    let outcome = Outcome {
+        emitted: recorder.take(),
+        held: recorder.held(),
+        lights: remote.lights(),
+        rumble: remote.is_rumbling(),
+        exit: exit.name(),
+    };
+    keyboard.release_all()?;
+    scenario.verify(&outcome)
+}
+
+/// How often the progress of a soak test is logged.
+const SOAK_REPORT: Duration = Duration::from_secs(10 * 60);
+
+/// How much the resident memory can grow over a soak test, from the
+/// end of the first pass over the scenarios.
+const SOAK_MEMORY_SLACK: u64 = 4 * 1024;
+
+/// Replays the scenarios over and over for the given duration (or
+/// until Ctrl+C is pressed), checking their outputs, and that the
+/// memory use and the number of tasks after each pass don't grow past
+/// the ones of the first pass. Fails if any check did.
+async fn soak(scenarios: &[Scenario], duration: Duration, context: &Context) -> Result<()> {
+    let mut termination = Termination::new()?;
+    let runtime = tokio::runtime::Handle::current();
+    let start = Instant::now();
+    let mut next_report = start + SOAK_REPORT;
+    let mut cycles = 0;
+    let mut failures = Vec::new();
+    // The resident memory (in KiB) and alive tasks after the first pass.
+    let mut baseline = None;
+    let mut peak_memory = 0;
+    info!(
+        "Soaking {} scenarios for {:.2}h",
+        scenarios.len(),
+        hours(duration)
+    );
+    while start.elapsed() < duration {
+        for scenario in scenarios {
+            let result = tokio::select! {
+                result = run_scenario(scenario, context) => result,
+                _ = termination.recv() => {
+                    info!("Terminating the soak test");
+                    return soak_summary(start, cycles, &failures);
+                }
+            };
+            cycles += 1;
+            if let Err(err) = result {
+                warn!("Cycle {} failed: {:#}", cycles, err);
+                failures.push(format!("cycle {}: {:#}", cycles, err));
+            }
+        }
+        // Let the aborted tasks of the sessions be dropped.
+        for _ in 0..8 {
+            tokio::task::yield_now().await;
         }
+        let memory = resident_memory()?;
+        let tasks = runtime.metrics().num_alive_tasks();
+        peak_memory = peak_memory.max(memory);
+        let (base_memory, base_tasks) = *baseline.get_or_insert((memory, tasks));
+        if memory > base_memory + SOAK_MEMORY_SLACK {
+            failures.push(format!(
+                "cycle {}: {} KiB resident, {} KiB after the first pass",
+                cycles, memory, base_memory
+            ));
+            // Only report the growth again if it goes on.
+            baseline = Some((memory, base_tasks));
+        }
+        if tasks > base_tasks {
+            failures.push(format!(
+                "cycle {}: {} tasks alive, {} after the first pass",
+                cycles, tasks, base_tasks
+            ));
+            baseline = Some((base_memory.max(memory), tasks));
+        }
+        if Instant::now() >= next_report {
+            next_report += SOAK_REPORT;
+            info!(
+                "Soaked for {:.2}h: {} cycles, {} failures, {} KiB resident, {} tasks",
+                hours(start.elapsed()),
+                cycles,
+                failures.len(),
+                memory,
+                tasks
+            );
+        }
+    }
+    println!(
+        "Memory: {} KiB resident after the first pass, {} KiB at most",
+        baseline.map_or(0, |(memory, _)| memory),
+        peak_memory
+    );
+    soak_summary(start, cycles, &failures)
+}
+
+/// Prints the summary of a soak test, failing if any check did.
+fn soak_summary(start: Instant, cycles: u32, failures: &[String]) -> Result<()> {
+    println!(
+        "Soaked for {:.2}h: {} cycles, {} failures",
+        hours(start.elapsed()),
+        cycles,
+        failures.len()
+    );
+    for failure in failures {
+        println!("  - {}", failure.replace('\n', "\n  "));
     }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("the soak test failed"))
+    }
+}
+
+fn hours(duration: Duration) -> f64 {
+    duration.as_secs_f64() / 3600.0
+}
+
+/// Returns the resident memory of the process, in KiB.
+fn resident_memory() -> Result<u64> {
+    let status = std::fs::read_to_string("/proc/self/status")?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .ok_or_else(|| anyhow!("cannot read the resident memory"))
+}
 
-    pub async fn tick(&mut self) -> tokio::time::Instant {
-        self.interval.tick().await
+/// Disconnects the Wii Remote with the given Bluetooth address, which
+/// turns it off. Failures are logged, the remote stays connected.
+///
+/// # Returns
+/// Whether the remote was disconnected.
+async fn power_off(address: Option<&str>) -> bool {
+    match address {
+        Some(address) => match pairing::disconnect(address).await {
+            Ok(()) => true,
+            Err(err) => {
+                warn!("Cannot disconnect the device: {}", err);
+                false
+            }
+        },
+        None => {
+            warn!("Cannot disconnect the device, unknown address");
+            false
+        }
     }
+}
+
+/// How long the kernel may take to remove a disconnected Wii Remote.
+const REMOVAL_TIMEOUT: Duration = Duration::from_secs(10);
 
-    /// Updates the Wii Remote lights according to the current metric.
-    pub async fn update(&self) -> Result<()> {
-        let level = match self.metric {
-            LightsMetric::Battery => self.device.battery()?,
-            LightsMetric::Connection => {
-                // Technically, RSSI is a measure of the received intensity,
-                // not connection quality. This is good enough for the Wii Remote.
-                // The scale goes from -80 to 0, where 0 indicates the greatest
-                // signal strength.
-                let rssi = 0; // todo
-                !((rssi * u8::MAX as i16 / -80) as u8)
+/// Waits until the events of a disconnected Wii Remote end, once the
+/// kernel removed it, instead of using its interface meanwhile.
+async fn wait_removal(device: &Device, shutdown: &mut watch::Receiver<bool>) {
+    let mut event_stream = match device.events() {
+        Ok(event_stream) => event_stream,
+        Err(_) => return,
+    };
+    let removal = async {
+        // The events still queued are dropped, the keys are released.
+        while let Ok(Some(_)) = event_stream.try_next().await {}
+    };
+    tokio::select! {
+        res = tokio::time::timeout(REMOVAL_TIMEOUT, removal) => {
+            if res.is_err() {
+                warn!("The device is still present after disconnecting it");
             }
+        }
+        _ = shutdown.changed() => {}
+    }
+}
+
+/// Publishes the input of the device at the given address without
+/// mapping it, as selected by `--output`, until it disconnects.
+async fn connect_unmapped(address: &Address, context: &Context) -> Result<()> {
+    let retries = context.args.retries();
+    let (device, name) = connect_device(address, retries).await?;
+    if name == "balanceboard" {
+        let budget = context.budget.as_deref();
+        let instance = context.args.instance.as_deref();
+        let output = context.args.balance_board;
+        return connect_balance_board(device, &name, output, instance, budget, retries).await;
+    }
+    let player = context.players.join();
+    let span = Span::current();
+    span.record("kind", name.as_str());
+    span.record("player", player.number());
+    info!("Device connected: {} (player {})", name, player.number());
+    let shutdown = context.shutdown.subscribe();
+    match context.args.output {
+        Output::Dolphin => {
+            let instance = context.args.instance.as_deref();
+            connect_dolphin(device, instance, shutdown, retries).await?
+        }
+        #[cfg(feature = "midi")]
+        Output::Midi => {
+            let mapper = MidiMapper::new(&context.config.midi);
+            let port =
+                midi::MidiPort::try_new(mapper, player.number(), context.args.instance.as_deref())?;
+            connect_midi(device, port, shutdown, retries).await?
+        }
+        Output::Kodi => {
+            // Checked on startup.
+            let config = context.config.kodi.clone().expect("no Kodi configuration");
+            connect_kodi(device, Kodi::new(config)?, shutdown, retries).await?
+        }
+        _ => output::stream(device, &name, player.number(), shutdown).await?,
+    }
+    info!("Device disconnected: {}", name);
+    Ok(())
+}
+
+/// Forwards the buttons and motion of a Wii Remote to a virtual
+/// gamepad until it disconnects.
+async fn connect_dolphin(
+    mut device: Device,
+    instance: Option<&str>,
+    mut shutdown: watch::Receiver<bool>,
+    retries: u32,
+) -> Result<()> {
+    let mut gamepad = DolphinDevice::try_new(instance)?;
+    let channels = Channels::CORE | Channels::ACCELEROMETER | Channels::IR;
+    let open = || remote::open(&mut device, channels, false);
+    retry("open the channels", retries, open).await?;
+    let mut event_stream = device.events()?;
+    loop {
+        let event = tokio::select! {
+            res = event_stream.try_next() => res?,
+            _ = shutdown.changed() => return Ok(()),
         };
+        match event {
+            Some(event) => gamepad.update(&event.kind)?,
+            None => return Ok(()),
+        }
+    }
+}
 
-        // `level` is a value from 0 to u8::MAX.
-        let last_ix = 1 + (level >> 6); // 1..=4
-        for ix in 1..=4 {
-            let light = Led::from_u8(ix).unwrap();
-            self.device.set_led(light, ix <= last_ix)?;
+/// How often the battery level of a Wii Remote forwarded to the bridge
+/// client is read.
+const BRIDGE_BATTERY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Forwards the events of a Wii Remote to the bridge client, and
+/// applies its light, rumble and power off requests, until it
+/// disconnects.
+async fn connect_bridged(
+    address: &Address,
+    bridge: &BridgeServer,
+    context: &Context,
+) -> Result<()> {
+    let retries = context.args.retries();
+    let (mut device, name) = connect_device(address, retries).await?;
+    if name == "balanceboard" {
+        warn!("Balance Boards aren't forwarded to the bridge client");
+        return Ok(());
+    }
+    let player = context.players.join();
+    let span = Span::current();
+    span.record("kind", name.as_str());
+    span.record("player", player.number());
+    let channels = Channels::CORE | Channels::ACCELEROMETER | Channels::IR | Channels::MOTION_PLUS;
+    let channels = channels & device.available();
+    let open = || remote::open(&mut device, channels, true);
+    retry("open the channels", retries, open).await?;
+    info!("Device connected: {} (player {})", name, player.number());
+
+    let bluetooth_address = monitor::bluetooth_address(address.as_ref());
+    let mut bridged = bridge.connect(player.number() as u8, &name);
+    let mut event_stream = device.events()?;
+    let mut battery = tokio::time::interval(BRIDGE_BATTERY_INTERVAL);
+    let mut shutdown = context.shutdown.subscribe();
+    loop {
+        tokio::select! {
+            event = event_stream.try_next() => match event? {
+                Some(event) => bridged.event(&event.kind),
+                None => break,
+            },
+            command = bridged.command() => match command {
+                Frame::Led { light, enabled, .. } => {
+                    if let Some(light) = Led::from_u8(light) {
+                        device.set_led(light, enabled)?;
+                    }
+                }
+                Frame::Rumble { enabled, .. } => device.set_rumble(enabled)?,
+                // The events end once disconnected.
+                Frame::PowerOff { .. } => {
+                    power_off(bluetooth_address.as_deref()).await;
+                }
+                _ => {}
+            },
+            _ = battery.tick() => bridged.battery(device.battery()?),
+            _ = shutdown.changed() => break,
         }
+    }
+    info!("Device disconnected: {}", name);
+    Ok(())
+}
 
-        Ok(())
+/// Sends the button presses of a Wii Remote to Kodi until it
+/// disconnects.
+async fn connect_kodi(
+    mut device: Device,
+    mut kodi: Kodi,
+    mut shutdown: watch::Receiver<bool>,
+    retries: u32,
+) -> Result<()> {
+    let open = || remote::open(&mut device, Channels::CORE, false);
+    retry("open the channels", retries, open).await?;
+    let mut event_stream = device.events()?;
+    loop {
+        let event = tokio::select! {
+            res = event_stream.try_next() => res?,
+            _ = shutdown.changed() => return Ok(()),
+        };
+        match event {
+            Some(event) => kodi.update(&event.kind),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Sends the MIDI messages of a Wii Remote until it disconnects.
+#[cfg(feature = "midi")]
+async fn connect_midi(
+    mut device: Device,
+    mut port: midi::MidiPort,
+    mut shutdown: watch::Receiver<bool>,
+    retries: u32,
+) -> Result<()> {
+    let channels = Channels::CORE | Channels::ACCELEROMETER | Channels::IR;
+    let open = || remote::open(&mut device, channels, false);
+    retry("open the channels", retries, open).await?;
+    let mut event_stream = device.events()?;
+    loop {
+        let event = tokio::select! {
+            res = event_stream.try_next() => res?,
+            _ = shutdown.changed() => return Ok(()),
+        };
+        match event {
+            Some(event) => port.update(&event.kind)?,
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Publishes the measurements of a Balance Board until it disconnects.
+async fn connect_balance_board(
+    mut device: Device,
+    name: &str,
+    output: BalanceOutput,
+    instance: Option<&str>,
+    budget: Option<&Budget>,
+    retries: u32,
+) -> Result<()> {
+    let mut board = BalanceBoard::try_new(output, instance)?;
+    let channels = Channels::BALANCE_BOARD;
+    let open = || remote::open(&mut device, channels, false);
+    retry("open the channels", retries, open).await?;
+    info!("Device connected: {}", name);
+
+    let mut event_stream = device.events()?;
+    while let Some(event) = event_stream.try_next().await? {
+        if budget.is_some_and(|budget| !budget.admit(&event.kind)) {
+            continue;
+        }
+        if let EventKind::BalanceBoard(cells) = event.kind {
+            board.update(&cells)?;
+        }
     }
+    info!("Device disconnected: {}", name);
+    Ok(())
+}
 
-    /// Updates the displayed metric.
-    pub async fn set_metric(&mut self, metric: LightsMetric) -> Result<()> {
-        self.metric = metric;
-        self.update().await
+/// The state of a device connection, kept while the device is
+/// released or its channels are reopened.
+struct Session {
+    player: Player,
+    budget: Option<Rc<Budget>>,
+    combinator: Option<Rc<Combinator>>,
+    extension: Option<ExtensionDevice>,
+    /// The mouse mode state, if configured.
+    pointer: Option<Pointer>,
+    /// The virtual device of the absolute pointer, if used.
+    absolute: Option<AbsolutePointer>,
+    /// The steering mode state, if configured.
+    steering: Option<Steering>,
+    /// The Nunchuk stick navigation state, if configured.
+    stick: Option<Stick>,
+    stats: EventStats,
+    /// The discharge history of the battery, predicting when to poll it.
+    battery: DischargeModel,
+    /// Receives the requests to release or reacquire the device.
+    handoff: Signal,
+    feedback: Feedback,
+    /// The drop detector, if enabled.
+    fall: Option<FallDetector>,
+    /// The shake detector, if the gesture is bound.
+    shake: Option<ShakeDetector>,
+    /// Tells whether the remote is stationary, if the commands and
+    /// power offs require it.
+    stationary: Option<StationaryGuard>,
+    /// The gesture recognizer, if any gesture is bound.
+    gestures: Option<GestureRecognizer>,
+    /// The head tracking output, if enabled.
+    head_tracking: Option<Opentrack>,
+    webhooks: Webhooks,
+    mqtt: Mqtt,
+    shutdown: watch::Receiver<bool>,
+    commands: broadcast::Receiver<control::Command>,
+    /// Opens a sync window, for the [`Action::Sync`](wiinote_core::mapping::Action::Sync)
+    /// bindings.
+    sync: broadcast::Sender<control::Command>,
+    reloads: watch::Receiver<Arc<Config>>,
+    events: Events,
+    /// Plays the sounds of the mapping, unless replaying a script.
+    speaker: Option<SoundPlayer>,
+    /// The Bluetooth address of the device, unknown when replaying a
+    /// script.
+    address: Option<String>,
+    /// The adapter of the device, unless replaying a script or bridged.
+    adapter: Option<AdapterWatch>,
+}
+
+impl Session {
+    /// Creates the state of a new connection to the named device.
+    fn new(context: &Context, name: &str) -> Result<Self> {
+        let Context { args, config, .. } = context;
+        let player = context.players.join();
+        let mut pointer = match &config.mouse {
+            Some(mouse) => {
+                let calibration = config.calibration.as_ref();
+                Some(Pointer::new(
+                    mouse,
+                    calibration.map(|c| c.homography).unwrap_or_default(),
+                ))
+            }
+            // The held pointer defaults to the IR camera.
+            None => config.binds_pointer().then(Pointer::default),
+        };
+        let region = config
+            .mouse
+            .as_ref()
+            .and_then(|mouse| mouse.region.as_ref());
+        if let (Some(pointer), Some(region)) = (&mut pointer, region) {
+            match screen::bounds(region) {
+                Ok(bounds) => pointer.set_bounds(bounds),
+                Err(err) => warn!("Cannot restrict the pointer to {}: {:#}", region, err),
+            }
+        }
+        let absolute = match &pointer {
+            Some(pointer) if pointer.is_absolute() => {
+                Some(AbsolutePointer::try_new(args.instance.as_deref())?)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            webhooks: context.webhooks.for_device(name, player.number()),
+            mqtt: context.mqtt.for_player(player.number()),
+            shutdown: context.shutdown.subscribe(),
+            commands: context.commands.subscribe(),
+            sync: context.commands.clone(),
+            reloads: context.reloads.clone(),
+            events: context.events.for_player(player.number()),
+            player,
+            budget: context.budget.clone(),
+            combinator: context.combinator.clone(),
+            extension: None,
+            pointer,
+            absolute,
+            steering: config.steering.as_ref().map(Steering::new),
+            stick: config.stick.as_ref().map(Stick::new),
+            stats: EventStats::new(),
+            battery: DischargeModel::new(),
+            handoff: signal(SignalKind::user_defined2())?,
+            feedback: Feedback::new(args.rumble),
+            fall: args.drop_detection.then(FallDetector::new),
+            stationary: config.stationary.as_ref().map(StationaryGuard::new),
+            shake: config
+                .binds(Button::Shake)
+                .then(|| ShakeDetector::new(args.adaptive_gestures)),
+            gestures: config.binds_gesture().then(GestureRecognizer::new),
+            head_tracking: context.opentrack.as_ref().map(Opentrack::for_device),
+            speaker: None,
+            address: None,
+            adapter: None,
+        })
+    }
+}
+
+/// Opens the channel of the plugged extension and creates its
+/// virtual device, removing the previous one if it was unplugged.
+fn sync_extension(
+    device: &mut Device,
+    name: &str,
+    current: &mut Option<ExtensionDevice>,
+    instance: Option<&str>,
+) -> Result<()> {
+    let plugged = Extension::from_name(name);
+    if current.as_ref().map(ExtensionDevice::extension) == plugged {
+        return Ok(());
     }
+
+    if let Some(previous) = current.take() {
+        device.close(previous.extension().channel())?;
+        info!("Extension unplugged: {:?}", previous.extension());
+    }
+    if let Some(extension) = plugged {
+        device.open(extension.channel(), false)?;
+        *current = Some(ExtensionDevice::try_new(extension, instance)?);
+        info!("Extension plugged: {:?}", extension);
+    }
+    Ok(())
 }
 
+/// Activates the profile of the plugged extension, if any, remembering
+/// the active one in `previous`. Once no extension with a profile is
+/// plugged anymore, the remembered profile is activated back.
+fn sync_profile(keyboard: &mut Keyboard, extension: &str, previous: &mut Option<String>) {
+    let active = keyboard.profile().1.name.clone();
+    match keyboard.select_extension_profile(extension) {
+        Some((ix, profile)) => {
+            if profile.name != active {
+                info!(
+                    "Switched to profile {}: {} ({} plugged)",
+                    ix + 1,
+                    profile.name,
+                    extension
+                );
+                previous.get_or_insert(active);
+            }
+        }
+        None => {
+            let restored = previous
+                .take()
+                .and_then(|name| keyboard.select_profile(&name));
+            if let Some((ix, profile)) = restored {
+                info!("Switched back to profile {}: {}", ix + 1, profile.name);
+            }
+        }
+    }
+}
+
+/// The reason the processing of a device connection stopped.
+enum Exit {
+    /// The connection was closed.
+    Disconnected,
+    /// An extension was plugged or unplugged; the channels
+    /// must be updated.
+    ExtensionChanged,
+    /// The device was released for another program to use.
+    Released,
+    /// No button was pressed for the configured period.
+    Idle,
+    /// A power off action ran, e.g. by holding Home.
+    PowerOff,
+    /// The Bluetooth adapter was turned off, closing the connection.
+    AdapterOff,
+    /// The process is terminating.
+    Shutdown,
+}
+
+impl Exit {
+    /// Returns the name of the exit in the scenarios.
+    fn name(&self) -> &'static str {
+        match self {
+            Exit::Disconnected => "disconnected",
+            Exit::ExtensionChanged => "extension-changed",
+            Exit::Released => "released",
+            Exit::Idle => "idle",
+            Exit::PowerOff => "power-off",
+            Exit::AdapterOff => "adapter-off",
+            Exit::Shutdown => "shutdown",
+        }
+    }
+}
+
+/// How long the lights blink before an idle Wii Remote is disconnected.
+const IDLE_FLASH: Duration = Duration::from_secs(2);
+
+/// How long the number of the activated profile is displayed.
+const PROFILE_FLASH: Duration = Duration::from_secs(2);
+
+/// How often the displayed pattern is rewritten, to fix the lights
+/// whose command was dropped.
+const LIGHTS_REFRESH: Duration = Duration::from_secs(30);
+
 /// Process the connection to the Wii Remote.
 ///
+/// Sending `SIGUSR1` to the process toggles the keyboard output of
+/// every connected Wii Remote.
+///
+/// When the configuration is reloaded (on `SIGHUP` or when the file
+/// changes), the new mappings are applied, keeping the active profile
+/// if it still exists.
+///
+/// Pressing Plus while holding Home activates the next profile, whose
+/// number is briefly displayed on the lights in binary and, with
+/// `--rumble`, told by as many rumble pulses.
+///
+/// Pressing Two while holding One toggles the numpad mode, where the
+/// D-pad moves the selection on a 3×4 numeric grid (displayed on the
+/// lights in binary) and A emits the selected key; B or Home exit.
+///
+/// While other Wii Remotes are connected, the lights show the player
+/// number instead of the battery or connection metric.
+///
+/// Holding Home for 3 seconds turns the Wii Remote off, unless its
+/// hold action is bound to something else.
+///
 /// # Returns
-/// If the device is disconnected gracefully, returns `Ok`. Otherwise,
-/// an error is returned.
-async fn handle(device: &mut Device, keyboard: &mut Keyboard) -> Result<()> {
+/// If the device is disconnected gracefully or the `handoff` signal
+/// is received, returns `Ok`. Otherwise, an error is returned.
+async fn handle(
+    device: &dyn Remote,
+    keyboard: &mut Keyboard,
+    cues: &AudioCues,
+    config: &Config,
+    session: &mut Session,
+) -> Result<Exit> {
+    let Session {
+        player,
+        budget,
+        combinator,
+        extension,
+        pointer,
+        absolute,
+        steering,
+        stick,
+        stats,
+        battery,
+        handoff,
+        feedback,
+        fall,
+        shake,
+        stationary,
+        gestures,
+        head_tracking,
+        webhooks,
+        mqtt,
+        shutdown,
+        commands,
+        sync,
+        reloads,
+        events,
+        speaker,
+        address,
+        adapter,
+    } = session;
+    let mouse_toggle = config.mouse.as_ref().map(|mouse| mouse.toggle);
+    let steering_toggle = config.steering.as_ref().map(|steering| steering.toggle);
     let mut event_stream = device.events()?;
-    let mut display = LightDisplay::new(device);
+    let low_percent = config.battery.low;
+    let mut display = LightDisplay::new(
+        device,
+        battery,
+        address.clone(),
+        player.number(),
+        low_percent,
+    );
+    let mut low_battery = false;
+    // The last quarter the battery percentage was in, logged when it changes.
+    let mut battery_quarter = None;
+    let mut one_held = false;
+    let mut home_held = false;
+    let mut numpad: Option<Numpad> = None;
+    display.set_shared(player.is_shared()).await?;
+    display.set_muted(keyboard.is_muted()).await?;
+    let mut mute_toggle = signal(SignalKind::user_defined1())?;
+    let mut lights_refresh =
+        tokio::time::interval_at(Instant::now() + LIGHTS_REFRESH, LIGHTS_REFRESH);
+    let mut idle = IdleTimer::new(config.idle.as_ref());
 
     loop {
+        for action in keyboard.take_remote_actions() {
+            match action {
+                RemoteAction::PowerOff => return Ok(Exit::PowerOff),
+                RemoteAction::Pointer(held) => match pointer {
+                    Some(pointer) => pointer.set_held(held),
+                    None => warn!("The pointer is available once the remote reconnects"),
+                },
+                RemoteAction::PlaySound(sound) => match speaker {
+                    Some(speaker) => speaker.play(sound),
+                    None => info!("Playing {}", sound.display()),
+                },
+                // Does nothing without `--rumble`, like the other pulses.
+                RemoteAction::Rumble(duration) => feedback.rumble(device, duration)?,
+                RemoteAction::Sync => {
+                    // Handled by the discovery, and by every connection.
+                    let (reply, _) = mpsc::unbounded_channel();
+                    let request = Request::Sync(control::SYNC_WINDOW);
+                    let _ = sync.send(control::Command { request, reply });
+                }
+                RemoteAction::Profile(ix) => {
+                    info!(
+                        "Switched to profile {}: {}",
+                        ix + 1,
+                        keyboard.profile().1.name
+                    );
+                    feedback.count(device, ix as u32 + 1)?;
+                    display.flash(ix as u8 + 1, PROFILE_FLASH).await?;
+                }
+            }
+        }
         let maybe_event = tokio::select! {
-            res = event_stream.try_next() => res?,
+            res = event_stream.try_next() => match res {
+                Ok(event) => event,
+                // The device files close with the adapter, maybe before it's noticed.
+                Err(_) if adapter.is_some() && !adapter::is_available() => {
+                    return Ok(Exit::AdapterOff);
+                }
+                Err(err) => return Err(err),
+            },
+            _ = adapter_off(adapter.as_mut()) => return Ok(Exit::AdapterOff),
             _ = display.tick() => {
-                display.update().await?;
+                let percent = match display.update().await? {
+                    Some(percent) => percent,
+                    None => continue,
+                };
+                // Only warn once, the level may fluctuate around the threshold.
+                events.publish("battery", || format!(r#","level":{}"#, percent));
+                mqtt.battery(percent);
+                if battery_quarter.replace(percent / 25) != Some(percent / 25) {
+                    info!("Battery at {}%", percent);
+                }
+                if !low_battery && percent < low_percent {
+                    low_battery = true;
+                    warn!("Battery low: {}%", percent);
+                    cues.play(Cue::LowBattery);
+                    feedback.rumble(device, feedback::LOW_BATTERY)?;
+                    webhooks.notify(WebhookEvent::BatteryLow, "");
+                    if config.battery.notify {
+                        let summary = tr!("battery-low-summary", "Wii Remote battery low");
+                        let body = tr!(
+                            "battery-low-body",
+                            "Player {player}: {percent}% left",
+                            player = player.number(),
+                            percent = percent,
+                        );
+                        if let Err(err) = notification::send(&summary, &body) {
+                            warn!("Cannot show notification: {}", err);
+                        }
+                    }
+                }
+                continue;
+            }
+            _ = mute_toggle.recv() => {
+                let muted = !keyboard.is_muted();
+                keyboard.set_muted(muted);
+                display.set_muted(muted).await?;
+                info!("Keyboard output {}", if muted { "muted" } else { "unmuted" });
+                continue;
+            }
+            _ = keyboard.timer_elapsed() => {
+                keyboard.run_timers()?;
+                continue;
+            }
+            _ = lights_refresh.tick() => {
+                display.refresh()?;
+                continue;
+            }
+            _ = player.changed() => {
+                display.set_shared(player.is_shared()).await?;
+                continue;
+            }
+            _ = feedback.tick() => {
+                feedback.advance(device)?;
+                continue;
+            }
+            _ = stick_tick(stick.as_ref()) => {
+                if let Some(nudge) = stick.as_mut().and_then(Stick::repeat) {
+                    nudge_stick(keyboard, nudge)?;
+                }
+                continue;
+            }
+            Ok(()) = reloads.changed() => {
+                let profiles = mapping::profiles(&reloads.borrow());
+                let (ix, profile) = keyboard.set_profiles(profiles);
+                info!("Mappings reloaded, profile {}: {}", ix + 1, profile.name);
+                continue;
+            }
+            state = idle.tick() => match state {
+                IdleState::Warning => {
+                    info!("Idle, disconnecting in {}s", idle::WARNING.as_secs());
+                    feedback.rumble(device, feedback::IDLE_WARNING)?;
+                    // The inner lights, a pattern no level can produce.
+                    display.flash(0b0110, IDLE_FLASH).await?;
+                    continue;
+                }
+                IdleState::Elapsed => return Ok(Exit::Idle),
+            },
+            _ = handoff.recv() => return Ok(Exit::Released),
+            _ = shutdown.changed() => return Ok(Exit::Shutdown),
+            command = commands.recv() => {
+                // Requests missed while the device was released are dropped.
+                if let Ok(command) = command {
+                    let reply = match command.request {
+                        Request::Battery => format!("{}%", device.battery()?),
+                        Request::Status => {
+                            let (ix, profile) = keyboard.profile();
+                            format!(
+                                "battery {}%, profile {} ({}){}",
+                                device.battery()?,
+                                ix + 1,
+                                profile.name,
+                                if keyboard.is_muted() { ", muted" } else { "" }
+                            )
+                        }
+                        Request::Profile(name) => match keyboard.select_profile(&name) {
+                            Some((ix, profile)) => {
+                                info!("Switched to profile {}: {} (control)", ix + 1, profile.name);
+                                feedback.count(device, ix as u32 + 1)?;
+                                display.flash(ix as u8 + 1, PROFILE_FLASH).await?;
+                                format!("switched to profile {}", ix + 1)
+                            }
+                            None => format!("unknown profile `{}`", name),
+                        },
+                        Request::Rumble(duration) if feedback.is_enabled() => {
+                            feedback.rumble(device, duration)?;
+                            "rumbling".to_owned()
+                        }
+                        Request::Rumble(_) => "rumble is disabled, see --rumble".to_owned(),
+                        Request::Sync(duration) => {
+                            display.set_syncing(Some(Instant::now() + duration)).await?;
+                            format!("blinking for {}s", duration.as_secs())
+                        }
+                        Request::Lights(custom) => {
+                            display.set_custom(custom).await?;
+                            match custom {
+                                Some(pattern) => format!("lights set to {:04b}", pattern),
+                                None => "lights display the metric".to_owned(),
+                            }
+                        }
+                    };
+                    let _ = command.reply.send(format!("player {}: {}", player.number(), reply));
+                }
                 continue;
             }
         };
 
         let event: Event = match maybe_event {
             Some(event) => event,
-            None => return Ok(()), // connection closed
+            None => return Ok(Exit::Disconnected), // connection closed
+        };
+        let id = stats.receive();
+        let handling = async {
+            if matches!(
+                event.kind,
+                EventKind::Key(..)
+                    | EventKind::NunchukKey(..)
+                    | EventKind::ClassicControllerKey(..)
+                    | EventKind::ProControllerKey(..)
+                    | EventKind::DrumsKey(..)
+                    | EventKind::GuitarKey(..)
+            ) {
+                idle.reset();
+            }
+            if let Some(budget) = budget {
+                if !budget.admit(&event.kind) {
+                    stats.ignore(&event.kind, IgnoreReason::OverBudget);
+                    return Ok(None);
+                }
+            }
+            match &event.kind {
+                EventKind::Key(key, state) => {
+                    events.publish("button", || {
+                        output::button_fields(id, "remote", *key, *state)
+                    });
+                    mqtt.button(id, key, state);
+                    let chord = match (combinator.as_deref(), Button::from_key(key)) {
+                        (Some(combinator), Some(button)) if *state == KeyState::Down => {
+                            combinator.press(player.number(), button)
+                        }
+                        _ => None,
+                    };
+                    if let Some(action) = chord {
+                        info!("Cross-device chord completed");
+                        if let Some(reason) = keyboard.trigger(action)? {
+                            stats.ignore(&event.kind, reason);
+                        }
+                    }
+                    if *state == KeyState::Down && !keyboard.is_muted() {
+                        match keyboard.profile().1.click {
+                            Click::Off => {}
+                            Click::Speaker => {
+                                if let Some(speaker) = speaker {
+                                    speaker.play_sound(Sound::click());
+                                }
+                            }
+                            Click::Host => cues.play(Cue::Click),
+                        }
+                    }
+                }
+                EventKind::Accelerometer { x, y, z } => events.publish("accelerometer", || {
+                    format!(r#","id":{},"x":{},"y":{},"z":{}"#, id, x, y, z)
+                }),
+                _ => {}
+            }
+
+            if let (Some(stick), EventKind::NunchukMove { x, y, .. }) =
+                (stick.as_mut(), &event.kind)
+            {
+                if let Some(nudge) = stick.update(*x, *y) {
+                    if let Some(reason) = nudge_stick(keyboard, nudge)? {
+                        stats.ignore(&event.kind, reason);
+                    }
+                }
+            }
+
+            match event.kind {
+                EventKind::Key(key, state) => match key {
+                    Key::One => {
+                        one_held = state != KeyState::Up;
+                        display.set_metric(LightsMetric::Battery).await?
+                    }
+                    Key::Two if one_held && state == KeyState::Down => {
+                        numpad = match numpad {
+                            Some(_) => None,
+                            None => Some(Numpad::new()),
+                        };
+                        display
+                            .set_selection(numpad.as_ref().map(Numpad::position))
+                            .await?;
+                    }
+                    Key::Two => display.set_metric(LightsMetric::Connection).await?,
+                    _ if mouse_toggle.is_some() && Button::from_key(&key) == mouse_toggle => {
+                        if state == KeyState::Down {
+                            let pointer = pointer.as_mut().unwrap();
+                            let active = pointer.toggle();
+                            feedback.rumble(device, feedback::MAPPING_SWITCH)?;
+                            info!("Mouse mode {}", if active { "enabled" } else { "disabled" });
+                            if !active {
+                                // Don't leave a button pressed.
+                                keyboard.mouse_button(Mouse::Left, &KeyState::Up)?;
+                                keyboard.mouse_button(Mouse::Right, &KeyState::Up)?;
+                            }
+                        }
+                    }
+                    _ if steering_toggle.is_some() && Button::from_key(&key) == steering_toggle => {
+                        if state == KeyState::Down {
+                            let (active, turn) = steering.as_mut().unwrap().toggle();
+                            feedback.rumble(device, feedback::MAPPING_SWITCH)?;
+                            info!(
+                                "Steering mode {}",
+                                if active { "enabled" } else { "disabled" }
+                            );
+                            steer(keyboard, turn)?;
+                        }
+                    }
+                    Key::A | Key::B if pointer.as_ref().is_some_and(Pointer::is_active) => {
+                        let button = if key == Key::A {
+                            Mouse::Left
+                        } else {
+                            Mouse::Right
+                        };
+                        if let Some(reason) = keyboard.mouse_button(button, &state)? {
+                            stats.ignore(&event.kind, reason);
+                        }
+                    }
+                    Key::Plus if home_held => {
+                        if state == KeyState::Down {
+                            // Home is a modifier, don't turn the remote off
+                            // nor emit its key on release.
+                            keyboard.cancel_hold(Button::Home);
+                            let (ix, profile) = keyboard.cycle_profile();
+                            // Tell the profile number eyes-free.
+                            feedback.count(device, ix as u32 + 1)?;
+                            info!("Switched to profile {}: {}", ix + 1, profile.name);
+                            display.flash(ix as u8 + 1, PROFILE_FLASH).await?;
+                        }
+                    }
+                    _ if numpad.is_some() => {
+                        // Only act on presses, the numpad emits clicks. Still
+                        // forward releases, the key may have been pressed before
+                        // entering the numpad mode.
+                        if state != KeyState::Down {
+                            if state == KeyState::Up {
+                                keyboard.update(&key, &state)?;
+                            }
+                            return Ok(None);
+                        }
+                        let pad = numpad.as_mut().unwrap();
+                        if pad.navigate(&key) {
+                            display.set_selection(Some(pad.position())).await?;
+                        } else if key == Key::A {
+                            if let Some(reason) = keyboard.click(pad.selected())? {
+                                stats.ignore(&event.kind, reason);
+                            }
+                        } else if key == Key::B || key == Key::Home {
+                            numpad = None;
+                            display.set_selection(None).await?;
+                        } else {
+                            stats.ignore(&event.kind, IgnoreReason::Unmapped);
+                        }
+                    }
+                    _ => {
+                        if key == Key::Home {
+                            home_held = state != KeyState::Up;
+                        }
+                        if let Some(reason) = keyboard.update(&key, &state)? {
+                            stats.ignore(&event.kind, reason);
+                        }
+                    }
+                },
+                EventKind::Watch => return Ok(Some(Exit::ExtensionChanged)),
+                EventKind::Accelerometer { .. }
+                | EventKind::Ir(_)
+                | EventKind::MotionPlus { .. } => {
+                    if let (Some(fall), EventKind::Accelerometer { x, y, z }) =
+                        (fall.as_mut(), &event.kind)
+                    {
+                        if fall.update(Instant::now(), *x, *y, *z) {
+                            info!("Device dropped");
+                            // Impacts commonly offset the gyroscope.
+                            if let Some(pointer) = pointer.as_mut() {
+                                pointer.recalibrate();
+                            }
+                        }
+                    }
+                    if let (Some(stationary), EventKind::Accelerometer { x, y, z }) =
+                        (stationary.as_mut(), &event.kind)
+                    {
+                        stationary.update(Instant::now(), *x, *y, *z);
+                        keyboard.set_moving(!stationary.is_stationary());
+                    }
+                    if let (Some(shake), EventKind::Accelerometer { x, y, z }) =
+                        (shake.as_mut(), &event.kind)
+                    {
+                        if shake.update(Instant::now(), *x, *y, *z) {
+                            webhooks.notify(WebhookEvent::Gesture, "shake");
+                            if let Some(reason) = keyboard.gesture(Button::Shake)? {
+                                stats.ignore(&event.kind, reason);
+                            }
+                        }
+                    }
+                    let gesture = gestures
+                        .as_mut()
+                        .and_then(|gestures| gestures.update(Instant::now(), &event.kind));
+                    if let Some(gesture) = gesture {
+                        debug!("Gesture recognized: {}", gesture.name());
+                        webhooks.notify(WebhookEvent::Gesture, gesture.name());
+                        if let Some(reason) = keyboard.gesture(gesture.into())? {
+                            stats.ignore(&event.kind, reason);
+                        }
+                    }
+                    if let (Some(steering), EventKind::Accelerometer { y, .. }) =
+                        (steering.as_mut(), &event.kind)
+                    {
+                        if let Some(turn) = steering.update(*y) {
+                            if let Some(reason) = steer(keyboard, turn)? {
+                                stats.ignore(&event.kind, reason);
+                            }
+                        }
+                    }
+                    if let (Some(tracking), EventKind::Ir(sources)) =
+                        (head_tracking.as_mut(), &event.kind)
+                    {
+                        tracking.update(sources.iter().flatten().map(|s| (s.x, s.y)));
+                    }
+                    let motion = pointer
+                        .as_mut()
+                        .and_then(|pointer| pointer.motion(&event.kind));
+                    if let Some((dx, dy)) = motion {
+                        if let Some(reason) = keyboard.move_pointer(dx, dy)? {
+                            stats.ignore(&event.kind, reason);
+                        }
+                    }
+                    let position = pointer
+                        .as_mut()
+                        .and_then(|pointer| pointer.position(&event.kind));
+                    if let (Some(position), Some(absolute)) = (position, absolute.as_mut()) {
+                        if keyboard.is_muted() {
+                            stats.ignore(&event.kind, IgnoreReason::Muted);
+                        } else {
+                            absolute.move_to(position)?;
+                        }
+                    }
+                }
+                // Translated by the stick above.
+                EventKind::NunchukMove { .. } if extension.is_none() && stick.is_some() => {}
+                kind => match extension {
+                    Some(extension) => extension.update(&kind)?,
+                    None => stats.ignore(&kind, IgnoreReason::UnsupportedChannel),
+                },
+            }
+            Ok::<_, anyhow::Error>(None)
         };
+        // The logs of the handling are tagged with the event ID.
+        if let Some(exit) = handling.instrument(info_span!("event", id)).await? {
+            return Ok(exit);
+        }
+    }
+}
 
-        if let EventKind::Key(key, state) = event.kind {
-            match key {
-                Key::One => display.set_metric(LightsMetric::Battery).await?,
-                Key::Two => display.set_metric(LightsMetric::Connection).await?,
-                _ => keyboard.update(&key, &state)?,
-            };
+/// Waits until the Bluetooth adapter is turned off. Never completes
+/// without an adapter.
+async fn adapter_off(adapter: Option<&mut AdapterWatch>) {
+    match adapter {
+        Some(adapter) => adapter.wait_until(false).await,
+        None => futures_util::future::pending().await,
+    }
+}
+
+/// Emits the nudge of the Nunchuk stick.
+fn nudge_stick(keyboard: &mut Keyboard, nudge: Nudge) -> Result<Option<IgnoreReason>> {
+    match nudge {
+        Nudge::Key(key) => keyboard.click(key),
+        Nudge::Scroll(horizontal, vertical) => keyboard.scroll(horizontal, vertical),
+    }
+}
+
+/// Waits until the nudge of the stick repeats. Never completes without
+/// a stick.
+async fn stick_tick(stick: Option<&Stick>) {
+    match stick {
+        Some(stick) => stick.tick().await,
+        None => futures_util::future::pending().await,
+    }
+}
+
+/// Emits the key changes of the steering mode.
+fn steer(keyboard: &mut Keyboard, turn: Turn) -> Result<Option<IgnoreReason>> {
+    if let Some(key) = turn.release {
+        keyboard.set_pressed(key, false)?;
+    }
+    match turn.press {
+        Some(key) => keyboard.set_pressed(key, true),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiinote_core::keyboard::{Emitted, Input};
+
+    fn context(config: &str) -> Context {
+        let config = Arc::new(toml::from_str::<Config>(config).unwrap());
+        Context {
+            args: Args::parse_from(["wiinote"]),
+            reloads: watch::channel(config.clone()).1,
+            config,
+            cues: AudioCues::disabled(),
+            players: Players::new(),
+            budget: None,
+            combinator: None,
+            webhooks: Webhooks::new(Vec::new()),
+            mqtt: Mqtt::new(None),
+            opentrack: None,
+            bridge: None,
+            shutdown: watch::channel(false).0,
+            commands: broadcast::channel(16).0,
+            adapter: AdapterWatch::fixed(true),
+            events: Events::new(false),
         }
     }
+
+    fn keyboard(context: &Context) -> (Keyboard, Recorder) {
+        let recorder = Recorder::new();
+        let profiles = mapping::profiles(&context.config);
+        let keyboard = Keyboard::with_emitter(profiles, Box::new(recorder.clone()));
+        (keyboard, recorder)
+    }
+
+    /// Returns the events pressing and releasing the keys in order,
+    /// 100 ms apart.
+    fn presses(keys: &[(Key, KeyState)]) -> Vec<(Duration, EventKind)> {
+        (0..)
+            .map(|ix| Duration::from_millis(100 * ix))
+            .zip(keys.iter().map(|(key, state)| EventKind::Key(*key, *state)))
+            .collect()
+    }
+
+    fn key(key: uinput::event::keyboard::Key) -> Input {
+        Input::Key(uinput::event::Keyboard::Key(key))
+    }
+
+    async fn run(remote: &ScriptedRemote, keyboard: &mut Keyboard, context: &Context) -> Exit {
+        let mut session = Session::new(context, "gen10").unwrap();
+        handle(
+            remote,
+            keyboard,
+            &context.cues,
+            &context.config,
+            &mut session,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn disconnects_when_the_events_end() {
+        let context = context("");
+        let (mut keyboard, recorder) = keyboard(&context);
+        let events = presses(&[(Key::A, KeyState::Down), (Key::A, KeyState::Up)]);
+        let remote = ScriptedRemote::new(events, 100);
+        let exit = run(&remote, &mut keyboard, &context).await;
+        assert!(matches!(exit, Exit::Disconnected));
+        assert_eq!(
+            recorder.take(),
+            [
+                Emitted::Press(key(uinput::event::keyboard::Key::Enter)),
+                Emitted::Release(key(uinput::event::keyboard::Key::Enter)),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shows_the_battery_on_connection() {
+        let context = context("");
+        let (mut keyboard, _) = keyboard(&context);
+        // The battery is read on the first tick, once connected.
+        let events = presses(&[(Key::A, KeyState::Down), (Key::A, KeyState::Up)]);
+        let remote = ScriptedRemote::new(events, 40);
+        run(&remote, &mut keyboard, &context).await;
+        assert_eq!(remote.lights(), 0b0011);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn home_and_plus_switch_the_profile() {
+        let context = context("[[profiles]]\nname = \"media\"\nbindings = { A = \"PlayPause\" }");
+        let (mut keyboard, recorder) = keyboard(&context);
+        let events = presses(&[
+            (Key::Home, KeyState::Down),
+            (Key::Plus, KeyState::Down),
+            (Key::Plus, KeyState::Up),
+            (Key::Home, KeyState::Up),
+            (Key::A, KeyState::Down),
+            (Key::A, KeyState::Up),
+        ]);
+        let remote = ScriptedRemote::new(events, 100);
+        run(&remote, &mut keyboard, &context).await;
+        assert_eq!(keyboard.profile().1.name, "media");
+        // Home is a modifier, its key isn't emitted.
+        let play_pause = Input::Key(uinput::event::Keyboard::Misc(
+            uinput::event::keyboard::Misc::PlayPause,
+        ));
+        assert_eq!(
+            recorder.take(),
+            [Emitted::Press(play_pause), Emitted::Release(play_pause)]
+        );
+        // The profile number is still flashed.
+        assert_eq!(remote.lights(), 0b0010);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn holding_home_powers_off() {
+        let context = context("");
+        let (mut keyboard, recorder) = keyboard(&context);
+        let events = vec![
+            (Duration::ZERO, EventKind::Key(Key::Home, KeyState::Down)),
+            (
+                Duration::from_secs(4),
+                EventKind::Key(Key::Home, KeyState::Up),
+            ),
+        ];
+        let remote = ScriptedRemote::new(events, 100);
+        let exit = run(&remote, &mut keyboard, &context).await;
+        assert!(matches!(exit, Exit::PowerOff));
+        assert_eq!(recorder.take(), []);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pauses_when_the_adapter_turns_off() {
+        let context = context("");
+        let (mut keyboard, _) = keyboard(&context);
+        let events = presses(&[(Key::A, KeyState::Down), (Key::A, KeyState::Up)]);
+        let remote = ScriptedRemote::new(events, 100);
+        let mut session = Session::new(&context, "gen10").unwrap();
+        session.adapter = Some(AdapterWatch::fixed(false));
+        let (cues, config) = (&context.cues, &context.config);
+        let exit = handle(&remote, &mut keyboard, cues, config, &mut session).await;
+        assert!(matches!(exit, Ok(Exit::AdapterOff)));
+    }
 }
@@ -0,0 +1,77 @@
+use alsa::seq::{EvCtrl, EvNote, Event, EventType, PortCap, PortType, Seq};
+use anyhow::{Context, Result};
+use std::ffi::CString;
+use tracing::info;
+use wiinote_core::keyboard::device_name;
+use wiinote_core::midi::{MidiMapper, MidiMessage};
+use xwiimote::event::EventKind;
+
+/// An ALSA sequencer port sending the MIDI messages of a Wii Remote,
+/// which DAWs and synthesizers subscribe to (e.g. with `aconnect`).
+pub struct MidiPort {
+    seq: Seq,
+    port: i32,
+    mapper: MidiMapper,
+}
+
+impl MidiPort {
+    /// Creates the `Wiinote` sequencer client (scoped to the instance
+    /// name) with the port of the player.
+    pub fn try_new(mapper: MidiMapper, player: usize, instance: Option<&str>) -> Result<Self> {
+        let seq = Seq::open(None, Some(alsa::Direction::Playback), false)
+            .context("cannot open the ALSA sequencer")?;
+        let client = CString::new(device_name("Wiinote", instance))?;
+        seq.set_client_name(&client)?;
+        let name = CString::new(format!("Wii Remote {}", player))?;
+        let port = seq.create_simple_port(
+            &name,
+            PortCap::READ | PortCap::SUBS_READ,
+            PortType::MIDI_GENERIC | PortType::APPLICATION,
+        )?;
+        info!(
+            "MIDI port {}:{} created, on channel {}",
+            seq.client_id()?,
+            port,
+            mapper.channel() + 1
+        );
+        Ok(Self { seq, port, mapper })
+    }
+
+    /// Sends the messages of the event to the subscribers.
+    pub fn update(&mut self, event: &EventKind) -> Result<()> {
+        let channel = self.mapper.channel();
+        for message in self.mapper.update(event) {
+            let mut event = match message {
+                MidiMessage::NoteOn { note, velocity } => {
+                    Event::new(EventType::Noteon, &note_data(channel, note, velocity))
+                }
+                MidiMessage::NoteOff { note } => {
+                    Event::new(EventType::Noteoff, &note_data(channel, note, 0))
+                }
+                MidiMessage::Control { controller, value } => Event::new(
+                    EventType::Controller,
+                    &EvCtrl {
+                        channel,
+                        param: controller.into(),
+                        value: value.into(),
+                    },
+                ),
+            };
+            event.set_source(self.port);
+            event.set_subs();
+            event.set_direct();
+            self.seq.event_output_direct(&mut event)?;
+        }
+        Ok(())
+    }
+}
+
+fn note_data(channel: u8, note: u8, velocity: u8) -> EvNote {
+    EvNote {
+        channel,
+        note,
+        velocity,
+        off_velocity: 0,
+        duration: 0,
+    }
+}
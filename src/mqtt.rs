@@ -0,0 +1,389 @@
+use anyhow::{bail, ensure, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{info, warn};
+use wiinote_core::config::MqttConfig;
+use wiinote_core::mapping::Button;
+use wiinote_core::output;
+use xwiimote::event::{Key, KeyState};
+
+/// The buttons announced as Home Assistant device triggers.
+const BUTTONS: [Button; 9] = [
+    Button::Up,
+    Button::Down,
+    Button::Left,
+    Button::Right,
+    Button::A,
+    Button::B,
+    Button::Plus,
+    Button::Minus,
+    Button::Home,
+];
+
+/// The interval within which the broker expects a packet, in seconds.
+const KEEP_ALIVE: u16 = 60;
+
+/// How long to wait for the broker to accept the connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait before connecting again after a failure.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// The number of connections opened, telling the client IDs apart.
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// A message to publish.
+struct Message {
+    topic: String,
+    payload: String,
+    /// Whether the broker keeps the message for the future subscribers.
+    retain: bool,
+}
+
+/// Publishes the events of a Wii Remote to an MQTT broker, under
+/// `<topic>/player<number>/`:
+///
+/// - `status`: `connected` or `disconnected` (retained).
+/// - `battery`: the battery percentage (retained).
+/// - `button/<name>`: `down` or `up` on each press and release.
+/// - `event`: the presses and releases as the JSON objects sent to the
+///   WebSocket clients, with the event ID to correlate with the logs.
+///
+/// The messages of each player are published in order, in the
+/// background, over a connection of its own whose will sets the status
+/// to `disconnected` if it drops (e.g. when wiinote is killed). While
+/// the broker is unreachable, the messages are dropped and the
+/// retained ones published again once reconnected.
+#[derive(Clone)]
+pub struct Mqtt {
+    config: Option<Arc<MqttConfig>>,
+    queue: Option<mpsc::UnboundedSender<Message>>,
+    player: usize,
+}
+
+impl Mqtt {
+    /// Creates the publisher of the players, if a broker is configured.
+    pub fn new(config: Option<MqttConfig>) -> Self {
+        Self {
+            config: config.map(Arc::new),
+            queue: None,
+            player: 0,
+        }
+    }
+
+    /// Returns the publisher of the events of the given player, which
+    /// disconnects from the broker once dropped.
+    pub fn for_player(&self, player: usize) -> Self {
+        let queue = self.config.clone().map(|config| {
+            let (queue, messages) = mpsc::unbounded_channel();
+            tokio::spawn(run(config, player, messages));
+            queue
+        });
+        Self {
+            config: self.config.clone(),
+            queue,
+            player,
+        }
+    }
+
+    /// Publishes the connection and, if enabled, the Home Assistant
+    /// discovery payloads of the player's device.
+    pub fn connected(&self, kind: &str) {
+        if self.config.as_ref().is_some_and(|config| config.discovery) {
+            self.announce(kind);
+        }
+        self.send("status", "connected", true);
+    }
+
+    pub fn disconnected(&self) {
+        self.send("status", "disconnected", true);
+    }
+
+    pub fn battery(&self, percentage: u8) {
+        self.send("battery", &percentage.to_string(), true);
+    }
+
+    pub fn button(&self, id: u64, key: &Key, state: &KeyState) {
+        let payload = match state {
+            KeyState::Down => "down",
+            KeyState::Up => "up",
+            KeyState::AutoRepeat => return,
+        };
+        if let Some(button) = Button::from_key(key) {
+            self.send(&format!("button/{:?}", button), payload, false);
+            let fields = output::button_fields(id, "remote", *key, *state);
+            let event = output::format_event(self.player, "button", &fields);
+            self.send("event", &event, false);
+        }
+    }
+
+    /// Queues a message under the topic of the player.
+    fn send(&self, subtopic: &str, payload: &str, retain: bool) {
+        if let (Some(config), Some(queue)) = (&self.config, &self.queue) {
+            let _ = queue.send(Message {
+                topic: format!("{}/player{}/{}", config.topic, self.player, subtopic),
+                payload: payload.to_owned(),
+                retain,
+            });
+        }
+    }
+
+    /// Publishes the discovery payloads of a battery sensor, a
+    /// connectivity sensor and a trigger per button.
+    fn announce(&self, kind: &str) {
+        let (config, queue) = match (&self.config, &self.queue) {
+            (Some(config), Some(queue)) => (config, queue),
+            _ => return,
+        };
+        let id = format!("wiinote_player{}", self.player);
+        let state = format!("{}/player{}", config.topic, self.player);
+        let device = format!(
+            r#""device":{{"identifiers":["{id}"],"name":"Wii Remote {player}","model":"{kind}","manufacturer":"Nintendo"}}"#,
+            id = id,
+            player = self.player,
+            kind = kind,
+        );
+        let mut entities = vec![
+            (
+                format!("sensor/{}_battery", id),
+                format!(
+                    r#"{{"name":"Battery","unique_id":"{id}_battery","state_topic":"{state}/battery","unit_of_measurement":"%","device_class":"battery",{device}}}"#,
+                    id = id,
+                    state = state,
+                    device = device,
+                ),
+            ),
+            (
+                format!("binary_sensor/{}_status", id),
+                format!(
+                    r#"{{"name":"Connected","unique_id":"{id}_status","state_topic":"{state}/status","payload_on":"connected","payload_off":"disconnected","device_class":"connectivity",{device}}}"#,
+                    id = id,
+                    state = state,
+                    device = device,
+                ),
+            ),
+        ];
+        for button in BUTTONS {
+            entities.push((
+                format!("device_automation/{}_{:?}", id, button),
+                format!(
+                    r#"{{"automation_type":"trigger","topic":"{state}/button/{button:?}","payload":"down","type":"button_short_press","subtype":"{button:?}",{device}}}"#,
+                    state = state,
+                    button = button,
+                    device = device,
+                ),
+            ));
+        }
+        for (entity, payload) in entities {
+            let _ = queue.send(Message {
+                topic: format!("{}/{}/config", config.discovery_prefix, entity),
+                payload,
+                retain: true,
+            });
+        }
+    }
+}
+
+/// Publishes the messages of the player until its publishers are
+/// dropped, connecting to the broker on demand.
+async fn run(
+    config: Arc<MqttConfig>,
+    player: usize,
+    mut messages: mpsc::UnboundedReceiver<Message>,
+) {
+    let will = format!("{}/player{}/status", config.topic, player);
+    // The last payload of each retained topic, to restore them after
+    // reconnecting.
+    let mut retained = HashMap::new();
+    let mut connection: Option<TcpStream> = None;
+    let mut next_attempt = Instant::now();
+    let mut failing = false;
+    let mut keep_alive = tokio::time::interval(Duration::from_secs(KEEP_ALIVE.into()) / 2);
+    let mut received = [0; 64];
+    loop {
+        let message = tokio::select! {
+            message = messages.recv() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            _ = keep_alive.tick(), if connection.is_some() => {
+                let stream = connection.as_mut().unwrap();
+                if let Err(err) = stream.write_all(&packet(PINGREQ, &[])).await {
+                    warn!("Lost the connection to the MQTT broker: {}", err);
+                    connection = None;
+                }
+                continue;
+            }
+            // Only the ping responses are received, without QoS.
+            read = async { connection.as_mut().unwrap().read(&mut received).await }, if connection.is_some() => {
+                if !matches!(read, Ok(len) if len > 0) {
+                    warn!("The MQTT broker closed the connection");
+                    connection = None;
+                }
+                continue;
+            }
+        };
+        if message.retain {
+            retained.insert(message.topic.clone(), message.payload.clone());
+        }
+        if connection.is_none() {
+            if Instant::now() < next_attempt {
+                // The retained messages are published once reconnected.
+                continue;
+            }
+            let mut stream = match connect(&config, &will).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    // Only warn once until reconnected, the broker may be down for long.
+                    if !failing {
+                        warn!(
+                            "Cannot connect to the MQTT broker {}:{}: {:#}",
+                            config.host, config.port, err
+                        );
+                        failing = true;
+                    }
+                    next_attempt = Instant::now() + RECONNECT_DELAY;
+                    continue;
+                }
+            };
+            if failing {
+                info!("Reconnected to the MQTT broker");
+                failing = false;
+            }
+            let mut restored = Vec::new();
+            for (topic, payload) in &retained {
+                restored.extend(publish_packet(topic, payload, true));
+            }
+            if let Err(err) = stream.write_all(&restored).await {
+                warn!("Lost the connection to the MQTT broker: {}", err);
+                continue;
+            }
+            keep_alive.reset();
+            connection = Some(stream);
+            if message.retain {
+                // Already restored.
+                continue;
+            }
+        }
+        let stream = connection.as_mut().unwrap();
+        let packet = publish_packet(&message.topic, &message.payload, message.retain);
+        if let Err(err) = stream.write_all(&packet).await {
+            warn!("Cannot publish to {}: {}", message.topic, err);
+            connection = None;
+        }
+    }
+    if let Some(mut stream) = connection {
+        // Discards the will, the status is already published.
+        let _ = stream.write_all(&packet(DISCONNECT, &[])).await;
+    }
+}
+
+/// The MQTT 3.1.1 packet types, shifted into the fixed header.
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PINGREQ: u8 = 0xc0;
+const DISCONNECT: u8 = 0xe0;
+
+/// Opens a connection to the broker, whose will publishes a retained
+/// `disconnected` status to the given topic.
+async fn connect(config: &MqttConfig, will: &str) -> Result<TcpStream> {
+    let id = CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+    // Clean session, with a retained will at QoS 0.
+    let mut flags = 0x02 | 0x04 | 0x20;
+    let mut payload = Vec::new();
+    put_string(
+        &mut payload,
+        &format!("wiinote-{}-{}", std::process::id(), id),
+    );
+    put_string(&mut payload, will);
+    put_string(&mut payload, "disconnected");
+    match (&config.username, &config.password) {
+        (Some(username), password) => {
+            flags |= 0x80;
+            put_string(&mut payload, username);
+            if let Some(password) = password {
+                flags |= 0x40;
+                put_string(&mut payload, password);
+            }
+        }
+        (None, Some(_)) => bail!("a password requires a username"),
+        (None, None) => {}
+    }
+    let mut body = Vec::new();
+    put_string(&mut body, "MQTT");
+    body.extend([4, flags]);
+    body.extend(KEEP_ALIVE.to_be_bytes());
+    body.extend(payload);
+
+    let handshake = async {
+        let mut stream = TcpStream::connect((config.host.as_str(), config.port)).await?;
+        stream.write_all(&packet(CONNECT, &body)).await?;
+        let mut connack = [0; 4];
+        stream.read_exact(&mut connack).await?;
+        ensure!(connack[..2] == [CONNACK, 2], "not an MQTT broker");
+        match connack[3] {
+            0 => Ok(stream),
+            4 | 5 => bail!("the broker rejected the credentials"),
+            code => bail!("the broker refused the connection ({})", code),
+        }
+    };
+    match tokio::time::timeout(CONNECT_TIMEOUT, handshake).await {
+        Ok(stream) => stream,
+        Err(_) => bail!("the broker didn't answer"),
+    }
+}
+
+/// Returns the packet publishing the payload at QoS 0.
+fn publish_packet(topic: &str, payload: &str, retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_string(&mut body, topic);
+    body.extend(payload.as_bytes());
+    packet(PUBLISH | retain as u8, &body)
+}
+
+/// Returns the packet of the given type and flags with its body,
+/// prefixed by its variable-length size.
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        if len == 0 {
+            packet.push(byte);
+            break;
+        }
+        packet.push(byte | 0x80);
+    }
+    packet.extend(body);
+    packet
+}
+
+/// Appends the string prefixed by its length.
+fn put_string(buf: &mut Vec<u8>, string: &str) {
+    buf.extend((string.len() as u16).to_be_bytes());
+    buf.extend(string.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_the_publish_packets() {
+        assert_eq!(
+            publish_packet("a/b", "up", true),
+            [0x31, 7, 0, 3, b'a', b'/', b'b', b'u', b'p']
+        );
+        let packet = publish_packet("a", &"x".repeat(200), false);
+        assert_eq!(packet[..3], [0x30, 0xcb, 0x01]);
+        assert_eq!(packet.len(), 3 + 203);
+    }
+}
@@ -0,0 +1,20 @@
+use anyhow::{Context, Result};
+use tokio::process::Command;
+use tracing::warn;
+
+/// Shows a desktop notification with `notify-send`, without waiting
+/// for it to be delivered. Failures are logged once it exits.
+pub fn send(summary: &str, body: &str) -> Result<()> {
+    let mut child = Command::new("notify-send")
+        .args(["--app-name=wiinote", "--urgency=critical", summary, body])
+        .spawn()
+        .context("cannot run notify-send")?;
+    tokio::spawn(async move {
+        match child.wait().await {
+            Ok(status) if !status.success() => warn!("notify-send exited with {}", status),
+            Ok(_) => {}
+            Err(err) => warn!("Cannot wait for notify-send: {}", err),
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use std::net::{SocketAddr, UdpSocket};
+use std::rc::Rc;
+use tracing::{debug, warn};
+use wiinote_core::headtrack::HeadTracker;
+
+/// Sends the head pose tracked by the IR camera of a Wii Remote to
+/// opentrack, whose "UDP over network" input listens at the address
+/// (by default, port 4242).
+pub struct Opentrack {
+    socket: Rc<UdpSocket>,
+    address: SocketAddr,
+    tracker: HeadTracker,
+    /// Whether the last packet couldn't be sent, to only warn once.
+    failing: bool,
+}
+
+impl Opentrack {
+    pub fn new(address: SocketAddr) -> Result<Self> {
+        let local: SocketAddr = match address {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0; 8], 0).into(),
+        };
+        let socket = UdpSocket::bind(local).context("cannot bind the head tracking socket")?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket: Rc::new(socket),
+            address,
+            tracker: HeadTracker::new(),
+            failing: false,
+        })
+    }
+
+    /// Returns the sender of the head pose of another device, sharing
+    /// the socket.
+    pub fn for_device(&self) -> Self {
+        Self {
+            socket: self.socket.clone(),
+            address: self.address,
+            tracker: HeadTracker::new(),
+            failing: false,
+        }
+    }
+
+    /// Sends the head pose for the IR sources, if any is in sight.
+    pub fn update(&mut self, sources: impl IntoIterator<Item = (i32, i32)>) {
+        let pose = match self.tracker.update(sources) {
+            Some(pose) => pose,
+            None => return,
+        };
+        match self.socket.send_to(&pose.to_opentrack(), self.address) {
+            Ok(_) => self.failing = false,
+            Err(err) if self.failing => debug!("Cannot send the head pose: {}", err),
+            Err(err) => {
+                warn!("Cannot send the head pose to {}: {}", self.address, err);
+                self.failing = true;
+            }
+        }
+    }
+}
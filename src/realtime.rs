@@ -0,0 +1,36 @@
+use std::io::Error;
+use tracing::{info, warn};
+
+/// The `SCHED_FIFO` priority of the output thread. Kept low so the
+/// kernel threads handling Bluetooth and input still preempt it.
+const PRIORITY: libc::c_int = 10;
+
+/// Locks all the process memory and switches the calling thread to
+/// the `SCHED_FIFO` real-time policy.
+///
+/// Each step fails independently; errors are reported with a hint
+/// on the missing capability and the program continues with the
+/// default policy.
+pub fn enable() {
+    if unsafe { libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE) } != 0 {
+        warn!(
+            "Cannot lock memory: {}. Grant CAP_IPC_LOCK or raise the \
+             memlock limit (`ulimit -l`)",
+            Error::last_os_error()
+        );
+    }
+
+    let param = libc::sched_param {
+        sched_priority: PRIORITY,
+    };
+    if unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) } != 0 {
+        warn!(
+            "Cannot switch to real-time scheduling: {}. Grant CAP_SYS_NICE \
+             or raise the rtprio limit (`ulimit -r`) to at least {}",
+            Error::last_os_error(),
+            PRIORITY
+        );
+    } else {
+        info!("Real-time scheduling enabled");
+    }
+}
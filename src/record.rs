@@ -0,0 +1,49 @@
+use anyhow::{Context, Result};
+use futures_util::stream::TryStreamExt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use tracing::info;
+use wiinote_core::monitor;
+use wiinote_core::recording::Recorder;
+use xwiimote::{Address, Channels, Device};
+
+/// Records the button, accelerometer, IR and Motion Plus events of the
+/// device at the given path (by default, the first connected one) to
+/// the file, until it disconnects or Ctrl+C is pressed. Other programs
+/// keep receiving the events.
+pub async fn record(path: Option<PathBuf>, file: &Path) -> Result<()> {
+    let path = match path {
+        Some(path) => path,
+        None => monitor::connected_devices()?
+            .into_iter()
+            .next()
+            .context("no connected devices found")?,
+    };
+    let mut device = Device::connect(&Address::from(path.clone()))?;
+    let channels = Channels::CORE | Channels::ACCELEROMETER | Channels::IR | Channels::MOTION_PLUS;
+    device.open(device.available() & channels, false)?;
+    let mut event_stream = device.events()?;
+
+    let writer = File::create(file).with_context(|| format!("cannot create {}", file.display()))?;
+    let mut recorder = Recorder::new(BufWriter::new(writer))?;
+    info!(
+        "Recording {} to {}, press Ctrl+C to stop",
+        path.display(),
+        file.display()
+    );
+    let mut count = 0;
+    loop {
+        let event = tokio::select! {
+            event = event_stream.try_next() => event?,
+            _ = tokio::signal::ctrl_c() => None,
+        };
+        match event {
+            Some(event) => count += recorder.record(&event)? as u32,
+            None => break,
+        }
+    }
+    recorder.flush()?;
+    info!("Recorded {} events", count);
+    Ok(())
+}
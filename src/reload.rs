@@ -0,0 +1,61 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tracing::{error, info};
+use wiinote_core::config::Config;
+
+/// How often the modification time of the configuration file is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reloads the configuration when `SIGHUP` is received or the file
+/// changes, publishing it to the receivers of `configs` once changed
+/// by `overrides` (the command line options).
+///
+/// An invalid file is reported and ignored, the previous configuration
+/// stays in effect.
+pub async fn watch(
+    path: Option<PathBuf>,
+    overrides: impl Fn(&mut Config),
+    configs: watch::Sender<Arc<Config>>,
+) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            error!("Cannot listen for SIGHUP: {}", err);
+            return;
+        }
+    };
+    // The file the configuration is read from, if known.
+    let watched = path.clone().or_else(Config::default_path);
+    let mut modified = watched.as_deref().and_then(modified_time);
+    let mut check = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => {}
+            _ = check.tick() => {
+                let current = watched.as_deref().and_then(modified_time);
+                if current == modified {
+                    continue;
+                }
+                modified = current;
+            }
+        }
+        match Config::load(path.as_deref()) {
+            Ok(mut config) => {
+                overrides(&mut config);
+                info!("Configuration reloaded");
+                configs.send_replace(Arc::new(config));
+            }
+            Err(err) => error!("Cannot reload configuration: {:#}", err),
+        }
+    }
+}
+
+/// Returns the modification time of the file, if it exists.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
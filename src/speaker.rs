@@ -0,0 +1,25 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tracing::info;
+use wiinote_core::monitor;
+use wiinote_core::speaker::{Sound, Speaker};
+
+/// Plays the sound file on the speaker of the device at the given path
+/// (by default, the first connected one), returning once it ends.
+pub async fn play(path: Option<PathBuf>, sound: &Path) -> Result<()> {
+    let path = match path {
+        Some(path) => path,
+        None => monitor::connected_devices()?
+            .into_iter()
+            .next()
+            .context("no connected devices found")?,
+    };
+    let sound = Sound::load(sound)?;
+    let mut speaker = Speaker::open(&path)?;
+    info!(
+        "Playing {:.1}s of sound on {}",
+        sound.duration().as_secs_f64(),
+        path.display()
+    );
+    speaker.play(&sound).await
+}
@@ -0,0 +1,26 @@
+use std::os::unix::net::UnixDatagram;
+use tracing::warn;
+
+/// Sends a state change (e.g. `READY=1`) to systemd, if the service
+/// is of `Type=notify`. Failures are logged, the service keeps running.
+pub fn notify(state: &str) {
+    let path = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return,
+    };
+    let result = UnixDatagram::unbound().and_then(|socket| {
+        let path = path.to_string_lossy();
+        match path.strip_prefix('@') {
+            // A socket in the abstract namespace.
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                socket.send_to_addr(state.as_bytes(), &address)
+            }
+            None => socket.send_to(state.as_bytes(), &*path),
+        }
+    });
+    if let Err(err) = result {
+        warn!("Cannot notify systemd: {}", err);
+    }
+}
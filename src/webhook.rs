@@ -0,0 +1,110 @@
+use anyhow::{ensure, Context, Result};
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{error, warn};
+use wiinote_core::config::{WebhookConfig, WebhookEvent};
+
+/// The delay before the first retry, doubled after each attempt.
+const BACKOFF: Duration = Duration::from_secs(1);
+/// The maximum time a single request may take.
+const TIMEOUT: &str = "10";
+
+/// Sends HTTP requests on the events of a Wii Remote, with `curl`.
+#[derive(Clone)]
+pub struct Webhooks {
+    hooks: Rc<[WebhookConfig]>,
+    /// The kind of the Wii Remote, available as `{device}`.
+    device: String,
+    /// The player number, available as `{player}`.
+    player: usize,
+}
+
+impl Webhooks {
+    pub fn new(hooks: Vec<WebhookConfig>) -> Self {
+        Self {
+            hooks: hooks.into(),
+            device: String::new(),
+            player: 0,
+        }
+    }
+
+    /// Returns the webhooks sending the events of the given device.
+    pub fn for_device(&self, device: &str, player: usize) -> Self {
+        Self {
+            hooks: self.hooks.clone(),
+            device: device.to_owned(),
+            player,
+        }
+    }
+
+    /// Sends the event to the webhooks subscribed to it, in the background.
+    ///
+    /// The `detail` (e.g. the gesture name) is available as `{detail}`.
+    /// Failed requests are retried with exponential backoff, and are
+    /// dropped if the process exits in the meantime.
+    pub fn notify(&self, event: WebhookEvent, detail: &str) {
+        for hook in self.hooks.iter() {
+            if !hook.events.contains(&event) {
+                continue;
+            }
+            let body = hook
+                .body
+                .replace("{event}", &escape(event.name()))
+                .replace("{device}", &escape(&self.device))
+                .replace("{player}", &self.player.to_string())
+                .replace("{detail}", &escape(detail));
+            let url = hook.url.clone();
+            let retries = hook.retries;
+            tokio::spawn(async move {
+                if let Err(err) = send(&url, &body, retries).await {
+                    error!("Webhook {} failed: {}", url, err);
+                }
+            });
+        }
+    }
+}
+
+/// Posts the JSON body to the URL, retrying up to `retries` times.
+async fn send(url: &str, body: &str, retries: u32) -> Result<()> {
+    let mut backoff = BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match post(url, body).await {
+            Err(err) if attempt < retries => {
+                warn!("Webhook {} failed, retrying: {}", url, err);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn post(url: &str, body: &str) -> Result<()> {
+    let status = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--max-time", TIMEOUT])
+        .args(["--header", "Content-Type: application/json"])
+        .args(["--data-raw", body, url])
+        .status()
+        .await
+        .context("cannot run curl")?;
+    ensure!(status.success(), "curl exited with {}", status);
+    Ok(())
+}
+
+/// Escapes the value to be placed within a JSON string.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
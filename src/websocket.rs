@@ -0,0 +1,113 @@
+use crate::control::{self, Command, Request, REQUESTS};
+use crate::webhook::escape;
+use anyhow::{anyhow, Context, Error, Result};
+use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{info, warn};
+use wiinote_core::output::{EVENTS, SCHEMA_VERSION};
+
+/// The address of the WebSocket server, e.g. `ws://0.0.0.0:8765`.
+#[derive(Copy, Clone, Debug)]
+pub struct ListenAddress(SocketAddr);
+
+impl FromStr for ListenAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let address = s
+            .strip_prefix("ws://")
+            .ok_or_else(|| anyhow!("expected `ws://<host>:<port>`, got `{}`", s))?;
+        Ok(Self(address.trim_end_matches('/').parse()?))
+    }
+}
+
+/// Accepts WebSocket clients, sending them the events of the connected
+/// Wii Remotes as JSON text messages.
+///
+/// The clients can send the requests of the control socket (e.g.
+/// `rumble 200ms` or `lights 14`) as text messages; each reply is
+/// sent back as a `{"reply": "..."}` object. They may start with the
+/// handshake of the control socket, `hello <version>`, replied with
+/// e.g. `{"hello":{"version":1,"requests":["battery"],"events":["button"]}}`.
+pub async fn serve(
+    address: ListenAddress,
+    events: broadcast::Sender<String>,
+    commands: broadcast::Sender<Command>,
+) -> Result<()> {
+    let listener = TcpListener::bind(address.0)
+        .await
+        .with_context(|| format!("cannot listen on {}", address.0))?;
+    info!("WebSocket server listening on {}", address.0);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let events = events.subscribe();
+        let commands = commands.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve_client(stream, events, &commands).await {
+                warn!("WebSocket client {} error: {}", peer, err);
+            }
+        });
+    }
+}
+
+async fn serve_client(
+    stream: TcpStream,
+    mut events: broadcast::Receiver<String>,
+    commands: &broadcast::Sender<Command>,
+) -> Result<()> {
+    let mut socket = tokio_tungstenite::accept_async(stream).await?;
+    loop {
+        tokio::select! {
+            message = socket.next() => match message.transpose()? {
+                Some(Message::Text(text)) => {
+                    let replies = match control::handshake(&text) {
+                        Some(Ok(version)) => {
+                            socket.send(Message::Text(hello(version))).await?;
+                            continue;
+                        }
+                        Some(Err(err)) => vec![format!("error: {}", err)],
+                        None => match text.trim().parse::<Request>() {
+                            Ok(request) => control::forward(request, commands).await,
+                            Err(err) => vec![format!("error: {}", err)],
+                        },
+                    };
+                    for reply in replies {
+                        let reply = format!(r#"{{"reply":"{}"}}"#, escape(&reply));
+                        socket.send(Message::Text(reply)).await?;
+                    }
+                }
+                Some(Message::Close(_)) | None => return Ok(()),
+                // Pings are answered by the library.
+                Some(_) => {}
+            },
+            event = events.recv() => match event {
+                Ok(event) => socket.send(Message::Text(event)).await?,
+                // Skip the events missed by a slow client.
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return Ok(()),
+            },
+        }
+    }
+}
+
+/// Formats the reply to the handshake as JSON, see [`control::hello`].
+fn hello(version: u32) -> String {
+    let list = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| format!(r#""{}""#, name))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    format!(
+        r#"{{"hello":{{"version":{},"schema":{},"requests":[{}],"events":[{}]}}}}"#,
+        version,
+        SCHEMA_VERSION,
+        list(&REQUESTS),
+        list(&EVENTS)
+    )
+}
@@ -0,0 +1,45 @@
+use crate::error::UinputError;
+use crate::keyboard::{create_device, device_name};
+use anyhow::Result;
+use uinput::event::absolute::Position;
+use uinput::event::controller::Mouse;
+use uinput::event::{Absolute, Controller};
+
+/// The range of the axes, mapped to the screen by the compositor.
+const AXIS_MAX: i32 = 32767;
+
+/// A virtual pointer placing the cursor at absolute screen positions,
+/// like a graphics tablet, for the `ir-absolute` pointer source.
+///
+/// The clicks are still emitted by the [`Keyboard`](crate::keyboard::Keyboard),
+/// at the cursor position.
+pub struct AbsolutePointer {
+    device: uinput::Device,
+}
+
+impl AbsolutePointer {
+    pub fn try_new(instance: Option<&str>) -> Result<Self, UinputError> {
+        let name = device_name("Wiinote Pointer", instance);
+        let device = create_device(name, |mut builder| {
+            for axis in [Position::X, Position::Y] {
+                builder = builder
+                    .event(Absolute::Position(axis))?
+                    .min(0)
+                    .max(AXIS_MAX);
+            }
+            // Without a button, the device is taken for a joystick.
+            builder.event(Controller::Mouse(Mouse::Left))
+        })?;
+        Ok(Self { device })
+    }
+
+    /// Moves the cursor to the position, in fractions of the screen size.
+    pub fn move_to(&mut self, (x, y): (f64, f64)) -> Result<()> {
+        let scale = |value: f64| (value.clamp(0.0, 1.0) * AXIS_MAX as f64).round() as i32;
+        self.device
+            .position(&Absolute::Position(Position::X), scale(x))?;
+        self.device
+            .position(&Absolute::Position(Position::Y), scale(y))?;
+        self.device.synchronize().map_err(|err| err.into())
+    }
+}
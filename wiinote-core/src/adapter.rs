@@ -0,0 +1,117 @@
+use anyhow::{ensure, Context, Result};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::watch;
+
+/// The interval between checks while waiting for an adapter.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Returns whether a Bluetooth adapter is present and not blocked
+/// (neither by software nor by a hardware switch).
+pub fn is_available() -> bool {
+    let mut switches = match fs::read_dir("/sys/class/rfkill") {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| read_attr(path, "type").as_deref() == Some("bluetooth"))
+            .peekable(),
+        Err(_) => return has_adapter(),
+    };
+
+    if switches.peek().is_none() {
+        // The kernel has no rfkill support, or the adapter has no switch.
+        return has_adapter();
+    }
+    switches.any(|path| {
+        read_attr(&path, "soft").as_deref() == Some("0")
+            && read_attr(&path, "hard").as_deref() == Some("0")
+    })
+}
+
+/// The availability of the Bluetooth adapter, checked in the
+/// background so that the connections pause when it's turned off
+/// instead of failing on the closed device files.
+#[derive(Clone)]
+pub struct AdapterWatch {
+    available: watch::Receiver<bool>,
+}
+
+impl AdapterWatch {
+    /// Starts checking the adapter, until every watch is dropped.
+    pub fn spawn() -> Self {
+        let (sender, available) = watch::channel(is_available());
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                    _ = sender.closed() => return,
+                }
+                let now = is_available();
+                if *sender.borrow() != now {
+                    let _ = sender.send(now);
+                }
+            }
+        });
+        Self { available }
+    }
+
+    /// Returns a watch of an adapter that never changes, e.g. for tests.
+    pub fn fixed(available: bool) -> Self {
+        Self {
+            available: watch::channel(available).1,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        *self.available.borrow()
+    }
+
+    /// Waits until a Bluetooth adapter becomes available or, if not
+    /// `available`, until it's turned off or removed.
+    pub async fn wait_until(&mut self, available: bool) {
+        while *self.available.borrow_and_update() != available {
+            if self.available.changed().await.is_err() {
+                // A fixed watch.
+                futures_util::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+/// Returns the received signal strength of the connected device with the
+/// given Bluetooth address, in dB relative to the ideal range of the
+/// adapter: 0 within it, negative below.
+pub async fn rssi(address: &str) -> Result<i16> {
+    let output = Command::new("hcitool")
+        .args(["rssi", address])
+        .output()
+        .await
+        .context("cannot run hcitool")?;
+    ensure!(
+        output.status.success(),
+        "hcitool exited with {}",
+        output.status
+    );
+    // e.g. `RSSI return value: -7`
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .trim()
+        .rsplit(' ')
+        .next()
+        .and_then(|value| value.parse().ok())
+        .with_context(|| format!("unexpected hcitool output `{}`", stdout.trim()))
+}
+
+fn has_adapter() -> bool {
+    fs::read_dir("/sys/class/bluetooth")
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false)
+}
+
+fn read_attr(path: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(path.join(name))
+        .ok()
+        .map(|value| value.trim().to_owned())
+}
@@ -0,0 +1,127 @@
+use crate::error::UinputError;
+use crate::keyboard::{create_device, device_name};
+use anyhow::{bail, Error, Result};
+use std::str::FromStr;
+use uinput::event::absolute::Position;
+use uinput::event::Absolute;
+
+/// The total weight (in 10g units) below which the board is considered
+/// empty, and the center of mass meaningless.
+const MIN_WEIGHT: i32 = 200;
+/// The range of the center of mass axes.
+const AXIS_MAX: i32 = 1000;
+/// The maximum total weight (in 10g units) reported on the weight axis.
+const WEIGHT_MAX: i32 = 150 * 100;
+
+/// Where the Balance Board measurements are published.
+#[derive(Copy, Clone, Debug)]
+pub enum BalanceOutput {
+    /// A virtual device with the center of mass on the X/Y axes and
+    /// the total weight on the Z axis.
+    Axes,
+    /// A JSON object per line on the standard output.
+    Json,
+}
+
+impl FromStr for BalanceOutput {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "axes" => Ok(Self::Axes),
+            "json" => Ok(Self::Json),
+            _ => bail!("expected `axes` or `json`, got `{}`", s),
+        }
+    }
+}
+
+/// A measurement of the Balance Board.
+#[derive(Copy, Clone, Debug)]
+pub struct Measurement {
+    /// The total weight, in 10g units.
+    pub weight: i32,
+    /// The center of mass, from -1 (left/back) to 1 (right/front).
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Measurement {
+    /// Computes the measurement from the load cell values, ordered as
+    /// reported by xwiimote: top-right, bottom-right, top-left and
+    /// bottom-left.
+    pub fn from_cells(cells: &[i32; 4]) -> Self {
+        let [top_right, bottom_right, top_left, bottom_left] = *cells;
+        let weight = cells.iter().sum();
+        if weight < MIN_WEIGHT {
+            return Self {
+                weight,
+                x: 0.0,
+                y: 0.0,
+            };
+        }
+        let total = weight as f64;
+        Self {
+            weight,
+            x: ((top_right + bottom_right) - (top_left + bottom_left)) as f64 / total,
+            y: ((top_right + top_left) - (bottom_right + bottom_left)) as f64 / total,
+        }
+    }
+}
+
+/// Publishes the measurements of a Balance Board.
+pub struct BalanceBoard {
+    /// The virtual device, if publishing to axes.
+    device: Option<uinput::Device>,
+}
+
+impl BalanceBoard {
+    pub fn try_new(output: BalanceOutput, instance: Option<&str>) -> Result<Self, UinputError> {
+        let device = match output {
+            BalanceOutput::Axes => {
+                let name = device_name("Wiinote Balance Board", instance);
+                Some(create_device(name, |mut builder| {
+                    for axis in [Position::X, Position::Y] {
+                        builder = builder
+                            .event(Absolute::Position(axis))?
+                            .min(-AXIS_MAX)
+                            .max(AXIS_MAX);
+                    }
+                    Ok(builder
+                        .event(Absolute::Position(Position::Z))?
+                        .min(0)
+                        .max(WEIGHT_MAX))
+                })?)
+            }
+            BalanceOutput::Json => None,
+        };
+        Ok(Self { device })
+    }
+
+    pub fn update(&mut self, cells: &[i32; 4]) -> Result<()> {
+        let measurement = Measurement::from_cells(cells);
+        let device = match &mut self.device {
+            Some(device) => device,
+            None => {
+                println!(
+                    r#"{{"weight":{:.2},"x":{:.3},"y":{:.3}}}"#,
+                    measurement.weight as f64 / 100.0,
+                    measurement.x,
+                    measurement.y
+                );
+                return Ok(());
+            }
+        };
+
+        let axis = |value: f64| (value * AXIS_MAX as f64).round() as i32;
+        let axes = [
+            (Position::X, axis(measurement.x)),
+            // The Y axis grows downwards (towards the back of the board).
+            (Position::Y, -axis(measurement.y)),
+            (Position::Z, measurement.weight.min(WEIGHT_MAX)),
+        ];
+        for (axis, value) in axes {
+            device.position(&Absolute::Position(axis), value)?;
+        }
+        device.synchronize().map_err(|err| err.into())
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The polling interval used until the discharge rate is known.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(20);
+
+/// The bounds of the predicted polling interval. The upper bound
+/// limits the error of a bad prediction, e.g. after the remote
+/// starts using more power (rumble, speaker).
+const MIN_INTERVAL: Duration = Duration::from_secs(20);
+const MAX_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The number of samples used to estimate the discharge rate.
+const WINDOW: usize = 16;
+
+/// Estimates the discharge rate of a battery from its level history,
+/// to predict when the level will next cross a threshold.
+///
+/// The levels are percentages, as reported by the `hid-wiimote` driver
+/// (which scales the byte of the status report to 0-100).
+pub struct DischargeModel {
+    samples: VecDeque<(Instant, u8)>,
+}
+
+impl Default for DischargeModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DischargeModel {
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(WINDOW),
+        }
+    }
+
+    pub fn record(&mut self, time: Instant, level: u8) {
+        if self.samples.len() == WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((time, level));
+    }
+
+    /// Returns the discharge rate in percentage points per second, computed with
+    /// a least squares fit over the recent samples.
+    ///
+    /// Returns `None` if there is not enough history, or the battery
+    /// is not discharging (e.g. if it was replaced).
+    pub fn rate(&self) -> Option<f64> {
+        let (first, _) = *self.samples.front()?;
+        let points: Vec<(f64, f64)> = self
+            .samples
+            .iter()
+            .map(|(time, level)| ((*time - first).as_secs_f64(), *level as f64))
+            .collect();
+
+        let n = points.len() as f64;
+        let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+        let mean_l = points.iter().map(|(_, l)| l).sum::<f64>() / n;
+        let (mut cov, mut var) = (0.0, 0.0);
+        for (t, l) in &points {
+            cov += (t - mean_t) * (l - mean_l);
+            var += (t - mean_t) * (t - mean_t);
+        }
+        if var == 0.0 {
+            return None; // less than two distinct samples
+        }
+
+        let slope = cov / var;
+        (slope < 0.0).then(|| -slope)
+    }
+
+    /// Returns the time to wait before polling the level again, so
+    /// that the poll happens right after the level drops below the
+    /// highest threshold lower than or equal to the current level.
+    ///
+    /// Falls back to [`DEFAULT_INTERVAL`] without enough history.
+    pub fn next_poll(&self, thresholds: &[u8]) -> Duration {
+        let (level, rate) = match (self.samples.back(), self.rate()) {
+            (Some((_, level)), Some(rate)) => (*level, rate),
+            _ => return DEFAULT_INTERVAL,
+        };
+        let next = match thresholds.iter().filter(|t| **t <= level).max() {
+            Some(threshold) => *threshold,
+            None => return MAX_INTERVAL, // already below every threshold
+        };
+
+        // The level must fall below the threshold.
+        let drop = (level - next) as f64 + 1.0;
+        Duration::from_secs_f64(drop / rate).clamp(MIN_INTERVAL, MAX_INTERVAL)
+    }
+}
@@ -0,0 +1,261 @@
+use crate::recording::{self, take};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::fs::File;
+use std::io::{self, Read};
+use xwiimote::event::EventKind;
+
+/// The first bytes sent by a bridge server, with the protocol version.
+pub const MAGIC: &[u8; 8] = b"WIIBRG\x00\x02";
+
+/// The length of the random challenges of the handshake.
+pub const NONCE_LEN: usize = 16;
+
+/// The length of a proof of the shared secret, an HMAC-SHA1.
+pub const PROOF_LEN: usize = 20;
+
+/// The side of the bridge proving it knows the shared secret.
+#[derive(Copy, Clone, Debug)]
+pub enum Role {
+    Server,
+    Client,
+}
+
+/// Returns a random challenge.
+pub fn nonce() -> io::Result<[u8; NONCE_LEN]> {
+    let mut nonce = [0; NONCE_LEN];
+    File::open("/dev/urandom")?.read_exact(&mut nonce)?;
+    Ok(nonce)
+}
+
+/// Returns the proof that the given side knows the secret, for the
+/// challenges of both sides.
+///
+/// After the [`MAGIC`] header, the server sends its challenge; the
+/// client replies with its own challenge and its proof, and the server
+/// closes the connection if it's wrong or sends its proof otherwise.
+/// Each side thus checks the other knows the secret, without sending
+/// it. The frames aren't encrypted.
+pub fn proof(
+    secret: &[u8],
+    role: Role,
+    server_nonce: &[u8],
+    client_nonce: &[u8],
+) -> [u8; PROOF_LEN] {
+    proof_mac(secret, role, server_nonce, client_nonce)
+        .finalize()
+        .into_bytes()
+        .into()
+}
+
+/// Returns whether the proof of the given side is right, comparing it
+/// in constant time.
+pub fn verify(
+    secret: &[u8],
+    role: Role,
+    server_nonce: &[u8],
+    client_nonce: &[u8],
+    proof: &[u8],
+) -> bool {
+    proof_mac(secret, role, server_nonce, client_nonce)
+        .verify_slice(proof)
+        .is_ok()
+}
+
+/// Returns the HMAC-SHA1 of the role and challenges, see [`proof`].
+fn proof_mac(secret: &[u8], role: Role, server_nonce: &[u8], client_nonce: &[u8]) -> Hmac<Sha1> {
+    let role: &[u8] = match role {
+        Role::Server => b"server",
+        Role::Client => b"client",
+    };
+    hmac(secret, &[role, server_nonce, client_nonce])
+}
+
+/// Returns the HMAC-SHA1 (RFC 2104) of the concatenated parts.
+fn hmac(key: &[u8], parts: &[&[u8]]) -> Hmac<Sha1> {
+    // HMAC takes keys of any length.
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("invalid HMAC key");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+/// The kinds of the frames.
+const FRAME_CONNECTED: u8 = 0;
+const FRAME_EVENT: u8 = 1;
+const FRAME_BATTERY: u8 = 2;
+const FRAME_DISCONNECTED: u8 = 3;
+const FRAME_LED: u8 = 4;
+const FRAME_RUMBLE: u8 = 5;
+const FRAME_POWER_OFF: u8 = 6;
+
+/// A message of the bridge between a server, which handles the
+/// Bluetooth connection of the Wii Remotes, and a client emitting
+/// their mapped keys on another machine.
+///
+/// The remotes are told apart by an identifier, unique among the
+/// ones connected to the server. The server sends the connections,
+/// events and battery levels of the remotes; the client sends the
+/// changes to their lights and rumble motor, and asks to turn them
+/// off.
+///
+/// On the wire, each frame is its length (a little-endian `u16`), its
+/// kind, the remote identifier and the values of the frame, after the
+/// [`MAGIC`] header sent by the server and the handshake (see
+/// [`proof`]). The events are encoded as in a
+/// [recording](crate::recording::Recorder), without the time.
+#[derive(Clone, Debug)]
+pub enum Frame {
+    /// A remote of the given kind (e.g. `gen10`) connected.
+    Connected {
+        remote: u8,
+        kind: String,
+    },
+    /// The remote sent a button, accelerometer, IR or Motion Plus event.
+    Event {
+        remote: u8,
+        event: EventKind,
+    },
+    /// The battery percentage of the remote, sent once connected and
+    /// when it changes.
+    Battery {
+        remote: u8,
+        level: u8,
+    },
+    Disconnected {
+        remote: u8,
+    },
+    /// Turns the light (1 to 4) of the remote on or off.
+    Led {
+        remote: u8,
+        light: u8,
+        enabled: bool,
+    },
+    Rumble {
+        remote: u8,
+        enabled: bool,
+    },
+    /// Disconnects the remote, turning it off.
+    PowerOff {
+        remote: u8,
+    },
+}
+
+impl Frame {
+    /// Returns the identifier of the remote the frame is about.
+    pub fn remote(&self) -> u8 {
+        match *self {
+            Self::Connected { remote, .. }
+            | Self::Event { remote, .. }
+            | Self::Battery { remote, .. }
+            | Self::Disconnected { remote }
+            | Self::Led { remote, .. }
+            | Self::Rumble { remote, .. }
+            | Self::PowerOff { remote } => remote,
+        }
+    }
+
+    /// Returns the frame with its length, or `None` if it's an event
+    /// that isn't forwarded.
+    pub fn encode(&self) -> Option<Vec<u8>> {
+        let mut frame = vec![0, 0];
+        match self {
+            Self::Connected { remote, kind } => {
+                frame.extend([FRAME_CONNECTED, *remote]);
+                frame.extend(kind.as_bytes());
+            }
+            Self::Event { remote, event } => {
+                frame.extend([FRAME_EVENT, *remote]);
+                if !recording::encode_event(event, &mut frame) {
+                    return None;
+                }
+            }
+            Self::Battery { remote, level } => frame.extend([FRAME_BATTERY, *remote, *level]),
+            Self::Disconnected { remote } => frame.extend([FRAME_DISCONNECTED, *remote]),
+            Self::Led {
+                remote,
+                light,
+                enabled,
+            } => frame.extend([FRAME_LED, *remote, *light, *enabled as u8]),
+            Self::Rumble { remote, enabled } => {
+                frame.extend([FRAME_RUMBLE, *remote, *enabled as u8])
+            }
+            Self::PowerOff { remote } => frame.extend([FRAME_POWER_OFF, *remote]),
+        }
+        let len = u16::try_from(frame.len() - 2).ok()?;
+        frame[..2].copy_from_slice(&len.to_le_bytes());
+        Some(frame)
+    }
+
+    /// Decodes a frame without its length, or returns `None` if it's
+    /// malformed.
+    pub fn decode(mut frame: &[u8]) -> Option<Self> {
+        let header = take(&mut frame, 2)?;
+        let (kind, remote) = (header[0], header[1]);
+        let decoded = match kind {
+            FRAME_CONNECTED => Self::Connected {
+                remote,
+                kind: String::from_utf8(std::mem::take(&mut frame).to_vec()).ok()?,
+            },
+            FRAME_EVENT => Self::Event {
+                remote,
+                event: recording::decode_event(&mut frame)?,
+            },
+            FRAME_BATTERY => Self::Battery {
+                remote,
+                level: take(&mut frame, 1)?[0],
+            },
+            FRAME_DISCONNECTED => Self::Disconnected { remote },
+            FRAME_LED => {
+                let values = take(&mut frame, 2)?;
+                Self::Led {
+                    remote,
+                    light: values[0],
+                    enabled: values[1] != 0,
+                }
+            }
+            FRAME_RUMBLE => Self::Rumble {
+                remote,
+                enabled: take(&mut frame, 1)?[0] != 0,
+            },
+            FRAME_POWER_OFF => Self::PowerOff { remote },
+            _ => return None,
+        };
+        frame.is_empty().then_some(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmac_matches_the_rfc_2202_vectors() {
+        let digest = hmac(b"Jefe", &[b"what do ya ", b"want for nothing?"])
+            .finalize()
+            .into_bytes();
+        let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79");
+        let digest = hmac(
+            &[0xaa; 80],
+            &[b"Test Using Larger Than Block-Size Key - Hash Key First"],
+        )
+        .finalize()
+        .into_bytes();
+        let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, "aa4ae5e15272d00e95705637ce8a3b55ed402112");
+    }
+
+    #[test]
+    fn proofs_depend_on_the_role_and_secret() {
+        let server = proof(b"secret", Role::Server, b"server", b"client");
+        let client = proof(b"secret", Role::Client, b"server", b"client");
+        let verify =
+            |secret: &[u8], proof: &[u8]| verify(secret, Role::Server, b"server", b"client", proof);
+        assert!(verify(b"secret", &server));
+        assert!(!verify(b"secret", &client));
+        assert!(!verify(b"guess", &server));
+        assert!(!verify(b"secret", &server[1..]));
+    }
+}
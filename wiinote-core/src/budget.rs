@@ -0,0 +1,74 @@
+use crate::config::{BudgetConfig, MotionChannel, Priority};
+use std::cell::Cell;
+use tokio::time::Instant;
+use xwiimote::event::EventKind;
+
+/// Caps the rate of motion events handled across all the connected
+/// Wii Remotes, shedding the lower priority channels first.
+///
+/// Button events are never dropped (a dropped release would leave the
+/// key pressed), but they consume the budget.
+pub struct Budget {
+    /// The maximum number of events per second.
+    rate: f64,
+    /// The priority of each channel, indexed by [`MotionChannel`] to
+    /// avoid hashing on every event.
+    priorities: [Priority; MotionChannel::COUNT],
+    /// The events that can be handled right away, up to `rate`.
+    tokens: Cell<f64>,
+    last_refill: Cell<Instant>,
+}
+
+impl Budget {
+    pub fn new(config: &BudgetConfig) -> Self {
+        let rate = config.max_rate as f64;
+        let mut priorities = [Priority::default(); MotionChannel::COUNT];
+        for (channel, priority) in &config.priorities {
+            priorities[*channel as usize] = *priority;
+        }
+        Self {
+            rate,
+            priorities,
+            tokens: Cell::new(rate),
+            last_refill: Cell::new(Instant::now()),
+        }
+    }
+
+    /// Returns whether the event fits in the budget, consuming it if so.
+    pub fn admit(&self, event: &EventKind) -> bool {
+        let now = Instant::now();
+        let elapsed = (now - self.last_refill.replace(now)).as_secs_f64();
+        let tokens = (self.tokens.get() + elapsed * self.rate).min(self.rate);
+
+        // Keep part of the budget for the higher priority channels.
+        let reserve = match channel(event) {
+            None => 0.0,
+            Some(channel) => match self.priorities[channel as usize] {
+                Priority::High => 1.0,
+                Priority::Normal => 1.0 + self.rate / 4.0,
+                Priority::Low => 1.0 + self.rate / 2.0,
+            },
+        };
+        if tokens < reserve {
+            self.tokens.set(tokens);
+            return false;
+        }
+        // Button events may overdraw the budget, delaying the motion events.
+        self.tokens.set(tokens - 1.0);
+        true
+    }
+}
+
+/// Returns the channel of a motion event, or `None` for button events.
+fn channel(event: &EventKind) -> Option<MotionChannel> {
+    Some(match event {
+        EventKind::Accelerometer { .. } => MotionChannel::Accelerometer,
+        EventKind::Ir(_) => MotionChannel::Ir,
+        EventKind::MotionPlus { .. } => MotionChannel::MotionPlus,
+        EventKind::NunchukMove { .. }
+        | EventKind::ClassicControllerMove { .. }
+        | EventKind::ProControllerMove { .. } => MotionChannel::Extension,
+        EventKind::BalanceBoard(_) => MotionChannel::BalanceBoard,
+        _ => return None,
+    })
+}
@@ -0,0 +1,194 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// The resolution of the IR camera.
+pub const IR_WIDTH: f64 = 1024.0;
+pub const IR_HEIGHT: f64 = 768.0;
+
+/// The header of the calibration section in the configuration file.
+const SECTION: &str = "[calibration]";
+
+/// The version of the format of the calibration section written by
+/// [`store`], increased when it changes; see [`check_version`].
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A projective transformation from the IR camera coordinates to the
+/// screen, in fractions of its size.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(from = "[f64; 9]")]
+pub struct Homography {
+    /// The 3x3 matrix, row by row.
+    matrix: [f64; 9],
+}
+
+impl From<[f64; 9]> for Homography {
+    fn from(matrix: [f64; 9]) -> Self {
+        Self { matrix }
+    }
+}
+
+impl Default for Homography {
+    /// Maps the whole field of view of the camera to the screen. The
+    /// camera sees the sensor bar move in the opposite direction of the
+    /// pointer horizontally.
+    fn default() -> Self {
+        Self::from([
+            -1.0 / IR_WIDTH,
+            0.0,
+            1.0,
+            0.0,
+            1.0 / IR_HEIGHT,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+        ])
+    }
+}
+
+impl Homography {
+    /// Returns the transformation mapping each IR position to the
+    /// screen position of the same index, if no three of them are
+    /// aligned.
+    pub fn from_points(ir: [(f64, f64); 4], screen: [(f64, f64); 4]) -> Option<Self> {
+        // Solve the 8 equations of the matrix coefficients, the last
+        // one being 1, by Gaussian elimination.
+        let mut rows = [[0.0; 9]; 8];
+        for (i, ((x, y), (u, v))) in ir.into_iter().zip(screen).enumerate() {
+            rows[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, u];
+            rows[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, v];
+        }
+        for col in 0..8 {
+            let pivot = (col..8)
+                .max_by(|&a, &b| rows[a][col].abs().partial_cmp(&rows[b][col].abs()).unwrap())?;
+            if rows[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            rows.swap(col, pivot);
+            let pivot_row = rows[col];
+            for (ix, row) in rows.iter_mut().enumerate() {
+                if ix != col {
+                    let factor = row[col] / pivot_row[col];
+                    for (value, pivot_value) in row.iter_mut().zip(pivot_row).skip(col) {
+                        *value -= factor * pivot_value;
+                    }
+                }
+            }
+        }
+        let mut matrix = [1.0; 9];
+        for (i, row) in rows.iter().enumerate() {
+            matrix[i] = row[8] / row[i];
+        }
+        Some(Self { matrix })
+    }
+
+    /// Returns the screen position of the IR position, clamped to the
+    /// screen.
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let m = &self.matrix;
+        let w = m[6] * x + m[7] * y + m[8];
+        let u = (m[0] * x + m[1] * y + m[2]) / w;
+        let v = (m[3] * x + m[4] * y + m[5]) / w;
+        (u.clamp(0.0, 1.0), v.clamp(0.0, 1.0))
+    }
+}
+
+/// Returns the position the Wii Remote points at in the IR camera
+/// coordinates: the center of the visible sources, if any.
+pub fn ir_position(sources: impl IntoIterator<Item = (i32, i32)>) -> Option<(f64, f64)> {
+    let (mut x, mut y, mut count) = (0.0, 0.0, 0);
+    for source in sources {
+        x += source.0 as f64;
+        y += source.1 as f64;
+        count += 1;
+    }
+    (count > 0).then(|| (x / count as f64, y / count as f64))
+}
+
+/// Checks that a calibration section of the given format version can be
+/// read. Sections without a version predate it, and have the format of
+/// the first one.
+///
+/// Sections written by a newer wiinote are rejected rather than misread.
+pub fn check_version(version: Option<u32>) -> Result<()> {
+    // No format changed since the first version, convert the older
+    // sections when one does.
+    match version.unwrap_or(1) {
+        version if (1..=FORMAT_VERSION).contains(&version) => Ok(()),
+        version => bail!(
+            "unsupported calibration version {}, expected at most {}; run `wiinote calibrate` again",
+            version,
+            FORMAT_VERSION
+        ),
+    }
+}
+
+/// Writes the homography to the calibration section of the
+/// configuration file, replacing the previous one. The rest of the
+/// file is kept as is.
+pub fn store(path: &Path, homography: &Homography) -> Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("cannot read {}", path.display())),
+    };
+    // Drop the previous section, up to the next table header.
+    let mut kept = Vec::new();
+    let mut in_section = false;
+    for line in contents.lines() {
+        let header = line.trim_start().starts_with('[');
+        if header {
+            in_section = line.trim() == SECTION;
+        }
+        if !in_section {
+            kept.push(line);
+        }
+    }
+    while kept.last().is_some_and(|line| line.trim().is_empty()) {
+        kept.pop();
+    }
+    let values: Vec<_> = homography
+        .matrix
+        .iter()
+        .map(|value| value.to_string())
+        .collect();
+    let mut contents = kept.join("\n");
+    if !contents.is_empty() {
+        contents.push_str("\n\n");
+    }
+    contents.push_str(&format!(
+        "{}\nversion = {}\nhomography = [{}]\n",
+        SECTION,
+        FORMAT_VERSION,
+        values.join(", ")
+    ));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents).with_context(|| format!("cannot write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+
+    fn load(section: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(&format!(
+            "[calibration]\n{}homography = [1, 0, 0, 0, 1, 0, 0, 0, 1]\n",
+            section
+        ))
+    }
+
+    #[test]
+    fn checks_the_format_version() {
+        let current = load("version = 1\n").unwrap().calibration.unwrap();
+        // Unversioned sections have the first format.
+        let unversioned = load("").unwrap().calibration.unwrap();
+        assert_eq!(unversioned.homography, current.homography);
+        // Newer formats may not be read the same way.
+        assert!(load("version = 2\n").is_err());
+        assert!(load("version = 0\n").is_err());
+    }
+}
@@ -0,0 +1,28 @@
+use anyhow::{ensure, Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Places the text on the clipboard of the graphical session, using
+/// `wl-copy` on Wayland and `xclip` otherwise.
+///
+/// Both tools fork to keep serving the selection, so this doesn't
+/// block until the text is pasted.
+pub fn copy(text: &str) -> Result<()> {
+    let mut command = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        Command::new("wl-copy")
+    } else {
+        let mut command = Command::new("xclip");
+        command.args(["-selection", "clipboard"]);
+        command
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("cannot run {}", program))?;
+    // Close stdin once written, so the tool stops reading.
+    child.stdin.take().unwrap().write_all(text.as_bytes())?;
+    let status = child.wait()?;
+    ensure!(status.success(), "{} exited with {}", program, status);
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use crate::mapping::{Action, Button, CrossChord};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use tokio::time::Instant;
+
+/// Resolves the [cross-device chords](CrossChord) from the presses of
+/// all the connected Wii Remotes, above their own mappings.
+///
+/// The individual buttons still run their bindings: waiting for the
+/// other players would delay every press.
+pub struct Combinator {
+    chords: Vec<CrossChord>,
+    /// The last button pressed by each player, and when.
+    presses: RefCell<HashMap<usize, (Button, Instant)>>,
+}
+
+impl Combinator {
+    pub fn new(chords: Vec<CrossChord>) -> Self {
+        Self {
+            chords,
+            presses: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Records the press of a button by the player (numbered from 1).
+    ///
+    /// # Returns
+    /// The action of the chord completed by the press, if any. Its
+    /// presses are forgotten, holding the buttons doesn't run it again.
+    pub fn press(&self, player: usize, button: Button) -> Option<Action> {
+        let now = Instant::now();
+        let mut presses = self.presses.borrow_mut();
+        presses.insert(player, (button, now));
+        let chord = self.chords.iter().find(|chord| {
+            chord.buttons.get(player - 1) == Some(&button)
+                && chord.buttons.iter().enumerate().all(|(ix, button)| {
+                    matches!(
+                        presses.get(&(ix + 1)),
+                        Some((pressed, at)) if pressed == button && now - *at <= chord.window
+                    )
+                })
+        })?;
+        for player in 1..=chord.buttons.len() {
+            presses.remove(&player);
+        }
+        Some(chord.action.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::time::Duration;
+
+    fn combinator() -> Combinator {
+        let config: Config = toml::from_str(
+            r#"
+            [[cross_chords]]
+            buttons = ["A", "A"]
+            action = "Space"
+            "#,
+        )
+        .unwrap();
+        Combinator::new(config.cross_chords)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_when_the_players_press_together() {
+        let combinator = combinator();
+        assert_eq!(combinator.press(1, Button::A), None);
+        tokio::time::advance(Duration::from_millis(150)).await;
+        let action = combinator.press(2, Button::A).unwrap();
+        assert_eq!(action, "Space".parse().map(Action::Key).unwrap());
+        // Forgotten once run.
+        assert_eq!(combinator.press(2, Button::A), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ignores_the_presses_out_of_the_window() {
+        let combinator = combinator();
+        assert_eq!(combinator.press(1, Button::A), None);
+        tokio::time::advance(Duration::from_millis(250)).await;
+        assert_eq!(combinator.press(2, Button::A), None);
+        // Another button in between breaks the chord.
+        assert_eq!(combinator.press(1, Button::B), None);
+        assert_eq!(combinator.press(2, Button::A), None);
+        assert!(combinator.press(1, Button::A).is_some());
+    }
+}
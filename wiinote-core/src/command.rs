@@ -0,0 +1,23 @@
+use anyhow::{Context, Result};
+use tokio::process::Command;
+use tracing::warn;
+
+/// Runs the command line with `sh -c` without waiting for it to exit.
+/// Failures are logged once it does.
+pub fn spawn(command: &str) -> Result<()> {
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .spawn()
+        .context("cannot run sh")?;
+    let command = command.to_owned();
+    tokio::spawn(async move {
+        match child.wait().await {
+            Ok(status) if !status.success() => {
+                warn!("Command `{}` exited with {}", command, status)
+            }
+            Ok(_) => {}
+            Err(err) => warn!("Cannot wait for command `{}`: {}", command, err),
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use crate::mapping::Snap;
+use anyhow::{ensure, Context, Result};
+use std::process::Command;
+
+/// Tiles the active window by invoking the KWin shortcut with `dbus-send`.
+///
+/// Other compositors don't expose a way to move the windows of other
+/// clients (wlr-foreign-toplevel can only maximize them), so only
+/// KDE Plasma is supported.
+pub fn snap(snap: Snap) -> Result<()> {
+    let shortcut = match snap {
+        Snap::Left => "Window Quick Tile Left",
+        Snap::Right => "Window Quick Tile Right",
+        Snap::Top => "Window Quick Tile Top",
+        Snap::Bottom => "Window Quick Tile Bottom",
+        Snap::TopLeft => "Window Quick Tile Top Left",
+        Snap::TopRight => "Window Quick Tile Top Right",
+        Snap::BottomLeft => "Window Quick Tile Bottom Left",
+        Snap::BottomRight => "Window Quick Tile Bottom Right",
+        Snap::Maximize => "Window Maximize",
+    };
+    let status = Command::new("dbus-send")
+        .args([
+            "--session",
+            "--type=method_call",
+            "--dest=org.kde.kglobalaccel",
+            "/component/kwin",
+            "org.kde.kglobalaccel.Component.invokeShortcut",
+        ])
+        .arg(format!("string:{}", shortcut))
+        .status()
+        .context("cannot run dbus-send")?;
+    ensure!(status.success(), "dbus-send exited with {}", status);
+    Ok(())
+}
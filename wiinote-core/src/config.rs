@@ -0,0 +1,883 @@
+use crate::calibration::{self, Homography};
+use crate::error::ConfigError;
+use crate::expression::Expression;
+use crate::gesture::Gesture;
+use crate::mapping::{Action, Button, Chord, CrossChord, KeyCode};
+use crate::screen::Region;
+use anyhow::{anyhow, Error, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use uinput::event::{self, keyboard};
+
+/// The user configuration, read from a TOML file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The actions of each button, overriding the default bindings.
+    pub bindings: HashMap<Button, Action>,
+    /// The actions of each button when held; see [`Mapping`](crate::mapping::Mapping).
+    pub holds: HashMap<Button, Action>,
+    /// The actions of the buttons pressed together, shared by all profiles.
+    pub chords: Vec<Chord>,
+    /// The actions of the buttons pressed together on several Wii
+    /// Remotes, see [`Combinator`](crate::combinator::Combinator).
+    pub cross_chords: Vec<CrossChord>,
+    /// Additional sets of bindings to cycle through at runtime.
+    pub profiles: Vec<ProfileConfig>,
+    /// The click played on each button press, none by default.
+    pub click: Click,
+    /// The mouse mode settings. The mode is unavailable if not set.
+    pub mouse: Option<MouseConfig>,
+    /// The steering mode settings. The mode is unavailable if not set.
+    pub steering: Option<SteeringConfig>,
+    /// The navigation with the Nunchuk stick. The stick does nothing
+    /// if not set, unless exposed with `--extension-devices`.
+    pub stick: Option<StickConfig>,
+    /// The calibration of the `ir-absolute` pointer, written by
+    /// `wiinote calibrate`.
+    pub calibration: Option<CalibrationConfig>,
+    /// The cap on the rate of handled motion events. Unlimited if not set.
+    pub budget: Option<BudgetConfig>,
+    /// The HTTP endpoints notified of the device events.
+    pub webhooks: Vec<WebhookConfig>,
+    /// The low battery warning settings.
+    pub battery: BatteryConfig,
+    /// The inactivity disconnection settings. The remotes stay
+    /// connected if not set.
+    pub idle: Option<IdleConfig>,
+    /// Only runs the commands and power offs while the remote is
+    /// roughly stationary, if set.
+    pub stationary: Option<StationaryConfig>,
+    /// The MQTT broker the device events are published to, if any.
+    pub mqtt: Option<MqttConfig>,
+    /// The messages sent with `--output midi`.
+    pub midi: MidiConfig,
+    /// The Kodi instance controlled with `--output kodi`.
+    pub kodi: Option<KodiConfig>,
+    /// The settings of the bridge between `--serve` and `--remote`.
+    pub bridge: Option<BridgeConfig>,
+    /// The settings of specific Wii Remotes, by Bluetooth address.
+    pub devices: HashMap<String, DeviceConfig>,
+    /// The Bluetooth addresses of the only Wii Remotes to connect to,
+    /// if not empty.
+    pub allow: Vec<String>,
+    /// The Bluetooth addresses of the Wii Remotes to leave alone.
+    pub ignore: Vec<String>,
+}
+
+/// The settings of a specific Wii Remote.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceConfig {
+    /// The name of the profile activated when the Wii Remote connects.
+    pub profile: Option<String>,
+}
+
+/// The mouse mode, where the Wii Remote motion moves the pointer
+/// and A and B emit left and right clicks.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MouseConfig {
+    /// The button toggling the mouse mode.
+    pub toggle: Button,
+    #[serde(default)]
+    pub source: PointerSource,
+    /// The pointer speed multiplier.
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    /// The filter smoothing the jitter of the IR sources, if any.
+    pub smoothing: Option<SmoothingConfig>,
+    /// The part of the desktop the `ir-absolute` pointer is restricted
+    /// to, the whole desktop by default.
+    pub region: Option<Region>,
+    /// Whether the IR pointers keep following the rotation of the
+    /// remote, measured by the Motion Plus, while the sensor bar is out
+    /// of sight.
+    #[serde(default)]
+    pub gyro_fallback: bool,
+}
+
+/// A filter smoothing the jitter of a position, at the cost of latency.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(tag = "filter", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum SmoothingConfig {
+    /// The one euro filter, a low-pass filter whose cutoff frequency
+    /// grows with the speed, see <https://gery.casiez.net/1euro/>.
+    OneEuro {
+        /// The cutoff frequency (in Hz) when still: lower values
+        /// smooth more.
+        #[serde(default = "default_min_cutoff")]
+        min_cutoff: f64,
+        /// How fast the cutoff frequency grows with the speed (in IR
+        /// camera pixels per second): higher values lag less.
+        #[serde(default = "default_beta")]
+        beta: f64,
+        /// The cutoff frequency (in Hz) of the speed estimate.
+        #[serde(default = "default_derivative_cutoff")]
+        derivative_cutoff: f64,
+    },
+    /// An exponential moving average.
+    Exponential {
+        /// The time (in ms) to reach 63% of a sudden movement.
+        time_constant_ms: f64,
+    },
+}
+
+fn default_min_cutoff() -> f64 {
+    1.0
+}
+
+fn default_beta() -> f64 {
+    0.01
+}
+
+fn default_derivative_cutoff() -> f64 {
+    1.0
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+/// The steering mode, where tilting the remote held sideways presses
+/// the left and right keys, e.g. for racing games.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SteeringConfig {
+    /// The button toggling the steering mode.
+    pub toggle: Button,
+    #[serde(default = "default_left")]
+    pub left: KeyCode,
+    #[serde(default = "default_right")]
+    pub right: KeyCode,
+    /// The tilt (in accelerometer units, about 100 per g) within which
+    /// the pressed key is released.
+    #[serde(default = "default_deadzone")]
+    pub deadzone: i32,
+    /// The tilt beyond which the key of the direction is pressed.
+    #[serde(default = "default_threshold")]
+    pub threshold: i32,
+}
+
+fn default_left() -> KeyCode {
+    KeyCode(event::Keyboard::Key(keyboard::Key::Left))
+}
+
+fn default_right() -> KeyCode {
+    KeyCode(event::Keyboard::Key(keyboard::Key::Right))
+}
+
+fn default_deadzone() -> i32 {
+    10
+}
+
+fn default_threshold() -> i32 {
+    25
+}
+
+/// The Nunchuk stick translated into repeated arrow keys or scroll
+/// wheel notches, for desktop navigation.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StickConfig {
+    #[serde(default)]
+    pub mode: StickMode,
+    /// The deflection (about 100 at the edge) within which the stick
+    /// is released.
+    #[serde(default = "default_stick_deadzone")]
+    pub deadzone: i32,
+    /// The deflection beyond which the direction is pushed.
+    #[serde(default = "default_stick_threshold")]
+    pub threshold: i32,
+    /// The time between the repeated keys or notches while pushed.
+    #[serde(default = "default_repeat_ms")]
+    pub repeat_ms: u64,
+}
+
+/// What pushing the Nunchuk stick emits.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StickMode {
+    /// The arrow key of the direction.
+    #[default]
+    Arrows,
+    /// Notches of the vertical or horizontal scroll wheel.
+    Scroll,
+}
+
+fn default_stick_deadzone() -> i32 {
+    20
+}
+
+fn default_stick_threshold() -> i32 {
+    60
+}
+
+fn default_repeat_ms() -> u64 {
+    150
+}
+
+/// The sensor driving the pointer.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PointerSource {
+    /// Follow the sensor bar seen by the IR camera.
+    #[default]
+    Ir,
+    /// Move at a speed proportional to the tilt of the remote.
+    Accelerometer,
+    /// Follow the rotation of the remote measured by the Motion Plus
+    /// gyroscope ("air mouse").
+    MotionPlus,
+    /// Place the pointer where the remote points on the screen, seen
+    /// by the IR camera, according to the calibration.
+    IrAbsolute,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawCalibrationConfig")]
+pub struct CalibrationConfig {
+    /// The transformation from the IR camera to the screen.
+    pub homography: Homography,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCalibrationConfig {
+    /// The format of the section, see [`calibration::FORMAT_VERSION`].
+    version: Option<u32>,
+    homography: Homography,
+}
+
+impl TryFrom<RawCalibrationConfig> for CalibrationConfig {
+    type Error = Error;
+
+    fn try_from(raw: RawCalibrationConfig) -> Result<Self> {
+        calibration::check_version(raw.version)?;
+        Ok(Self {
+            homography: raw.homography,
+        })
+    }
+}
+
+/// A named set of bindings, applied on top of the top-level bindings.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub name: String,
+    #[serde(default)]
+    pub bindings: HashMap<Button, Action>,
+    #[serde(default)]
+    pub holds: HashMap<Button, Action>,
+    /// Overrides the top-level click.
+    pub click: Option<Click>,
+    /// The extension (`nunchuk`, `classic`...) whose plugging activates
+    /// the profile. The previous profile is restored once unplugged.
+    pub extension: Option<String>,
+}
+
+/// Where the click of a button press is played, e.g. to hear the
+/// presses of a presentation clicker.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Click {
+    #[default]
+    Off,
+    /// A beep on the speaker of the Wii Remote.
+    Speaker,
+    /// A tick on the host speakers, for remotes whose speaker can't
+    /// be used. Requires the `audio` feature.
+    Host,
+}
+
+/// When and how the user is warned that the battery is running low,
+/// besides the log message, the audio cue and the rumble pulse (with
+/// `--rumble`).
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatteryConfig {
+    /// The battery percentage below which the warning is issued.
+    pub low: u8,
+    /// Whether to show a desktop notification.
+    pub notify: bool,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            low: 10,
+            notify: false,
+        }
+    }
+}
+
+/// A Kodi instance controlled with `--output kodi` through its JSON-RPC
+/// API, possibly on another machine.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KodiConfig {
+    /// The HTTP endpoint, e.g. `http://htpc.local:8080/jsonrpc`.
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The action of each button, replacing its default: the name of a
+    /// Kodi action (e.g. `playpause`) or an `Input` method (e.g.
+    /// `Input.ShowOSD`).
+    #[serde(default)]
+    pub actions: HashMap<Button, String>,
+}
+
+/// The bridge forwarding the remotes of a `--serve` server to a
+/// `--remote` client.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BridgeConfig {
+    /// The secret shared by the server and its clients, which prove
+    /// to each other they know it when connecting.
+    pub secret: String,
+}
+
+/// The MIDI messages of the buttons and motion of the Wii Remotes,
+/// sent with `--output midi`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MidiConfig {
+    /// The MIDI channel of the messages, from 1 to 16.
+    pub channel: u8,
+    /// The velocity of the notes, from 1 to 127.
+    pub velocity: u8,
+    /// The message of each button. By default, the buttons play the
+    /// notes of a chromatic scale from middle C (60).
+    pub buttons: HashMap<Button, MidiControl>,
+    /// The continuous controller of each motion axis. By default, the
+    /// tilt sends the modulation wheel (1) and breath (2) controllers,
+    /// and the IR pointer the general purpose ones (16 and 17).
+    pub axes: HashMap<MidiAxis, MidiAxisControl>,
+}
+
+impl Default for MidiConfig {
+    fn default() -> Self {
+        let buttons = [
+            Button::A,
+            Button::B,
+            Button::Up,
+            Button::Down,
+            Button::Left,
+            Button::Right,
+            Button::Minus,
+            Button::Plus,
+            Button::Home,
+        ];
+        Self {
+            channel: 1,
+            velocity: 100,
+            buttons: buttons
+                .into_iter()
+                .zip(60..)
+                .map(|(button, note)| (button, MidiControl::Note(note)))
+                .collect(),
+            axes: HashMap::from([
+                (MidiAxis::Pitch, MidiAxisControl::from(1)),
+                (MidiAxis::Roll, MidiAxisControl::from(2)),
+                (MidiAxis::PointerX, MidiAxisControl::from(16)),
+                (MidiAxis::PointerY, MidiAxisControl::from(17)),
+            ]),
+        }
+    }
+}
+
+/// The MIDI message of a button, e.g. `{ note = 60 }`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub enum MidiControl {
+    /// A note, played while the button is held.
+    Note(u8),
+    /// A controller, set to 127 while the button is held and 0
+    /// otherwise (e.g. 64 for the sustain pedal).
+    Cc(u8),
+}
+
+/// A motion axis of the Wii Remote, sent as a continuous controller.
+// Deserialized from a string, TOML table keys don't support enums.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum MidiAxis {
+    /// The tilt of the remote up and down, from pointing at the floor
+    /// (0) to the ceiling (127).
+    Pitch,
+    /// The rotation of the remote around its pointing axis, from
+    /// rolled left (0) to right (127).
+    Roll,
+    /// The horizontal position pointed at with the IR camera, from
+    /// left (0) to right (127).
+    PointerX,
+    /// The vertical position pointed at with the IR camera, from top
+    /// (0) to bottom (127).
+    PointerY,
+}
+
+impl MidiAxis {
+    pub const ALL: [MidiAxis; 4] = [
+        MidiAxis::Pitch,
+        MidiAxis::Roll,
+        MidiAxis::PointerX,
+        MidiAxis::PointerY,
+    ];
+
+    /// Returns the name of the axis in the expressions of a
+    /// [`MidiAxisControl`], e.g. `pointer_x`.
+    pub fn variable(self) -> &'static str {
+        match self {
+            Self::Pitch => "pitch",
+            Self::Roll => "roll",
+            Self::PointerX => "pointer_x",
+            Self::PointerY => "pointer_y",
+        }
+    }
+}
+
+impl FromStr for MidiAxis {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "pitch" => Self::Pitch,
+            "roll" => Self::Roll,
+            "pointer-x" => Self::PointerX,
+            "pointer-y" => Self::PointerY,
+            _ => return Err(anyhow!("unknown axis `{}`", s)),
+        })
+    }
+}
+
+impl TryFrom<String> for MidiAxis {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+/// The continuous controller of a motion axis, either its number or
+/// e.g. `{ cc = 1, value = "clamp(pitch * 2 - 0.5, 0, 1)" }`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "RawMidiAxisControl")]
+pub struct MidiAxisControl {
+    /// The controller number.
+    pub cc: u8,
+    /// The value of the controller, as a fraction (from 0 to 1) of its
+    /// range computed from those of the axes, named `pitch`, `roll`,
+    /// `pointer_x` and `pointer_y`. By default, the fraction of the
+    /// axis itself.
+    pub value: Option<Expression>,
+}
+
+impl From<u8> for MidiAxisControl {
+    fn from(cc: u8) -> Self {
+        Self { cc, value: None }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum RawMidiAxisControl {
+    Cc(u8),
+    // Parse the expression in `try_from` to keep its error message,
+    // untagged enums only report that no variant matched.
+    Expression { cc: u8, value: String },
+}
+
+impl TryFrom<RawMidiAxisControl> for MidiAxisControl {
+    type Error = Error;
+
+    fn try_from(raw: RawMidiAxisControl) -> Result<Self> {
+        let (cc, value) = match raw {
+            RawMidiAxisControl::Cc(cc) => return Ok(cc.into()),
+            RawMidiAxisControl::Expression { cc, value } => (cc, value.parse::<Expression>()?),
+        };
+        for name in value.variables() {
+            if !MidiAxis::ALL.iter().any(|axis| axis.variable() == name) {
+                return Err(anyhow!(
+                    "unknown variable `{}`, expected pitch, roll, pointer_x or pointer_y",
+                    name
+                ));
+            }
+        }
+        Ok(Self {
+            cc,
+            value: Some(value),
+        })
+    }
+}
+
+/// When the Wii Remotes are disconnected after no button was pressed
+/// for a while, to save their batteries.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IdleConfig {
+    /// The inactivity period, in minutes.
+    pub timeout: u64,
+    /// Whether to rumble (with `--rumble`) and blink the lights
+    /// 30 seconds before disconnecting.
+    #[serde(default = "default_warn")]
+    pub warn: bool,
+}
+
+fn default_warn() -> bool {
+    true
+}
+
+impl IdleConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout * 60)
+    }
+}
+
+/// The motion below which a Wii Remote is roughly stationary, see
+/// [`StationaryGuard`](crate::stationary::StationaryGuard).
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StationaryConfig {
+    /// The largest variance of the acceleration over the window, in
+    /// accelerometer units squared (1 g is about 100 units).
+    #[serde(default = "default_max_variance")]
+    pub max_variance: f64,
+    /// The period over which the variance is measured, in milliseconds.
+    #[serde(default = "default_stationary_window")]
+    pub window_ms: u64,
+}
+
+fn default_max_variance() -> f64 {
+    25.0
+}
+
+fn default_stationary_window() -> u64 {
+    250
+}
+
+/// The rate of events handled across all the connected Wii Remotes,
+/// to protect slow hosts from motion-heavy channels.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BudgetConfig {
+    /// The maximum number of events per second.
+    pub max_rate: u32,
+    /// The order in which the channels are dropped when over budget.
+    #[serde(default)]
+    pub priorities: HashMap<MotionChannel, Priority>,
+}
+
+/// The channels producing continuous motion events.
+// Deserialized from a string, TOML table keys don't support enums.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum MotionChannel {
+    Accelerometer,
+    Ir,
+    MotionPlus,
+    /// The motion of any extension (Nunchuk, Classic Controller...).
+    Extension,
+    BalanceBoard,
+}
+
+impl MotionChannel {
+    pub const COUNT: usize = 5;
+}
+
+impl FromStr for MotionChannel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "accelerometer" => Self::Accelerometer,
+            "ir" => Self::Ir,
+            "motion-plus" => Self::MotionPlus,
+            "extension" => Self::Extension,
+            "balance-board" => Self::BalanceBoard,
+            _ => return Err(anyhow!("unknown channel `{}`", s)),
+        })
+    }
+}
+
+impl TryFrom<String> for MotionChannel {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// An HTTP endpoint receiving a POST request with a JSON body on
+/// the selected events, e.g. a Node-RED flow.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    /// The request body, where `{event}`, `{device}`, `{player}` and
+    /// `{detail}` are replaced by their values.
+    #[serde(default = "default_body")]
+    pub body: String,
+    /// The number of times a failed request is retried.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+fn default_body() -> String {
+    r#"{"event": "{event}", "device": "{device}", "player": {player}, "detail": "{detail}"}"#
+        .to_owned()
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+/// An MQTT broker receiving the device events, e.g. for a smart-home
+/// controller; see the `wiinote` README for the topics.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MqttConfig {
+    #[serde(default = "default_mqtt_host")]
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The prefix of the topics.
+    #[serde(default = "default_mqtt_topic")]
+    pub topic: String,
+    /// Whether to publish the Home Assistant discovery payloads.
+    #[serde(default)]
+    pub discovery: bool,
+    #[serde(default = "default_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+fn default_mqtt_host() -> String {
+    "localhost".to_owned()
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_topic() -> String {
+    "wiinote".to_owned()
+}
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_owned()
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WebhookEvent {
+    Connected,
+    Disconnected,
+    BatteryLow,
+    /// A bound gesture was detected; its name is the detail.
+    Gesture,
+}
+
+impl WebhookEvent {
+    /// Returns the name of the event, as written in the configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Connected => "connected",
+            Self::Disconnected => "disconnected",
+            Self::BatteryLow => "battery-low",
+            Self::Gesture => "gesture",
+        }
+    }
+}
+
+impl Config {
+    /// Returns whether the button is bound in any profile.
+    pub fn binds(&self, button: Button) -> bool {
+        self.bindings.contains_key(&button)
+            || self
+                .profiles
+                .iter()
+                .any(|profile| profile.bindings.contains_key(&button))
+    }
+
+    /// Returns whether a [`Gesture`] is bound in any profile.
+    pub fn binds_gesture(&self) -> bool {
+        Gesture::ALL
+            .into_iter()
+            .any(|gesture| self.binds(gesture.into()))
+    }
+
+    /// Returns whether a button is bound to the [pointer](Action::Pointer)
+    /// in any profile, as a regular, hold or chord action.
+    pub fn binds_pointer(&self) -> bool {
+        let bindings = [&self.bindings, &self.holds].into_iter().chain(
+            self.profiles
+                .iter()
+                .flat_map(|profile| [&profile.bindings, &profile.holds]),
+        );
+        bindings
+            .flat_map(|bindings| bindings.values())
+            .chain(self.chords.iter().map(|chord| &chord.action))
+            .any(|action| action.includes(&Action::Pointer))
+    }
+
+    /// Binds the buttons like a presentation clicker: A and Right go to
+    /// the next slide, B and Left to the previous one, and holding B
+    /// moves the pointer. The other bindings are kept.
+    pub fn apply_presentation(&mut self) {
+        let key = |key| Action::Key(KeyCode(event::Keyboard::Key(key)));
+        for (button, action) in [
+            (Button::A, key(keyboard::Key::Right)),
+            (Button::Right, key(keyboard::Key::Right)),
+            (Button::B, key(keyboard::Key::Left)),
+            (Button::Left, key(keyboard::Key::Left)),
+        ] {
+            self.bindings.insert(button, action);
+        }
+        self.holds.insert(Button::B, Action::Pointer);
+    }
+
+    /// Returns the settings of the Wii Remote with the given Bluetooth
+    /// address, compared case-insensitively.
+    pub fn device(&self, address: &str) -> Option<&DeviceConfig> {
+        self.devices
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(address))
+            .map(|(_, device)| device)
+    }
+
+    /// Returns whether to connect to the Wii Remote with the given
+    /// Bluetooth address, if known, according to the `allow` and
+    /// `ignore` lists.
+    pub fn accepts(&self, address: Option<&str>) -> bool {
+        let listed = |list: &[String]| {
+            address.is_some_and(|address| {
+                list.iter()
+                    .any(|listed| listed.eq_ignore_ascii_case(address))
+            })
+        };
+        (self.allow.is_empty() || listed(&self.allow)) && !listed(&self.ignore)
+    }
+
+    /// Returns the default location of the configuration file,
+    /// `$XDG_CONFIG_HOME/wiinote/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("wiinote").join("config.toml"))
+    }
+
+    /// Loads the configuration file at the given path.
+    ///
+    /// If no path is given, the file at the [default location](Self::default_path)
+    /// is loaded if present; otherwise, the default configuration is returned.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let path = match path {
+            Some(path) => path.to_owned(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(source) => return Err(ConfigError::Read { path, source }),
+        };
+        toml::from_str(&contents).map_err(|source| ConfigError::Parse { path, source })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_budget_priorities() {
+        let config: Config = toml::from_str(
+            r#"
+            [budget]
+            max_rate = 200
+
+            [budget.priorities]
+            accelerometer = "low"
+            ir = "high"
+            motion-plus = "normal"
+            extension = "low"
+            balance-board = "high"
+            "#,
+        )
+        .unwrap();
+        let priorities = config.budget.unwrap().priorities;
+        assert_eq!(priorities.len(), MotionChannel::COUNT);
+        assert!(matches!(
+            priorities[&MotionChannel::Accelerometer],
+            Priority::Low
+        ));
+        assert!(matches!(priorities[&MotionChannel::Ir], Priority::High));
+        assert!(matches!(
+            priorities[&MotionChannel::MotionPlus],
+            Priority::Normal
+        ));
+        assert!(matches!(
+            priorities[&MotionChannel::Extension],
+            Priority::Low
+        ));
+        assert!(matches!(
+            priorities[&MotionChannel::BalanceBoard],
+            Priority::High
+        ));
+
+        let err = toml::from_str::<Config>(
+            r#"
+            [budget]
+            max_rate = 200
+
+            [budget.priorities]
+            gyroscope = "low"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown channel `gyroscope`"));
+    }
+
+    #[test]
+    fn parses_midi_axis_expressions() {
+        let config: Config = toml::from_str(
+            r#"
+            [midi.axes]
+            pitch = 1
+            roll = { cc = 2, value = "(pitch + roll) / 2" }
+            "#,
+        )
+        .unwrap();
+        let axes = config.midi.axes;
+        assert_eq!(axes[&MidiAxis::Pitch], MidiAxisControl::from(1));
+        let roll = &axes[&MidiAxis::Roll];
+        assert_eq!(roll.cc, 2);
+        assert_eq!(roll.value.as_ref().unwrap().variables(), ["pitch", "roll"]);
+
+        for (value, message) in [
+            ("yaw * 2", "unknown variable `yaw`"),
+            ("pitch +", "invalid expression `pitch +`: unexpected end"),
+        ] {
+            let err = toml::from_str::<Config>(&format!(
+                "[midi.axes]\nroll = {{ cc = 2, value = \"{}\" }}",
+                value
+            ))
+            .unwrap_err();
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+    }
+}
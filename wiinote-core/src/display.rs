@@ -0,0 +1,450 @@
+use crate::adapter;
+use crate::battery::DischargeModel;
+use crate::remote::Remote;
+use anyhow::Result;
+use num_traits::FromPrimitive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+use xwiimote::Led;
+
+/// The refresh interval of the connection strength.
+const CONNECTION_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The lights shown while the connection strength cannot be read, a
+/// pattern no level can produce.
+const UNKNOWN_STRENGTH: u8 = 0b0101;
+
+/// How long the lights stay on, then off, while blinking.
+const BLINK: Duration = Duration::from_millis(500);
+
+/// Whether a connection strength read failed, only logged once as
+/// `hcitool` is likely missing or unsupported by the adapter.
+static STRENGTH_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// The metrics that can be displayed in a [`LightDisplay`].
+pub enum LightsMetric {
+    /// Display the battery level.
+    Battery,
+    /// Display the connection strength level.
+    Connection,
+}
+
+/// Returns the number of lights (1 to 4) showing the battery percentage:
+/// a light per started quarter.
+pub fn battery_lights(percentage: u8) -> u8 {
+    1 + (percentage / 25).min(3)
+}
+
+/// Returns the number of lights (1 to 4) showing the RSSI: a light per
+/// started quarter of the scale.
+///
+/// Technically, RSSI is a measure of the received intensity, not
+/// connection quality. This is good enough for the Wii Remote. The
+/// scale goes from -80 to 0, where 0 indicates the greatest signal
+/// strength.
+pub fn connection_lights(rssi: i16) -> u8 {
+    let strength = (rssi.clamp(-80, 0) + 80) * 100 / 80;
+    battery_lights(strength as u8)
+}
+
+/// The connection strength, read in the background.
+#[derive(Copy, Clone, Debug)]
+enum Strength {
+    /// Not read yet.
+    Pending,
+    /// The RSSI, see [`connection_lights`].
+    Known(i16),
+    Unknown,
+}
+
+/// Reads the connection strength of the device every
+/// [`CONNECTION_INTERVAL`] in the background, as `hcitool` may take a
+/// while, until the receiver is dropped.
+fn watch_strength(address: Option<String>) -> watch::Receiver<Strength> {
+    let address = match address {
+        Some(address) => address,
+        None => return watch::channel(Strength::Unknown).1,
+    };
+    let (sender, strength) = watch::channel(Strength::Pending);
+    tokio::spawn(async move {
+        loop {
+            let read = match adapter::rssi(&address).await {
+                Ok(rssi) => Strength::Known(rssi),
+                Err(err) if STRENGTH_FAILED.swap(true, Ordering::Relaxed) => {
+                    debug!("Cannot read the connection strength: {:#}", err);
+                    Strength::Unknown
+                }
+                Err(err) => {
+                    warn!("Cannot read the connection strength: {:#}", err);
+                    Strength::Unknown
+                }
+            };
+            if sender.send(read).is_err() {
+                return;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(CONNECTION_INTERVAL) => {}
+                _ = sender.closed() => return,
+            }
+        }
+    });
+    strength
+}
+
+/// Returns when the blinking lights ending at the given instant toggle next.
+fn next_blink(until: Instant, now: Instant) -> Instant {
+    let left = until.saturating_duration_since(now).as_millis();
+    if left == 0 {
+        return now;
+    }
+    match left % BLINK.as_millis() {
+        0 => now + BLINK,
+        phase => now + Duration::from_millis(phase as u64),
+    }
+}
+
+/// The set of lights of a Wii Remote, used as a display.
+pub struct LightDisplay<'a> {
+    device: &'a dyn Remote,
+    /// The Bluetooth address of the device, to measure the connection
+    /// strength. Unknown for scripted remotes.
+    address: Option<String>,
+    metric: LightsMetric,
+    /// The player number, displayed instead of the metric while
+    /// other Wii Remotes are connected.
+    player: usize,
+    shared: bool,
+    /// Whether the keyboard output is muted. Takes precedence
+    /// over the current metric and player number.
+    muted: bool,
+    /// The numpad position to display in binary, if the numpad
+    /// mode is active. Takes precedence over the muted state.
+    selection: Option<u8>,
+    /// A pattern displayed until the given instant, taking
+    /// precedence over everything else.
+    flash: Option<(u8, Instant)>,
+    /// The end of the sync window, until which every light blinks.
+    /// Takes precedence over everything but the flashed pattern.
+    syncing: Option<Instant>,
+    /// A pattern requested through the control socket, taking
+    /// precedence over everything but the flashed pattern.
+    custom: Option<u8>,
+    /// The connection strength, read while displayed.
+    strength: Option<watch::Receiver<Strength>>,
+    /// The discharge history of the battery, kept by the connection
+    /// across the displays of its handling.
+    battery: &'a mut DischargeModel,
+    /// The last battery level read, and the instant to read it again.
+    level: Option<u8>,
+    next_battery: Instant,
+    /// The battery percentage below which a warning is issued, polled
+    /// for like the LED thresholds.
+    low_battery: u8,
+    /// The pattern last written to the lights.
+    shown: Option<u8>,
+}
+
+impl<'a> LightDisplay<'a> {
+    pub fn new(
+        device: &'a dyn Remote,
+        battery: &'a mut DischargeModel,
+        address: Option<String>,
+        player: usize,
+        low_battery: u8,
+    ) -> Self {
+        Self {
+            device,
+            address,
+            player,
+            shared: false,
+            // Default to battery level, the connection strength is
+            // probably high immediately after pairing.
+            metric: LightsMetric::Battery,
+            muted: false,
+            selection: None,
+            flash: None,
+            syncing: None,
+            custom: None,
+            strength: None,
+            battery,
+            level: None,
+            next_battery: Instant::now(),
+            low_battery,
+            shown: None,
+        }
+    }
+
+    /// Waits until the lights need to be updated, either to refresh
+    /// the metric, to blink or because the flashed pattern expired.
+    pub async fn tick(&mut self) {
+        let mut deadline = match self.flash {
+            Some((_, deadline)) if deadline < self.next_battery => deadline,
+            _ => self.next_battery,
+        };
+        if let Some(until) = self.syncing {
+            deadline = deadline.min(next_blink(until, Instant::now()));
+        }
+        let strength = &mut self.strength;
+        let strength_changed = async move {
+            let changed = match strength {
+                Some(strength) => strength.changed().await.is_ok(),
+                None => false,
+            };
+            if !changed {
+                // Not displayed, or read once without an address.
+                futures_util::future::pending().await
+            }
+        };
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => {}
+            _ = strength_changed => {}
+        }
+        let now = Instant::now();
+        if self.flash.is_some_and(|(_, until)| until <= now) {
+            self.flash = None;
+        }
+        if self.syncing.is_some_and(|until| until <= now) {
+            self.syncing = None;
+        }
+    }
+
+    /// Updates the Wii Remote lights according to the current metric,
+    /// reading the battery level if due. Returns the level read, if any.
+    ///
+    /// The battery level is polled adaptively, whatever the lights
+    /// display: once the discharge rate is known, the next poll is
+    /// scheduled for when the level is predicted to cross the next LED
+    /// (or low battery) threshold.
+    pub async fn update(&mut self) -> Result<Option<u8>> {
+        let now = Instant::now();
+        let mut read = None;
+        if now >= self.next_battery {
+            let level = self.device.battery()?;
+            self.battery.record(now, level);
+            // The LED thresholds, see `battery_lights`.
+            let thresholds = [25, 50, 75, self.low_battery];
+            self.next_battery = now + self.battery.next_poll(&thresholds);
+            self.level = Some(level);
+            read = Some(level);
+        }
+        self.render()?;
+        Ok(read)
+    }
+
+    /// Updates the Wii Remote lights with the last values read.
+    fn render(&mut self) -> Result<()> {
+        if let Some((pattern, _)) = self.flash {
+            return self.show(pattern);
+        }
+        if let Some(until) = self.syncing {
+            let left = until.saturating_duration_since(Instant::now());
+            let off = (left.as_millis() / BLINK.as_millis()) % 2 == 1;
+            return self.show(if off { 0 } else { 0b1111 });
+        }
+        if let Some(pattern) = self.custom {
+            return self.show(pattern);
+        }
+        if let Some(position) = self.selection {
+            return self.show(position);
+        }
+        if self.muted {
+            // Light the outer LEDs only, a pattern no level can produce.
+            return self.show(0b1001);
+        }
+        if self.shared && self.player <= 4 {
+            // Light the LED of the player, like the Wii does.
+            return self.show(1 << (self.player - 1));
+        }
+
+        let lights = match self.metric {
+            LightsMetric::Battery => match self.level {
+                Some(level) => battery_lights(level),
+                // Shown once read, on the first update.
+                None => return Ok(()),
+            },
+            LightsMetric::Connection => {
+                let address = &self.address;
+                let strength = self
+                    .strength
+                    .get_or_insert_with(|| watch_strength(address.clone()));
+                let strength = *strength.borrow();
+                match strength {
+                    Strength::Known(rssi) => connection_lights(rssi),
+                    Strength::Unknown => return self.show(UNKNOWN_STRENGTH),
+                    // Shown once read.
+                    Strength::Pending => return Ok(()),
+                }
+            }
+        };
+
+        // `lights` is a value from 1 to 4.
+        self.show((1 << lights) - 1)
+    }
+
+    /// Turns on the lights whose bit is set in the pattern,
+    /// where the first light is the least significant bit.
+    fn show(&mut self, pattern: u8) -> Result<()> {
+        for ix in 1..=4 {
+            let light = Led::from_u8(ix).unwrap();
+            self.device.set_led(light, pattern & (1 << (ix - 1)) != 0)?;
+        }
+        self.shown = Some(pattern);
+        Ok(())
+    }
+
+    /// Rewrites the displayed pattern, in case a command was lost.
+    ///
+    /// The kernel reports the brightness it last set, not the state of
+    /// the lights, so there is nothing to read back and compare.
+    pub fn refresh(&mut self) -> Result<()> {
+        match self.shown {
+            Some(pattern) => self.show(pattern),
+            None => Ok(()),
+        }
+    }
+
+    /// Updates the displayed metric.
+    pub async fn set_metric(&mut self, metric: LightsMetric) -> Result<()> {
+        if let LightsMetric::Battery = metric {
+            // Stops reading the connection strength.
+            self.strength = None;
+        }
+        self.metric = metric;
+        self.render()
+    }
+
+    /// Sets whether the muted pattern is displayed instead of the metric.
+    pub async fn set_muted(&mut self, muted: bool) -> Result<()> {
+        self.muted = muted;
+        self.render()
+    }
+
+    /// Displays the pattern for the given duration.
+    pub async fn flash(&mut self, pattern: u8, duration: Duration) -> Result<()> {
+        self.flash = Some((pattern, Instant::now() + duration));
+        self.render()
+    }
+
+    /// Blinks every light until the given instant, or stops blinking.
+    pub async fn set_syncing(&mut self, until: Option<Instant>) -> Result<()> {
+        self.syncing = until;
+        self.render()
+    }
+
+    /// Sets whether the player number is displayed instead of the metric.
+    pub async fn set_shared(&mut self, shared: bool) -> Result<()> {
+        self.shared = shared;
+        self.render()
+    }
+
+    /// Sets the pattern to display instead of the metric, if any.
+    pub async fn set_custom(&mut self, custom: Option<u8>) -> Result<()> {
+        self.custom = custom;
+        self.render()
+    }
+
+    /// Sets the numpad position to display instead of the metric.
+    pub async fn set_selection(&mut self, selection: Option<u8>) -> Result<()> {
+        self.selection = selection;
+        self.render()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remote::ScriptedRemote;
+
+    #[test]
+    fn battery_lights_count_started_quarters() {
+        for (percentage, lights) in [(0, 1), (24, 1), (25, 2), (99, 4), (100, 4)] {
+            assert_eq!(battery_lights(percentage), lights, "{}%", percentage);
+        }
+    }
+
+    #[test]
+    fn connection_lights_clamp_the_rssi() {
+        for (rssi, lights) in [(-81, 1), (-80, 1), (-40, 3), (0, 4), (5, 4)] {
+            assert_eq!(connection_lights(rssi), lights, "RSSI {}", rssi);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shows_the_battery_quarters() {
+        for (battery, pattern) in [(10, 0b0001), (60, 0b0111), (100, 0b1111)] {
+            let remote = ScriptedRemote::new(Vec::new(), battery);
+            let mut model = DischargeModel::new();
+            let mut display = LightDisplay::new(&remote, &mut model, None, 1, 10);
+            assert_eq!(display.update().await.unwrap(), Some(battery));
+            assert_eq!(remote.lights(), pattern, "battery at {}%", battery);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn patterns_take_precedence_over_the_metric() {
+        let remote = ScriptedRemote::new(Vec::new(), 60);
+        let mut model = DischargeModel::new();
+        let mut display = LightDisplay::new(&remote, &mut model, None, 2, 10);
+        display.update().await.unwrap();
+        display.set_shared(true).await.unwrap();
+        assert_eq!(remote.lights(), 0b0010);
+        display.set_muted(true).await.unwrap();
+        assert_eq!(remote.lights(), 0b1001);
+        display.flash(0b0110, Duration::from_secs(2)).await.unwrap();
+        assert_eq!(remote.lights(), 0b0110);
+        // The flash expires on the next tick.
+        display.tick().await;
+        display.update().await.unwrap();
+        assert_eq!(remote.lights(), 0b1001);
+        display.set_muted(false).await.unwrap();
+        display.set_shared(false).await.unwrap();
+        assert_eq!(remote.lights(), 0b0111);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shows_an_unknown_connection_strength() {
+        let remote = ScriptedRemote::new(Vec::new(), 60);
+        let mut model = DischargeModel::new();
+        let mut display = LightDisplay::new(&remote, &mut model, None, 1, 10);
+        display.update().await.unwrap();
+        display.set_metric(LightsMetric::Connection).await.unwrap();
+        assert_eq!(remote.lights(), UNKNOWN_STRENGTH);
+        display.set_metric(LightsMetric::Battery).await.unwrap();
+        assert_eq!(remote.lights(), 0b0111);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn blinks_while_syncing() {
+        let remote = ScriptedRemote::new(Vec::new(), 60);
+        let mut model = DischargeModel::new();
+        let mut display = LightDisplay::new(&remote, &mut model, None, 1, 10);
+        display.update().await.unwrap();
+        let start = Instant::now();
+        display.set_syncing(Some(start + BLINK * 3)).await.unwrap();
+        assert_eq!(remote.lights(), 0b0000);
+        let mut shown = Vec::new();
+        while Instant::now() < start + BLINK * 3 {
+            display.tick().await;
+            display.update().await.unwrap();
+            shown.push((Instant::now() - start, remote.lights()));
+        }
+        assert_eq!(
+            shown,
+            [(BLINK, 0b1111), (BLINK * 2, 0b0000), (BLINK * 3, 0b0111),]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refresh_rewrites_the_shown_pattern() {
+        let remote = ScriptedRemote::new(Vec::new(), 60);
+        let mut model = DischargeModel::new();
+        let mut display = LightDisplay::new(&remote, &mut model, None, 1, 10);
+        display.update().await.unwrap();
+        remote.set_led(Led::from_u8(1).unwrap(), false).unwrap();
+        display.refresh().unwrap();
+        assert_eq!(remote.lights(), 0b0111);
+    }
+}
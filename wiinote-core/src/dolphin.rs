@@ -0,0 +1,120 @@
+use crate::error::UinputError;
+use crate::keyboard::{create_device, device_name};
+use anyhow::Result;
+use uinput::event::absolute::Position;
+use uinput::event::controller::{DPad, GamePad};
+use uinput::event::{Absolute, Controller};
+use xwiimote::event::{EventKind, Key, KeyState};
+
+/// The range of the accelerometer axes, about 5g.
+const ACCELEROMETER_MAX: i32 = 500;
+/// The resolution of the IR camera.
+const IR_WIDTH: i32 = 1023;
+const IR_HEIGHT: i32 = 767;
+
+/// A virtual gamepad with every button of the Wii Remote, the
+/// accelerometer on the X/Y/Z axes and the first IR source on the
+/// RX/RY axes, to be bound in Dolphin's "Emulated Wii Remote" with
+/// the evdev input backend.
+///
+/// The buttons keep their names where the gamepad has them; One and
+/// Two are X and Y, Plus and Minus are Start and Select, and Home is
+/// Mode.
+pub struct DolphinDevice {
+    device: uinput::Device,
+}
+
+impl DolphinDevice {
+    pub fn try_new(instance: Option<&str>) -> Result<Self, UinputError> {
+        let name = device_name("Wiinote Wii Remote", instance);
+        let device = create_device(name, |mut builder| {
+            let buttons = [
+                GamePad::A,
+                GamePad::B,
+                GamePad::X,
+                GamePad::Y,
+                GamePad::Start,
+                GamePad::Select,
+                GamePad::Mode,
+            ];
+            for button in buttons {
+                builder = builder.event(Controller::GamePad(button))?;
+            }
+            for direction in [DPad::Up, DPad::Down, DPad::Left, DPad::Right] {
+                builder = builder.event(Controller::DPad(direction))?;
+            }
+            for axis in [Position::X, Position::Y, Position::Z] {
+                builder = builder
+                    .event(Absolute::Position(axis))?
+                    .min(-ACCELEROMETER_MAX)
+                    .max(ACCELEROMETER_MAX);
+            }
+            builder = builder
+                .event(Absolute::Position(Position::RX))?
+                .min(0)
+                .max(IR_WIDTH);
+            Ok(builder
+                .event(Absolute::Position(Position::RY))?
+                .min(0)
+                .max(IR_HEIGHT))
+        })?;
+        Ok(Self { device })
+    }
+
+    /// Forwards the event of the Wii Remote to the virtual device.
+    ///
+    /// Events from other sources are ignored.
+    pub fn update(&mut self, event: &EventKind) -> Result<()> {
+        match event {
+            EventKind::Key(key, state) => {
+                let button = match button_event(key) {
+                    Some(button) => button,
+                    None => return Ok(()),
+                };
+                match *state {
+                    KeyState::Down => self.device.press(&button)?,
+                    KeyState::Up => self.device.release(&button)?,
+                    _ => {}
+                };
+            }
+            EventKind::Accelerometer { x, y, z } => {
+                let axes = [(Position::X, x), (Position::Y, y), (Position::Z, z)];
+                for (axis, value) in axes {
+                    let value = (*value).clamp(-ACCELEROMETER_MAX, ACCELEROMETER_MAX);
+                    self.device.position(&Absolute::Position(axis), value)?;
+                }
+            }
+            EventKind::Ir(sources) => {
+                // Keep the last position while the sensor bar is out of sight.
+                let source = match sources.iter().flatten().next() {
+                    Some(source) => source,
+                    None => return Ok(()),
+                };
+                let x = Absolute::Position(Position::RX);
+                self.device.position(&x, source.x.clamp(0, IR_WIDTH))?;
+                let y = Absolute::Position(Position::RY);
+                self.device.position(&y, source.y.clamp(0, IR_HEIGHT))?;
+            }
+            _ => return Ok(()),
+        }
+        self.device.synchronize().map_err(|err| err.into())
+    }
+}
+
+/// Converts the Wii Remote key to a gamepad event.
+fn button_event(key: &Key) -> Option<Controller> {
+    Some(match *key {
+        Key::A => Controller::GamePad(GamePad::A),
+        Key::B => Controller::GamePad(GamePad::B),
+        Key::One => Controller::GamePad(GamePad::X),
+        Key::Two => Controller::GamePad(GamePad::Y),
+        Key::Plus => Controller::GamePad(GamePad::Start),
+        Key::Minus => Controller::GamePad(GamePad::Select),
+        Key::Home => Controller::GamePad(GamePad::Mode),
+        Key::Up => Controller::DPad(DPad::Up),
+        Key::Down => Controller::DPad(DPad::Down),
+        Key::Left => Controller::DPad(DPad::Left),
+        Key::Right => Controller::DPad(DPad::Right),
+        _ => return None,
+    })
+}
@@ -0,0 +1,102 @@
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// The errors of the library, by subsystem, for the callers to tell
+/// them apart without downcasting.
+#[derive(Debug, Error)]
+pub enum WiinoteError {
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+    #[error(transparent)]
+    Script(#[from] ScriptError),
+    #[error(transparent)]
+    Uinput(#[from] UinputError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+}
+
+/// A failure to connect to a Wii Remote, or to open its channels.
+#[derive(Debug, Error)]
+pub enum ConnectionError {
+    /// The device can't be opened by this user; see the udev rule of
+    /// the README.
+    #[error("permission denied")]
+    PermissionDenied(#[source] io::Error),
+    /// The device is gone, e.g. it disconnected meanwhile.
+    #[error("device not found")]
+    NotFound(#[source] io::Error),
+    /// Any other I/O error, e.g. while the device is still being set
+    /// up right after pairing.
+    #[error("device I/O error")]
+    Io(#[source] io::Error),
+}
+
+impl ConnectionError {
+    /// Returns whether retrying the operation may succeed.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Io(_))
+    }
+}
+
+impl From<io::Error> for ConnectionError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied(err),
+            io::ErrorKind::NotFound => Self::NotFound(err),
+            _ => Self::Io(err),
+        }
+    }
+}
+
+/// A failure to load the events of a [`ScriptedRemote`](crate::remote::ScriptedRemote).
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("cannot read script {}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid script {}", path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error("event at {0}ms must have either a key and a state, or an accelerometer reading")]
+    InvalidEvent(u64),
+    #[error("unknown Wii Remote key `{0}`")]
+    UnknownKey(String),
+    #[error("malformed recording {}", .0.display())]
+    Recording(PathBuf),
+}
+
+/// A failure to create a virtual input device, e.g. because
+/// `/dev/uinput` can't be opened.
+#[derive(Debug, Error)]
+pub enum UinputError {
+    #[error("cannot create the virtual device `{name}`")]
+    Create {
+        name: String,
+        #[source]
+        source: uinput::Error,
+    },
+}
+
+/// A failure to load the configuration file.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("cannot read config file {}", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid config file {}", path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+}
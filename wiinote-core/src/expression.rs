@@ -0,0 +1,317 @@
+use anyhow::{anyhow, bail, Error, Result};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// An arithmetic expression computing the value of an axis from those
+/// of other axes, e.g. `clamp(x * 1.5 - 0.1, 0, 1)` or
+/// `(pitch + roll) / 2`, deserialized from a string.
+///
+/// It has numbers, variables, the `+`, `-`, `*` and `/` operators,
+/// parentheses and the functions `abs`, `min`, `max`, `clamp`, `pow`
+/// and `sqrt`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Expression(Node);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Function {
+    Abs,
+    Min,
+    Max,
+    Clamp,
+    Pow,
+    Sqrt,
+}
+
+impl Function {
+    /// The functions, by name, with their number of arguments.
+    const ALL: [(&'static str, Function, usize); 6] = [
+        ("abs", Function::Abs, 1),
+        ("min", Function::Min, 2),
+        ("max", Function::Max, 2),
+        ("clamp", Function::Clamp, 3),
+        ("pow", Function::Pow, 2),
+        ("sqrt", Function::Sqrt, 1),
+    ];
+
+    fn apply(self, args: &[f64]) -> f64 {
+        match (self, args) {
+            (Self::Abs, [x]) => x.abs(),
+            (Self::Min, [a, b]) => a.min(*b),
+            (Self::Max, [a, b]) => a.max(*b),
+            // Unlike `f64::clamp`, doesn't panic if the bounds are swapped.
+            (Self::Clamp, [x, min, max]) => x.max(*min).min(*max),
+            (Self::Pow, [x, exponent]) => x.powf(*exponent),
+            (Self::Sqrt, [x]) => x.sqrt(),
+            _ => unreachable!("the arguments are counted when parsing"),
+        }
+    }
+}
+
+impl Expression {
+    /// Returns the names of the variables of the expression.
+    pub fn variables(&self) -> Vec<&str> {
+        fn visit<'a>(node: &'a Node, names: &mut Vec<&'a str>) {
+            match node {
+                Node::Number(_) => {}
+                Node::Variable(name) => names.push(name),
+                Node::Negate(node) => visit(node, names),
+                Node::Binary(_, left, right) => {
+                    visit(left, names);
+                    visit(right, names);
+                }
+                Node::Call(_, args) => args.iter().for_each(|arg| visit(arg, names)),
+            }
+        }
+        let mut names = Vec::new();
+        visit(&self.0, &mut names);
+        names
+    }
+
+    /// Computes the value of the expression with the values of the
+    /// variables, or `None` if one of them has none.
+    pub fn evaluate(&self, variable: impl Fn(&str) -> Option<f64>) -> Option<f64> {
+        fn evaluate(node: &Node, variable: &dyn Fn(&str) -> Option<f64>) -> Option<f64> {
+            Some(match node {
+                Node::Number(value) => *value,
+                Node::Variable(name) => variable(name)?,
+                Node::Negate(node) => -evaluate(node, variable)?,
+                Node::Binary(operator, left, right) => {
+                    let (left, right) = (evaluate(left, variable)?, evaluate(right, variable)?);
+                    match operator {
+                        Operator::Add => left + right,
+                        Operator::Subtract => left - right,
+                        Operator::Multiply => left * right,
+                        Operator::Divide => left / right,
+                    }
+                }
+                Node::Call(function, args) => {
+                    let args = args
+                        .iter()
+                        .map(|arg| evaluate(arg, variable))
+                        .collect::<Option<Vec<_>>>()?;
+                    function.apply(&args)
+                }
+            })
+        }
+        Some(evaluate(&self.0, &variable)?).filter(|value| value.is_finite())
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let node = parser
+            .expression()
+            .map_err(|err| anyhow!("invalid expression `{}`: {}", s, err))?;
+        parser.skip_spaces();
+        if parser.pos < s.len() {
+            bail!(
+                "invalid expression `{}`: unexpected `{}`",
+                s,
+                &s[parser.pos..]
+            );
+        }
+        Ok(Self(node))
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+/// A recursive descent parser, one method per precedence level.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_spaces(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skips the spaces and returns the next character, if any.
+    fn peek(&mut self) -> Option<char> {
+        self.skip_spaces();
+        self.input[self.pos..].chars().next()
+    }
+
+    /// Consumes the next character if it's the given one.
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+        found
+    }
+
+    /// Parses the terms added or subtracted.
+    fn expression(&mut self) -> Result<Node> {
+        let mut node = self.term()?;
+        loop {
+            let operator = match self.peek() {
+                Some('+') => Operator::Add,
+                Some('-') => Operator::Subtract,
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            node = Node::Binary(operator, Box::new(node), Box::new(self.term()?));
+        }
+    }
+
+    /// Parses the factors multiplied or divided.
+    fn term(&mut self) -> Result<Node> {
+        let mut node = self.factor()?;
+        loop {
+            let operator = match self.peek() {
+                Some('*') => Operator::Multiply,
+                Some('/') => Operator::Divide,
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            node = Node::Binary(operator, Box::new(node), Box::new(self.factor()?));
+        }
+    }
+
+    /// Parses a number, variable, call, negation or parenthesized
+    /// expression.
+    fn factor(&mut self) -> Result<Node> {
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                Ok(Node::Negate(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let node = self.expression()?;
+                if !self.eat(')') {
+                    bail!("missing `)`");
+                }
+                Ok(node)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let number = self.token(|c| c.is_ascii_digit() || c == '.');
+                number
+                    .parse()
+                    .map(Node::Number)
+                    .map_err(|_| anyhow!("invalid number `{}`", number))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let name = self.token(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !self.eat('(') {
+                    return Ok(Node::Variable(name.to_owned()));
+                }
+                let (_, function, arity) = Function::ALL
+                    .iter()
+                    .find(|(function, _, _)| *function == name)
+                    .ok_or_else(|| anyhow!("unknown function `{}`", name))?;
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.expression()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            bail!("missing `)` after the arguments of `{}`", name);
+                        }
+                    }
+                }
+                if args.len() != *arity {
+                    bail!("`{}` takes {} arguments, got {}", name, arity, args.len());
+                }
+                Ok(Node::Call(*function, args))
+            }
+            Some(c) => bail!("unexpected `{}`", c),
+            None => bail!("unexpected end"),
+        }
+    }
+
+    /// Consumes the characters matching the predicate.
+    fn token(&mut self, matches: impl Fn(char) -> bool) -> &'a str {
+        let (input, start) = (self.input, self.pos);
+        let len = input[start..]
+            .find(|c| !matches(c))
+            .unwrap_or(input.len() - start);
+        self.pos += len;
+        &input[start..self.pos]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(expression: &str, variables: &[(&str, f64)]) -> Option<f64> {
+        let expression: Expression = expression.parse().unwrap();
+        expression.evaluate(|name| {
+            variables
+                .iter()
+                .find(|(variable, _)| *variable == name)
+                .map(|(_, value)| *value)
+        })
+    }
+
+    #[test]
+    fn evaluates_expressions() {
+        assert_eq!(evaluate("1 + 2 * 3 - 4 / 2", &[]), Some(5.0));
+        assert_eq!(evaluate("(1 + 2) * -3", &[]), Some(-9.0));
+        assert_eq!(
+            evaluate("(pitch + roll) / 2", &[("pitch", 0.2), ("roll", 0.6)]),
+            Some(0.4)
+        );
+        assert_eq!(
+            evaluate("clamp(pitch * 2 - 0.5, 0, 1)", &[("pitch", 0.9)]),
+            Some(1.0)
+        );
+        assert_eq!(
+            evaluate("max(abs(-.5), pow(2, 2)) + sqrt(4)", &[]),
+            Some(6.0)
+        );
+        // Missing variables and non-finite values have no value.
+        assert_eq!(evaluate("pitch + roll", &[("pitch", 0.2)]), None);
+        assert_eq!(evaluate("1 / 0", &[]), None);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for (expression, err) in [
+            ("", "unexpected end"),
+            ("(pitch", "missing `)`"),
+            ("pitch roll", "unexpected `roll`"),
+            ("1..2", "invalid number `1..2`"),
+            ("log(2)", "unknown function `log`"),
+            ("clamp(pitch, 0)", "`clamp` takes 3 arguments, got 2"),
+        ] {
+            let message = expression.parse::<Expression>().unwrap_err().to_string();
+            assert!(message.ends_with(err), "{}: {}", expression, message);
+        }
+        let expression: Expression = "min(pitch, roll) * pointer_x".parse().unwrap();
+        assert_eq!(expression.variables(), ["pitch", "roll", "pointer_x"]);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::error::UinputError;
+use crate::keyboard::{create_device, device_name};
+use anyhow::Result;
+use uinput::event::absolute::Position;
+use uinput::event::controller::{DPad, GamePad};
+use uinput::event::{Absolute, Controller};
+use xwiimote::event::{EventKind, Key, KeyState};
+use xwiimote::Channels;
+
+/// The extensions exposed as separate virtual devices.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Extension {
+    Nunchuk,
+    ClassicController,
+}
+
+impl Extension {
+    /// Parses the extension name reported by xwiimote.
+    ///
+    /// Returns `None` if no extension is plugged or it is not supported.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nunchuk" => Some(Self::Nunchuk),
+            "classic" => Some(Self::ClassicController),
+            _ => None,
+        }
+    }
+
+    /// The channel providing the extension events.
+    pub fn channel(&self) -> Channels {
+        match *self {
+            Self::Nunchuk => Channels::NUNCHUK,
+            Self::ClassicController => Channels::CLASSIC_CONTROLLER,
+        }
+    }
+
+    fn device_name(&self) -> &'static str {
+        match *self {
+            Self::Nunchuk => "Wiinote Nunchuk",
+            Self::ClassicController => "Wiinote Classic Controller",
+        }
+    }
+}
+
+/// A virtual input device with just the buttons and axes of an extension.
+pub struct ExtensionDevice {
+    extension: Extension,
+    device: uinput::Device,
+}
+
+impl ExtensionDevice {
+    pub fn try_new(extension: Extension, instance: Option<&str>) -> Result<Self, UinputError> {
+        let name = device_name(extension.device_name(), instance);
+        let device = create_device(name, |mut builder| {
+            match extension {
+                Extension::Nunchuk => {
+                    for button in [GamePad::C, GamePad::Z] {
+                        builder = builder.event(Controller::GamePad(button))?;
+                    }
+                    for axis in [Position::X, Position::Y] {
+                        builder = builder.event(Absolute::Position(axis))?.min(-100).max(100);
+                    }
+                }
+                Extension::ClassicController => {
+                    let buttons = [
+                        GamePad::A,
+                        GamePad::B,
+                        GamePad::X,
+                        GamePad::Y,
+                        GamePad::TL,
+                        GamePad::TR,
+                        GamePad::TL2,
+                        GamePad::TR2,
+                        GamePad::Start,
+                        GamePad::Select,
+                        GamePad::Mode,
+                    ];
+                    for button in buttons {
+                        builder = builder.event(Controller::GamePad(button))?;
+                    }
+                    for direction in [DPad::Up, DPad::Down, DPad::Left, DPad::Right] {
+                        builder = builder.event(Controller::DPad(direction))?;
+                    }
+                    for axis in [Position::X, Position::Y, Position::RX, Position::RY] {
+                        builder = builder.event(Absolute::Position(axis))?.min(-32).max(32);
+                    }
+                    for axis in [Position::Z, Position::RZ] {
+                        builder = builder.event(Absolute::Position(axis))?.min(0).max(64);
+                    }
+                }
+            }
+            Ok(builder)
+        })?;
+        Ok(Self { extension, device })
+    }
+
+    pub fn extension(&self) -> Extension {
+        self.extension
+    }
+
+    /// Forwards the extension event to the virtual device.
+    ///
+    /// Events from other sources are ignored.
+    pub fn update(&mut self, event: &EventKind) -> Result<()> {
+        match (self.extension, event) {
+            (Extension::Nunchuk, EventKind::NunchukKey(key, state))
+            | (Extension::ClassicController, EventKind::ClassicControllerKey(key, state)) => {
+                let button = match button_event(key) {
+                    Some(button) => button,
+                    None => return Ok(()),
+                };
+                match *state {
+                    KeyState::Down => self.device.press(&button)?,
+                    KeyState::Up => self.device.release(&button)?,
+                    _ => {}
+                };
+            }
+            (Extension::Nunchuk, EventKind::NunchukMove { x, y, .. }) => {
+                self.device.position(&Absolute::Position(Position::X), *x)?;
+                self.device.position(&Absolute::Position(Position::Y), *y)?;
+            }
+            (
+                Extension::ClassicController,
+                EventKind::ClassicControllerMove {
+                    left_x,
+                    left_y,
+                    right_x,
+                    right_y,
+                    left_trigger,
+                    right_trigger,
+                },
+            ) => {
+                let axes = [
+                    (Position::X, left_x),
+                    (Position::Y, left_y),
+                    (Position::RX, right_x),
+                    (Position::RY, right_y),
+                    (Position::Z, left_trigger),
+                    (Position::RZ, right_trigger),
+                ];
+                for (axis, value) in axes {
+                    self.device.position(&Absolute::Position(axis), *value)?;
+                }
+            }
+            _ => return Ok(()),
+        }
+        self.device.synchronize().map_err(|err| err.into())
+    }
+}
+
+/// Converts the extension key to a gamepad event.
+fn button_event(key: &Key) -> Option<Controller> {
+    Some(match *key {
+        Key::A => Controller::GamePad(GamePad::A),
+        Key::B => Controller::GamePad(GamePad::B),
+        Key::X => Controller::GamePad(GamePad::X),
+        Key::Y => Controller::GamePad(GamePad::Y),
+        Key::C => Controller::GamePad(GamePad::C),
+        Key::Z => Controller::GamePad(GamePad::Z),
+        Key::TL => Controller::GamePad(GamePad::TL),
+        Key::TR => Controller::GamePad(GamePad::TR),
+        Key::ZL => Controller::GamePad(GamePad::TL2),
+        Key::ZR => Controller::GamePad(GamePad::TR2),
+        Key::Plus => Controller::GamePad(GamePad::Start),
+        Key::Minus => Controller::GamePad(GamePad::Select),
+        Key::Home => Controller::GamePad(GamePad::Mode),
+        Key::Up => Controller::DPad(DPad::Up),
+        Key::Down => Controller::DPad(DPad::Down),
+        Key::Left => Controller::DPad(DPad::Left),
+        Key::Right => Controller::DPad(DPad::Right),
+        _ => return None,
+    })
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The accelerometer reading of 1 g, approximately.
+const G: i32 = 100;
+/// The acceleration below which the remote is considered falling.
+const FREE_FALL: i32 = G * 3 / 10;
+/// The acceleration above which the remote is considered to hit something.
+const IMPACT: i32 = G * 3;
+/// How long the remote must be falling before an impact counts as a drop,
+/// to ignore quick shakes (a fall of ~5cm).
+const MIN_FALL: Duration = Duration::from_millis(100);
+/// How long after the fall ends the impact may be detected.
+const IMPACT_WINDOW: Duration = Duration::from_millis(300);
+
+#[derive(Copy, Clone, Debug)]
+enum State {
+    Resting,
+    /// The remote started falling at the given instant.
+    Falling(Instant),
+    /// The remote fell and is expected to hit something before the instant.
+    Fell(Instant),
+}
+
+/// Detects drops of the Wii Remote: a free fall followed by an impact.
+pub struct FallDetector {
+    state: State,
+    drops: u32,
+}
+
+impl Default for FallDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FallDetector {
+    pub fn new() -> Self {
+        Self {
+            state: State::Resting,
+            drops: 0,
+        }
+    }
+
+    /// The number of drops detected.
+    pub fn drops(&self) -> u32 {
+        self.drops
+    }
+
+    /// Updates the detector with an accelerometer reading, returning
+    /// whether it completes a drop.
+    pub fn update(&mut self, time: Instant, x: i32, y: i32, z: i32) -> bool {
+        let magnitude = x * x + y * y + z * z;
+        let falling = magnitude < FREE_FALL * FREE_FALL;
+        let impact = magnitude > IMPACT * IMPACT;
+
+        self.state = match self.state {
+            State::Resting if falling => State::Falling(time),
+            State::Falling(start) if !falling => {
+                if impact && time - start >= MIN_FALL {
+                    self.drops += 1;
+                    self.state = State::Resting;
+                    return true;
+                }
+                if time - start >= MIN_FALL {
+                    State::Fell(time + IMPACT_WINDOW)
+                } else {
+                    State::Resting
+                }
+            }
+            State::Fell(deadline) if impact && time <= deadline => {
+                self.drops += 1;
+                self.state = State::Resting;
+                return true;
+            }
+            State::Fell(deadline) if time > deadline => State::Resting,
+            state => state,
+        };
+        false
+    }
+}
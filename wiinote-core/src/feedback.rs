@@ -0,0 +1,115 @@
+use crate::remote::Remote;
+use anyhow::Result;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The pulse played when a Wii Remote connects.
+pub const CONNECTED: Duration = Duration::from_millis(200);
+/// The pulse played when the battery runs low.
+pub const LOW_BATTERY: Duration = Duration::from_millis(600);
+/// The pulse played before disconnecting an idle Wii Remote.
+pub const IDLE_WARNING: Duration = Duration::from_millis(400);
+/// The pulse played when a button switches the mapping (profile or mouse mode).
+pub const MAPPING_SWITCH: Duration = Duration::from_millis(80);
+/// The length of each pulse of a count, and of the pause between them.
+const COUNT_PULSE: Duration = Duration::from_millis(120);
+const COUNT_PAUSE: Duration = Duration::from_millis(180);
+
+/// Drives the rumble motor of a Wii Remote, stopping it once the
+/// requested pulse elapses.
+pub struct Feedback {
+    enabled: bool,
+    /// The instant the current pulse or pause ends.
+    until: Option<Instant>,
+    /// Whether the motor is running; otherwise, the motor is paused
+    /// between the pulses of a count.
+    running: bool,
+    /// The pulses of a count left to play after the current pause.
+    remaining: u32,
+}
+
+impl Feedback {
+    /// Creates the feedback state. If not `enabled`, pulses are ignored.
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            until: None,
+            running: false,
+            remaining: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Starts the motor for the given duration. If the motor is already
+    /// running, the pulse is extended if it would end later.
+    pub fn rumble(&mut self, device: &dyn Remote, duration: Duration) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let until = Instant::now() + duration;
+        self.until = match self.until {
+            Some(current) if self.running => Some(current.max(until)),
+            _ => Some(until),
+        };
+        // A single pulse interrupts a count.
+        self.running = true;
+        self.remaining = 0;
+        device.set_rumble(true)?;
+        Ok(())
+    }
+
+    /// Plays `count` short pulses, e.g. to tell the number of the
+    /// selected profile without looking at the lights.
+    pub fn count(&mut self, device: &dyn Remote, count: u32) -> Result<()> {
+        if !self.enabled || count == 0 {
+            return Ok(());
+        }
+        self.until = Some(Instant::now() + COUNT_PULSE);
+        self.running = true;
+        self.remaining = count - 1;
+        device.set_rumble(true)?;
+        Ok(())
+    }
+
+    /// Waits until the current pulse or pause elapses, after which
+    /// [`advance`](Self::advance) must be called. Never completes if
+    /// nothing is playing.
+    pub async fn tick(&self) {
+        match self.until {
+            Some(until) => tokio::time::sleep_until(until).await,
+            None => futures_util::future::pending().await,
+        }
+    }
+
+    /// Stops the elapsed pulse, or starts the next pulse of a count.
+    pub fn advance(&mut self, device: &dyn Remote) -> Result<()> {
+        if !self.running {
+            self.remaining -= 1;
+            self.until = Some(Instant::now() + COUNT_PULSE);
+            self.running = true;
+            return device.set_rumble(true);
+        }
+
+        self.until = if self.remaining > 0 {
+            Some(Instant::now() + COUNT_PAUSE)
+        } else {
+            None
+        };
+        self.running = false;
+        device.set_rumble(false)?;
+        Ok(())
+    }
+
+    /// Stops the motor, discarding the pulses left.
+    pub fn stop(&mut self, device: &dyn Remote) -> Result<()> {
+        self.remaining = 0;
+        if self.until.take().is_some() && self.running {
+            self.running = false;
+            device.set_rumble(false)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use crate::calibration::{self, IR_WIDTH};
+use crate::gyro::Gyro;
+use std::time::{Duration, Instant};
+use xwiimote::event::EventKind;
+
+/// The Motion Plus angular rate, in units per degree per second, in
+/// its slow (precise) mode.
+const UNITS_PER_DEGREE: f64 = 13.8;
+/// The IR camera pixels per degree, its horizontal field of view being
+/// about 33 degrees.
+const PIXELS_PER_DEGREE: f64 = IR_WIDTH / 33.0;
+/// The longest time between two Motion Plus samples that is integrated,
+/// e.g. not after the channel was reopened.
+const MAX_PERIOD: Duration = Duration::from_millis(100);
+
+/// A position estimated by the [`Fusion`].
+#[derive(Copy, Clone, Debug)]
+pub struct Estimate {
+    /// The position in the IR camera coordinates.
+    pub position: (f64, f64),
+    /// Whether the position jumped back to the IR sources, which came
+    /// back into sight.
+    pub recentered: bool,
+}
+
+/// Estimates where the Wii Remote points, in the IR camera coordinates,
+/// without the sensor bar in sight.
+///
+/// While the IR sources are visible, their center is the position.
+/// Once they go out of sight, the rotation measured by the Motion Plus
+/// moves the position on from the last one seen, compensating the roll
+/// of the remote measured by the accelerometer. The gyroscope drifts,
+/// so the position is recentered on the sources once they are back.
+pub struct Fusion {
+    gyro: Gyro,
+    /// The estimated position, unknown until the sources are first seen.
+    position: Option<(f64, f64)>,
+    /// Whether the sources are in sight.
+    in_sight: bool,
+    /// The rotation of the remote around its pointing axis, in radians,
+    /// 0 when the buttons face up.
+    roll: f64,
+    /// The instant of the last Motion Plus sample.
+    last_sample: Option<Instant>,
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fusion {
+    pub fn new() -> Self {
+        Self {
+            gyro: Gyro::new(),
+            position: None,
+            in_sight: false,
+            roll: 0.0,
+            last_sample: None,
+        }
+    }
+
+    /// Restarts the gyroscope calibration.
+    pub fn recalibrate(&mut self) {
+        self.gyro.recalibrate();
+    }
+
+    /// Updates the estimate with the IR, accelerometer or Motion Plus
+    /// event, returning the new position if it changed.
+    pub fn update(&mut self, event: &EventKind) -> Option<Estimate> {
+        match event {
+            EventKind::Ir(sources) => {
+                let sources = sources.iter().flatten().map(|s| (s.x, s.y));
+                let position = calibration::ir_position(sources);
+                let recentered = position.is_some() && !self.in_sight;
+                self.in_sight = position.is_some();
+                self.position = position.or(self.position);
+                position.map(|position| Estimate {
+                    position,
+                    recentered,
+                })
+            }
+            EventKind::Accelerometer { x, z, .. } => {
+                // Gravity moves from the Z to the X axis as the remote rolls.
+                self.roll = (*x as f64).atan2(*z as f64);
+                None
+            }
+            EventKind::MotionPlus { x, y, z } => {
+                // Keep tracking the bias while the sources are in sight.
+                let rates = self.gyro.update(*x, *y, *z)?;
+                let now = Instant::now();
+                let period = now.duration_since(self.last_sample.replace(now)?);
+                if self.in_sight || period > MAX_PERIOD {
+                    return None;
+                }
+                // Turning left or right rotates the remote around its Z
+                // axis (yaw), pointing up or down around its X axis
+                // (pitch), until it rolls.
+                let (sin, cos) = self.roll.sin_cos();
+                let yaw = rates[2] * cos + rates[0] * sin;
+                let pitch = rates[0] * cos - rates[2] * sin;
+                let scale = period.as_secs_f64() / UNITS_PER_DEGREE * PIXELS_PER_DEGREE;
+                let (x, y) = self.position.as_mut()?;
+                // The camera sees the sources move in the opposite
+                // direction horizontally.
+                *x += yaw * scale;
+                *y -= pitch * scale;
+                Some(Estimate {
+                    position: (*x, *y),
+                    recentered: false,
+                })
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use crate::gyro::Gyro;
+use std::collections::VecDeque;
+use std::f64::consts::{PI, TAU};
+use std::time::Duration;
+use tokio::time::Instant;
+use xwiimote::event::EventKind;
+
+/// The accelerometer reading of 1 g, approximately.
+const G: i32 = 100;
+/// The deviation from the resting acceleration counted as a jolt.
+const JOLT: i32 = G * 3 / 2;
+/// The bounds of the adapted jolt threshold.
+const MIN_JOLT: i32 = G;
+const MAX_JOLT: i32 = G * 3;
+/// The fraction of the typical jolt peak used as the adapted threshold.
+const ADAPTED_RATIO: f64 = 0.75;
+/// The weight of the latest shake in the typical jolt peak.
+const LEARNING_RATE: f64 = 0.2;
+/// The number of jolts that make a shake, and the window they must
+/// happen in.
+const JOLTS: usize = 3;
+const WINDOW: Duration = Duration::from_millis(600);
+/// The time after a shake during which no other shake is detected,
+/// so one shake fires a single event.
+const DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// The acceleration, besides gravity, counted as a swing or a flick.
+const SWING: f64 = G as f64 * 2.0;
+/// The deviation from 1 g within which the remote is considered at
+/// rest, and the reading used to track gravity.
+const REST: f64 = G as f64 / 5.0;
+/// The weight of a resting reading in the gravity estimate.
+const GRAVITY_RATE: f64 = 0.05;
+/// The Motion Plus roll rate counted as a twist, about 300°/s.
+const TWIST_RATE: f64 = 4000.0;
+/// The roll, in degrees, counted as a twist without a Motion Plus, and
+/// the window it must happen in.
+const TWIST_ANGLE: f64 = 60.0;
+const TWIST_WINDOW: Duration = Duration::from_millis(400);
+/// The minimum acceleration (without a Motion Plus) or angular rate
+/// (with it, about 100°/s) while drawing a circle.
+const CIRCLE_ACCELERATION: f64 = G as f64 / 2.0;
+const CIRCLE_RATE: f64 = 1400.0;
+/// The longest time a circle may take.
+const CIRCLE_DURATION: Duration = Duration::from_millis(1500);
+/// The time after a gesture during which no other is recognized, so
+/// that the deceleration ending a swing isn't taken for another one.
+const GESTURE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Detects shakes of the Wii Remote from its acceleration.
+///
+/// If adaptive, the jolt threshold follows how hard the user shakes:
+/// it is lowered for gentle shakes, down to 1 g, and raised for
+/// energetic ones, so that smaller motions aren't taken as shakes.
+pub struct ShakeDetector {
+    /// The instants of the recent jolts, within the window, and the
+    /// peak deviation of each.
+    jolts: VecDeque<(Instant, i32)>,
+    /// Whether the last reading was a jolt, so that a jolt spanning
+    /// several readings counts once.
+    jolting: bool,
+    last_shake: Option<Instant>,
+    /// The typical jolt peak of the shakes, if adaptive.
+    typical_peak: Option<f64>,
+    /// The deviation counted as a jolt.
+    threshold: i32,
+}
+
+impl ShakeDetector {
+    pub fn new(adaptive: bool) -> Self {
+        Self {
+            jolts: VecDeque::with_capacity(JOLTS),
+            jolting: false,
+            last_shake: None,
+            typical_peak: adaptive.then_some((JOLT as f64) / ADAPTED_RATIO),
+            threshold: JOLT,
+        }
+    }
+
+    /// Returns the current jolt threshold, in accelerometer units.
+    pub fn threshold(&self) -> i32 {
+        self.threshold
+    }
+
+    /// Updates the detector with an accelerometer reading, returning
+    /// whether it completes a shake.
+    pub fn update(&mut self, time: Instant, x: i32, y: i32, z: i32) -> bool {
+        let magnitude = ((x * x + y * y + z * z) as f64).sqrt() as i32;
+        let deviation = (magnitude - G).abs();
+        let jolting = deviation > self.threshold;
+        let new_jolt = jolting && !self.jolting;
+        self.jolting = jolting;
+        if jolting && !new_jolt {
+            if let Some((_, peak)) = self.jolts.back_mut() {
+                *peak = (*peak).max(deviation);
+            }
+        }
+        if !new_jolt {
+            return false;
+        }
+        if matches!(self.last_shake, Some(last) if time - last < DEBOUNCE) {
+            return false;
+        }
+
+        while matches!(self.jolts.front(), Some((first, _)) if time - *first > WINDOW) {
+            self.jolts.pop_front();
+        }
+        self.jolts.push_back((time, deviation));
+        if self.jolts.len() < JOLTS {
+            return false;
+        }
+        self.learn();
+        self.jolts.clear();
+        self.last_shake = Some(time);
+        true
+    }
+
+    /// Moves the threshold towards the peaks of the detected shake.
+    fn learn(&mut self) {
+        let typical_peak = match &mut self.typical_peak {
+            Some(typical_peak) => typical_peak,
+            None => return,
+        };
+        // The last jolt just started, its peak is unknown.
+        let completed = self.jolts.len() - 1;
+        let total: i32 = self
+            .jolts
+            .iter()
+            .take(completed)
+            .map(|(_, peak)| peak)
+            .sum();
+        let mean = total as f64 / completed as f64;
+        *typical_peak += (mean - *typical_peak) * LEARNING_RATE;
+        self.threshold = ((*typical_peak * ADAPTED_RATIO) as i32).clamp(MIN_JOLT, MAX_JOLT);
+    }
+}
+
+/// A motion of the Wii Remote recognized by the [`GestureRecognizer`],
+/// bindable like a button.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Gesture {
+    SwingLeft,
+    SwingRight,
+    SwingUp,
+    SwingDown,
+    /// A quick jab forward, towards the screen.
+    Flick,
+    /// A quick roll of the wrist, counterclockwise as seen from behind
+    /// the remote.
+    TwistLeft,
+    TwistRight,
+    /// A full circle drawn with the tip of the remote, as seen from
+    /// behind it.
+    CircleClockwise,
+    CircleCounterclockwise,
+}
+
+impl Gesture {
+    pub const ALL: [Gesture; 9] = [
+        Gesture::SwingLeft,
+        Gesture::SwingRight,
+        Gesture::SwingUp,
+        Gesture::SwingDown,
+        Gesture::Flick,
+        Gesture::TwistLeft,
+        Gesture::TwistRight,
+        Gesture::CircleClockwise,
+        Gesture::CircleCounterclockwise,
+    ];
+
+    /// The name of the gesture in the logs and webhooks, e.g. `swing-left`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SwingLeft => "swing-left",
+            Self::SwingRight => "swing-right",
+            Self::SwingUp => "swing-up",
+            Self::SwingDown => "swing-down",
+            Self::Flick => "flick",
+            Self::TwistLeft => "twist-left",
+            Self::TwistRight => "twist-right",
+            Self::CircleClockwise => "circle-clockwise",
+            Self::CircleCounterclockwise => "circle-counterclockwise",
+        }
+    }
+}
+
+/// Recognizes the [`Gesture`]s from the accelerometer and, if plugged,
+/// Motion Plus readings.
+///
+/// Swings and flicks are the first peak of the acceleration without
+/// gravity, along the axis of the motion. Twists are fast rolls, and
+/// circles a full turn of the direction of the motion, both measured
+/// by the gyroscope once Motion Plus readings arrive, and estimated
+/// from the accelerometer until then.
+pub struct GestureRecognizer {
+    /// The gravity estimate, removed from the readings.
+    gravity: Option<[f64; 3]>,
+    gyro: Gyro,
+    /// Whether Motion Plus readings arrive.
+    motion_plus: bool,
+    /// The recent roll angles, in degrees, without a Motion Plus.
+    rolls: VecDeque<(Instant, f64)>,
+    circle: CircleTracker,
+    last_gesture: Option<Instant>,
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GestureRecognizer {
+    pub fn new() -> Self {
+        Self {
+            gravity: None,
+            gyro: Gyro::new(),
+            motion_plus: false,
+            rolls: VecDeque::new(),
+            circle: CircleTracker::default(),
+            last_gesture: None,
+        }
+    }
+
+    /// Updates the recognizer with an accelerometer or Motion Plus
+    /// reading, returning the gesture it completes, if any.
+    pub fn update(&mut self, time: Instant, event: &EventKind) -> Option<Gesture> {
+        match *event {
+            EventKind::Accelerometer { x, y, z } => self.accelerate(time, x, y, z),
+            EventKind::MotionPlus { x, y, z } => {
+                self.motion_plus = true;
+                let rates = self.gyro.update(x, y, z)?;
+                // The Y axis is the long axis of the remote.
+                if rates[1].abs() > TWIST_RATE {
+                    let twist = if rates[1] > 0.0 {
+                        Gesture::TwistRight
+                    } else {
+                        Gesture::TwistLeft
+                    };
+                    return self.recognize(time, twist);
+                }
+                // The motion of the tip: turning right is a negative
+                // rate around the Z axis, pointing up a positive one
+                // around the X axis.
+                let circle = self
+                    .circle
+                    .update(time, (-rates[2], rates[0]), CIRCLE_RATE)?;
+                self.recognize(time, circle)
+            }
+            _ => None,
+        }
+    }
+
+    fn accelerate(&mut self, time: Instant, x: i32, y: i32, z: i32) -> Option<Gesture> {
+        let reading = [x as f64, y as f64, z as f64];
+        let magnitude = reading
+            .iter()
+            .map(|value| value * value)
+            .sum::<f64>()
+            .sqrt();
+        let resting = (magnitude - G as f64).abs() < REST;
+        let gravity = self.gravity.get_or_insert(reading);
+        if resting {
+            for (gravity, value) in gravity.iter_mut().zip(reading) {
+                *gravity += (value - *gravity) * GRAVITY_RATE;
+            }
+        }
+        // The X axis points to the left of the remote, Y forward and
+        // Z up.
+        let mut linear = reading;
+        for (value, gravity) in linear.iter_mut().zip(*gravity) {
+            *value -= gravity;
+        }
+
+        if !self.motion_plus {
+            // Gravity only gives the roll while no other acceleration
+            // adds to it.
+            if let Some(twist) = resting.then(|| self.roll(time, x, z)).flatten() {
+                return self.recognize(time, twist);
+            }
+            if let Some(circle) =
+                self.circle
+                    .update(time, (-linear[0], linear[2]), CIRCLE_ACCELERATION)
+            {
+                return self.recognize(time, circle);
+            }
+        }
+
+        let (axis, value) = linear
+            .into_iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))?;
+        if value.abs() < SWING {
+            return None;
+        }
+        let swing = match (axis, value > 0.0) {
+            (0, true) => Gesture::SwingLeft,
+            (0, false) => Gesture::SwingRight,
+            (1, true) => Gesture::Flick,
+            (1, false) => return None, // pulled back
+            (_, true) => Gesture::SwingUp,
+            (_, false) => Gesture::SwingDown,
+        };
+        self.recognize(time, swing)
+    }
+
+    /// Returns the twist the roll of the remote completes, estimated
+    /// from the direction of gravity.
+    fn roll(&mut self, time: Instant, x: i32, z: i32) -> Option<Gesture> {
+        let roll = (x as f64).atan2(z as f64).to_degrees();
+        while matches!(self.rolls.front(), Some((first, _)) if time - *first > TWIST_WINDOW) {
+            self.rolls.pop_front();
+        }
+        self.rolls.push_back((time, roll));
+        let (_, first) = self.rolls.front()?;
+        let turned = (roll - first + 540.0) % 360.0 - 180.0;
+        if turned.abs() < TWIST_ANGLE {
+            return None;
+        }
+        self.rolls.clear();
+        Some(if turned > 0.0 {
+            Gesture::TwistRight
+        } else {
+            Gesture::TwistLeft
+        })
+    }
+
+    /// Returns the gesture, unless another one was just recognized.
+    fn recognize(&mut self, time: Instant, gesture: Gesture) -> Option<Gesture> {
+        if matches!(self.last_gesture, Some(last) if time - last < GESTURE_DEBOUNCE) {
+            return None;
+        }
+        self.last_gesture = Some(time);
+        Some(gesture)
+    }
+}
+
+/// Follows the direction of a motion, to tell when it turns a full
+/// circle.
+#[derive(Default)]
+struct CircleTracker {
+    /// The direction of the last motion, in radians.
+    direction: Option<f64>,
+    /// The angle turned since the start, counterclockwise.
+    turned: f64,
+    start: Option<Instant>,
+}
+
+impl CircleTracker {
+    /// Updates the tracker with the motion (rightwards and upwards),
+    /// returning the circle it completes, if any. Motions weaker than
+    /// the minimum stop the circle.
+    fn update(&mut self, time: Instant, (x, y): (f64, f64), min: f64) -> Option<Gesture> {
+        if x.hypot(y) < min {
+            *self = Self::default();
+            return None;
+        }
+        let direction = y.atan2(x);
+        let start = *self.start.get_or_insert(time);
+        if time - start > CIRCLE_DURATION {
+            // Too slow, start over from here.
+            *self = Self {
+                direction: Some(direction),
+                turned: 0.0,
+                start: Some(time),
+            };
+            return None;
+        }
+        if let Some(last) = self.direction.replace(direction) {
+            let delta = direction - last;
+            // The shortest way between the directions.
+            self.turned += (delta + PI).rem_euclid(TAU) - PI;
+        }
+        if self.turned.abs() < TAU {
+            return None;
+        }
+        let circle = if self.turned < 0.0 {
+            Gesture::CircleClockwise
+        } else {
+            Gesture::CircleCounterclockwise
+        };
+        *self = Self::default();
+        Some(circle)
+    }
+}
@@ -0,0 +1,64 @@
+/// The number of samples averaged to estimate the initial bias.
+const CALIBRATION_SAMPLES: u32 = 100;
+/// The angular rate below which the remote is considered still, and
+/// the reading used to track the bias drift.
+const STILL_THRESHOLD: f64 = 30.0;
+/// The weight of a still reading in the bias estimate.
+const DRIFT_RATE: f64 = 0.01;
+
+/// Removes the bias from the Motion Plus angular rates.
+///
+/// The bias is first estimated from the average of the initial samples,
+/// during which the remote must be held still, and then follows the
+/// drift of the readings while the remote is still.
+pub struct Gyro {
+    bias: [f64; 3],
+    /// The number of samples accumulated in `bias` during calibration.
+    calibration: Option<u32>,
+}
+
+impl Default for Gyro {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gyro {
+    pub fn new() -> Self {
+        Self {
+            bias: [0.0; 3],
+            calibration: Some(0),
+        }
+    }
+
+    /// Restarts the calibration, e.g. after an impact knocked it off.
+    pub fn recalibrate(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Returns the angular rates around the X, Y and Z axes without
+    /// the bias, or `None` while calibrating.
+    pub fn update(&mut self, x: i32, y: i32, z: i32) -> Option<[f64; 3]> {
+        let raw = [x as f64, y as f64, z as f64];
+        if let Some(samples) = self.calibration {
+            let samples = samples + 1;
+            for (bias, value) in self.bias.iter_mut().zip(raw) {
+                // The running average of the samples.
+                *bias += (value - *bias) / samples as f64;
+            }
+            self.calibration = (samples < CALIBRATION_SAMPLES).then_some(samples);
+            return None;
+        }
+
+        let mut rates = [0.0; 3];
+        for ix in 0..3 {
+            rates[ix] = raw[ix] - self.bias[ix];
+        }
+        if rates.iter().all(|rate| rate.abs() < STILL_THRESHOLD) {
+            for (bias, value) in self.bias.iter_mut().zip(raw) {
+                *bias += (value - *bias) * DRIFT_RATE;
+            }
+        }
+        Some(rates)
+    }
+}
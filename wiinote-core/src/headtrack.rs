@@ -0,0 +1,107 @@
+use crate::calibration::{self, IR_HEIGHT, IR_WIDTH};
+
+/// The horizontal field of view of the IR camera, in degrees.
+const FIELD_OF_VIEW: f64 = 33.0;
+/// The distance between the outermost LEDs of a typical head tracking
+/// cap or clip, in centimeters.
+const LED_SPACING: f64 = 14.0;
+
+/// The position (in centimeters) and rotation (in degrees) of the head,
+/// relative to the IR camera axis.
+///
+/// With the camera facing the user, `x` and `yaw` grow to the user's
+/// right, `y` and `pitch` upwards and `z` away from the camera.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct HeadPose {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f64,
+    pub pitch: f64,
+    pub roll: f64,
+}
+
+impl HeadPose {
+    /// Encodes the pose as a packet of the opentrack "UDP over network"
+    /// input: the six values as little-endian doubles.
+    pub fn to_opentrack(&self) -> [u8; 48] {
+        let values = [self.x, self.y, self.z, self.yaw, self.pitch, self.roll];
+        let mut packet = [0; 48];
+        for (bytes, value) in packet.chunks_exact_mut(8).zip(values) {
+            bytes.copy_from_slice(&value.to_le_bytes());
+        }
+        packet
+    }
+}
+
+/// Estimates the pose of a head wearing IR LEDs (a cap or a clip with
+/// up to four of them) from the sources seen by the camera of a Wii
+/// Remote placed on the monitor.
+///
+/// The rotation follows the center of the sources, the distance their
+/// spread, and the roll the angle of the outermost ones.
+pub struct HeadTracker {
+    last: HeadPose,
+}
+
+impl Default for HeadTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeadTracker {
+    pub fn new() -> Self {
+        Self {
+            last: HeadPose {
+                z: 60.0, // at arm's length, until two sources are seen
+                ..HeadPose::default()
+            },
+        }
+    }
+
+    /// Returns the head pose for the IR sources, if any is in sight.
+    /// With a single source, the previous distance and roll are kept.
+    pub fn update(&mut self, sources: impl IntoIterator<Item = (i32, i32)>) -> Option<HeadPose> {
+        let sources: Vec<_> = sources.into_iter().collect();
+        let (x, y) = calibration::ir_position(sources.iter().copied())?;
+        let degrees_per_pixel = FIELD_OF_VIEW / IR_WIDTH;
+        // The camera faces the user, their right is its left.
+        let yaw = (IR_WIDTH / 2.0 - x) * degrees_per_pixel;
+        let pitch = (y - IR_HEIGHT / 2.0) * degrees_per_pixel;
+
+        let outermost = sources
+            .iter()
+            .enumerate()
+            .flat_map(|(i, a)| sources[i + 1..].iter().map(move |b| (*a, *b)))
+            .filter(|(a, b)| a != b)
+            .max_by_key(|((x0, y0), (x1, y1))| (x1 - x0).pow(2) + (y1 - y0).pow(2));
+        let (z, roll) = match outermost {
+            Some(((x0, y0), (x1, y1))) => {
+                let (dx, dy) = ((x0 - x1) as f64, (y1 - y0) as f64);
+                let spread = (dx.hypot(dy) * degrees_per_pixel).to_radians();
+                let z = LED_SPACING / 2.0 / (spread / 2.0).tan();
+                // The order of the sources is arbitrary, keep the
+                // roll within a half turn.
+                let mut roll = dy.atan2(dx).to_degrees();
+                if roll > 90.0 {
+                    roll -= 180.0;
+                } else if roll <= -90.0 {
+                    roll += 180.0;
+                }
+                (z, roll)
+            }
+            None => (self.last.z, self.last.roll),
+        };
+
+        self.last = HeadPose {
+            x: z * yaw.to_radians().tan(),
+            y: z * pitch.to_radians().tan(),
+            z,
+            yaw,
+            pitch,
+            roll,
+        };
+        Some(self.last)
+    }
+}
@@ -0,0 +1,65 @@
+use crate::config::IdleConfig;
+use std::future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How long before disconnecting the user is warned.
+pub const WARNING: Duration = Duration::from_secs(30);
+
+/// What happened once the Wii Remote was left idle for long enough.
+pub enum IdleState {
+    /// The remote is about to be disconnected.
+    Warning,
+    /// The remote must be disconnected.
+    Elapsed,
+}
+
+/// Tracks the time since the last button press, to disconnect the
+/// Wii Remote once it is left alone and save its batteries.
+pub struct IdleTimer {
+    /// The inactivity period, unless the timer is disabled.
+    timeout: Option<Duration>,
+    warn: bool,
+    deadline: Instant,
+    warned: bool,
+}
+
+impl IdleTimer {
+    pub fn new(config: Option<&IdleConfig>) -> Self {
+        let timeout = config.map(IdleConfig::timeout);
+        Self {
+            timeout,
+            warn: config.is_some_and(|config| config.warn),
+            deadline: Instant::now() + timeout.unwrap_or_default(),
+            warned: false,
+        }
+    }
+
+    /// Restarts the inactivity period, e.g. on a button press.
+    pub fn reset(&mut self) {
+        if let Some(timeout) = self.timeout {
+            self.deadline = Instant::now() + timeout;
+            self.warned = false;
+        }
+    }
+
+    /// Waits until the warning is due, if enabled, and then until the
+    /// inactivity period elapses. Never completes if disabled.
+    pub async fn tick(&mut self) -> IdleState {
+        if self.timeout.is_none() {
+            return future::pending().await;
+        }
+        let warning = self.deadline.checked_sub(WARNING);
+        match warning {
+            Some(warning) if self.warn && !self.warned && warning > Instant::now() => {
+                tokio::time::sleep_until(warning).await;
+                self.warned = true;
+                IdleState::Warning
+            }
+            _ => {
+                tokio::time::sleep_until(self.deadline).await;
+                IdleState::Elapsed
+            }
+        }
+    }
+}
@@ -0,0 +1,493 @@
+use crate::mapping::{self, Action, Button, KeyCode};
+use anyhow::{anyhow, bail, Context, Error, Result};
+use serde::Deserialize;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// The mappers whose button assignments can be imported.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// A GlovePIE script (`.PIE`), e.g. `Key.Right = Wiimote.A`.
+    GlovePie,
+    /// A WiinUSoft profile (`.wsp`), mapping the buttons to those of an
+    /// Xbox 360 controller.
+    WiinUSoft,
+}
+
+impl Format {
+    /// Guesses the format from the extension of the file name.
+    pub fn from_extension(name: &str) -> Option<Self> {
+        let (_, extension) = name.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "pie" => Some(Self::GlovePie),
+            "wsp" | "json" => Some(Self::WiinUSoft),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "glovepie" => Ok(Self::GlovePie),
+            "wiinusoft" => Ok(Self::WiinUSoft),
+            _ => bail!("expected `glovepie` or `wiinusoft`, got `{}`", s),
+        }
+    }
+}
+
+/// The button assignments translated from the script or profile of
+/// another mapper, as the sections of a wiinote configuration.
+#[derive(Debug, Default)]
+pub struct Import {
+    /// The keys of each button, in order of appearance; a button
+    /// assigned several keys presses them all.
+    bindings: Vec<(Button, Vec<String>)>,
+    /// The keys of the buttons held down.
+    holds: Vec<(Button, Vec<String>)>,
+    /// The keys of the buttons pressed together.
+    chords: Vec<(Vec<Button>, Vec<String>)>,
+    /// Why each assignment that has no equivalent was skipped.
+    skipped: Vec<String>,
+}
+
+impl Import {
+    /// Translates the script or profile in the given format.
+    pub fn parse(format: Format, contents: &str) -> Result<Self> {
+        match format {
+            Format::GlovePie => Ok(Self::glovepie(contents)),
+            Format::WiinUSoft => Self::wiinusoft(contents),
+        }
+    }
+
+    /// Translates the assignments of keys or mouse buttons to Wii Remote
+    /// buttons of a GlovePIE script, e.g. `Key.Ctrl + Key.C = Wiimote.A`,
+    /// `Key.Escape = Wiimote.A and Wiimote.B` for a chord, or
+    /// `Key.Space = HeldDown(Wiimote.A, 1s)` for a hold. The other
+    /// statements (conditions, variables, the lights...) are skipped.
+    pub fn glovepie(script: &str) -> Self {
+        let mut import = Self::default();
+        let mut in_comment = false;
+        for (ix, line) in script.lines().enumerate() {
+            let code = strip_comments(line, &mut in_comment);
+            for statement in code.split(';').map(str::trim) {
+                if statement.is_empty() {
+                    continue;
+                }
+                if let Err(err) = import.glovepie_statement(statement) {
+                    import
+                        .skipped
+                        .push(format!("line {}: `{}`: {}", ix + 1, statement, err));
+                }
+            }
+        }
+        import
+    }
+
+    fn glovepie_statement(&mut self, statement: &str) -> Result<()> {
+        let lower = statement.to_ascii_lowercase();
+        if lower.contains("==") || lower.starts_with("if ") || !lower.contains('=') {
+            bail!("not an assignment");
+        }
+        let (output, input) = statement.split_once('=').unwrap();
+        let output = output.trim().trim_end_matches(':').trim();
+        if output.to_ascii_lowercase().starts_with("wiimote") {
+            bail!("sets the Wii Remote, not a key");
+        }
+        let keys = output
+            .split('+')
+            .map(|key| glovepie_key(key.trim()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let (input, held) = match strip_call(input.trim(), "helddown") {
+            // The hold time is the one of wiinote.
+            Some(args) => (args.split(',').next().unwrap_or_default(), true),
+            None => (input.trim(), false),
+        };
+        let input = strip_call(input, "pressed").unwrap_or(input);
+        for alternative in split_words(input, &["or", "||", "|"]) {
+            let buttons = split_words(alternative, &["and", "&&", "&"])
+                .into_iter()
+                .map(glovepie_button)
+                .collect::<Result<Vec<_>>>()?;
+            self.add(&buttons, held, &keys)?;
+        }
+        Ok(())
+    }
+
+    /// Translates the Wii Remote buttons of a WiinUSoft profile mapped
+    /// to the buttons of an Xbox 360 controller with an equivalent key,
+    /// e.g. A to Enter and B to Esc, as in the big picture modes of
+    /// media centers. The sticks and triggers are skipped.
+    pub fn wiinusoft(profile: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Profile {
+            controller_map_keys: Vec<String>,
+            controller_map_values: Vec<String>,
+        }
+
+        const KEYS: [(&str, &str); 12] = [
+            ("UP", "Up"),
+            ("DOWN", "Down"),
+            ("LEFT", "Left"),
+            ("RIGHT", "Right"),
+            ("A", "Enter"),
+            ("B", "Esc"),
+            ("X", "Space"),
+            ("Y", "Tab"),
+            ("LB", "PageUp"),
+            ("RB", "PageDown"),
+            ("BACK", "BackSpace"),
+            ("START", "Enter"),
+        ];
+
+        let profile: Profile =
+            serde_json::from_str(profile).context("invalid WiinUSoft profile")?;
+        let mut import = Self::default();
+        for (input, output) in profile
+            .controller_map_keys
+            .iter()
+            .zip(&profile.controller_map_values)
+        {
+            let button = match input.strip_prefix('w') {
+                // The Wii Remote buttons are prefixed with `w`, e.g. `wA`.
+                Some(button) => glovepie_button(button),
+                None => Err(anyhow!("not a Wii Remote button")),
+            };
+            let key = KEYS
+                .iter()
+                .find(|(name, _)| name == output)
+                .map(|(_, key)| key.to_string())
+                .ok_or_else(|| anyhow!("no key is equivalent to `{}`", output));
+            if let Err(err) = button.and_then(|button| import.add(&[button], false, &[key?])) {
+                import
+                    .skipped
+                    .push(format!("`{}` to `{}`: {}", input, output, err));
+            }
+        }
+        Ok(import)
+    }
+
+    /// Assigns the keys to the buttons, pressed together if several,
+    /// or held down. Keys assigned to the same buttons are merged.
+    fn add(&mut self, buttons: &[Button], held: bool, keys: &[String]) -> Result<()> {
+        // Check the action through the configuration parser.
+        Action::deserialize(action(keys))?;
+        let mapped = match (buttons, held) {
+            ([button], false) => entry(&mut self.bindings, *button),
+            ([button], true) => entry(&mut self.holds, *button),
+            (_, false) => entry(&mut self.chords, buttons.to_vec()),
+            (_, true) => bail!("held chords are not supported"),
+        };
+        for key in keys {
+            if !mapped.contains(key) {
+                mapped.push(key.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns why the assignments without an equivalent were skipped.
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// Formats the sections of the configuration, listing the skipped
+    /// assignments in comments.
+    pub fn to_toml(&self) -> String {
+        let mut toml = String::new();
+        for skipped in &self.skipped {
+            let _ = writeln!(toml, "# Skipped {}", skipped);
+        }
+        for (header, assignments) in [("[bindings]", &self.bindings), ("[holds]", &self.holds)] {
+            if assignments.is_empty() {
+                continue;
+            }
+            if !toml.is_empty() {
+                toml.push('\n');
+            }
+            toml.push_str(header);
+            toml.push('\n');
+            for (button, keys) in assignments {
+                let _ = writeln!(toml, "{:?} = {}", button, format_action(keys));
+            }
+        }
+        for (buttons, keys) in &self.chords {
+            if !toml.is_empty() {
+                toml.push('\n');
+            }
+            let buttons: Vec<_> = buttons
+                .iter()
+                .map(|button| format!("{:?}", button))
+                .collect();
+            let _ = writeln!(
+                toml,
+                "[[chords]]\nbuttons = {:?}\naction = {}",
+                buttons,
+                format_action(keys)
+            );
+        }
+        toml
+    }
+}
+
+/// Removes the `//` and `/* */` comments of the line, the latter
+/// possibly spanning several lines.
+fn strip_comments(mut line: &str, in_comment: &mut bool) -> String {
+    let mut code = String::new();
+    loop {
+        if *in_comment {
+            match line.find("*/") {
+                Some(end) => {
+                    line = &line[end + 2..];
+                    *in_comment = false;
+                }
+                None => return code,
+            }
+        }
+        match (line.find("//"), line.find("/*")) {
+            (Some(line_comment), block) if !matches!(block, Some(block) if block < line_comment) => {
+                code.push_str(&line[..line_comment]);
+                return code;
+            }
+            (_, Some(block)) => {
+                code.push_str(&line[..block]);
+                line = &line[block + 2..];
+                *in_comment = true;
+            }
+            (_, None) => {
+                code.push_str(line);
+                return code;
+            }
+        }
+    }
+}
+
+/// Returns the keys assigned to the buttons, added if missing.
+fn entry<T: PartialEq>(assignments: &mut Vec<(T, Vec<String>)>, buttons: T) -> &mut Vec<String> {
+    let ix = match assignments.iter().position(|(other, _)| *other == buttons) {
+        Some(ix) => ix,
+        None => {
+            assignments.push((buttons, Vec::new()));
+            assignments.len() - 1
+        }
+    };
+    &mut assignments[ix].1
+}
+
+/// Returns the action pressing the keys: a key name, or a group of them.
+fn action(keys: &[String]) -> toml::Value {
+    let names = keys.iter().cloned().map(toml::Value::String);
+    match keys {
+        [_] => names.into_iter().next().unwrap(),
+        _ => {
+            let mut group = toml::value::Table::new();
+            group.insert("group".to_owned(), toml::Value::Array(names.collect()));
+            toml::Value::Table(group)
+        }
+    }
+}
+
+/// Formats the action pressing the keys as an inline TOML value.
+fn format_action(keys: &[String]) -> String {
+    let names: Vec<_> = keys.iter().map(|key| format!(r#""{}""#, key)).collect();
+    match names.as_slice() {
+        [name] => name.clone(),
+        _ => format!("{{ group = [{}] }}", names.join(", ")),
+    }
+}
+
+/// Returns the arguments of a call to the given function, e.g. `1, 2`
+/// for `f(1, 2)`, if the expression is one.
+fn strip_call<'a>(expression: &'a str, function: &str) -> Option<&'a str> {
+    let (name, args) = expression.split_once('(')?;
+    let args = args.trim_end().strip_suffix(')')?;
+    name.trim()
+        .eq_ignore_ascii_case(function)
+        .then(|| args.trim())
+}
+
+/// Splits the expression at the given operators, words (e.g. `and`)
+/// being surrounded by spaces.
+fn split_words<'a>(expression: &'a str, operators: &[&str]) -> Vec<&'a str> {
+    let lower = expression.to_ascii_lowercase();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut ix = 0;
+    while ix < expression.len() {
+        let found = operators.iter().find(|op| {
+            let word = op.starts_with(char::is_alphabetic);
+            lower[ix..].starts_with(*op)
+                && (!word
+                    || (lower[..ix].ends_with(' ') && lower[ix + op.len()..].starts_with(' ')))
+        });
+        match found {
+            Some(op) => {
+                parts.push(expression[start..ix].trim());
+                ix += op.len();
+                start = ix;
+            }
+            None => ix += lower[ix..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    parts.push(expression[start..].trim());
+    parts
+}
+
+/// Returns the button of a GlovePIE input, e.g. `Wiimote.A` or
+/// `Wiimote1.Home`.
+fn glovepie_button(input: &str) -> Result<Button> {
+    let lower = input.to_ascii_lowercase();
+    let name = match lower.split_once('.') {
+        Some(("wiimote" | "wiimote1", name)) => name,
+        Some((device, _)) if device.starts_with("wiimote") => {
+            bail!("only the first Wii Remote is imported")
+        }
+        Some(_) => bail!("unsupported input `{}`", input),
+        // The WiinUSoft names have no device.
+        None => lower.as_str(),
+    };
+    match name {
+        "one" | "two" => bail!("One and Two are reserved for the light display"),
+        "up" | "down" | "left" | "right" | "a" | "b" | "plus" | "minus" | "home" => name.parse(),
+        _ => bail!("unsupported input `{}`", input),
+    }
+}
+
+/// Returns the name of the key or mouse button of a GlovePIE output,
+/// e.g. `Key.Escape`, `Ctrl` or `Mouse.LeftButton`.
+fn glovepie_key(output: &str) -> Result<String> {
+    const ALIASES: [(&str, &str); 62] = [
+        ("escape", "Esc"),
+        ("return", "Enter"),
+        ("spacebar", "Space"),
+        ("backspace", "BackSpace"),
+        ("uparrow", "Up"),
+        ("downarrow", "Down"),
+        ("leftarrow", "Left"),
+        ("rightarrow", "Right"),
+        ("pgup", "PageUp"),
+        ("prior", "PageUp"),
+        ("pgdn", "PageDown"),
+        ("next", "PageDown"),
+        ("ins", "Insert"),
+        ("del", "Delete"),
+        ("ctrl", "LeftControl"),
+        ("control", "LeftControl"),
+        ("leftctrl", "LeftControl"),
+        ("lctrl", "LeftControl"),
+        ("rightctrl", "RightControl"),
+        ("rctrl", "RightControl"),
+        ("shift", "LeftShift"),
+        ("lshift", "LeftShift"),
+        ("rshift", "RightShift"),
+        ("alt", "LeftAlt"),
+        ("lalt", "LeftAlt"),
+        ("menu", "LeftAlt"),
+        ("ralt", "RightAlt"),
+        ("altgr", "RightAlt"),
+        ("windows", "LeftMeta"),
+        ("win", "LeftMeta"),
+        ("leftwindows", "LeftMeta"),
+        ("leftwin", "LeftMeta"),
+        ("lwin", "LeftMeta"),
+        ("dash", "Minus"),
+        ("equals", "Equal"),
+        ("period", "Dot"),
+        ("fullstop", "Dot"),
+        ("forwardslash", "Slash"),
+        ("one", "1"),
+        ("two", "2"),
+        ("three", "3"),
+        ("four", "4"),
+        ("five", "5"),
+        ("six", "6"),
+        ("seven", "7"),
+        ("eight", "8"),
+        ("nine", "9"),
+        ("zero", "0"),
+        ("volumemute", "Mute"),
+        ("mediaplaypause", "PlayPause"),
+        ("medianexttrack", "NextSong"),
+        ("nexttrack", "NextSong"),
+        ("mediaprevtrack", "PreviousSong"),
+        ("prevtrack", "PreviousSong"),
+        ("previoustrack", "PreviousSong"),
+        ("mediastop", "StopCD"),
+        ("leftbutton", "MouseLeft"),
+        ("rightbutton", "MouseRight"),
+        ("middlebutton", "MouseMiddle"),
+        ("wheelup", "ScrollUp"),
+        ("wheeldown", "ScrollDown"),
+        ("stop", "StopCD"),
+    ];
+
+    let lower = output.to_ascii_lowercase();
+    let (device, name) = match lower.split_once('.') {
+        Some((device, name)) => (device, name),
+        None => ("key", lower.as_str()),
+    };
+    let alias = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map(|(_, key)| *key);
+    let mouse = alias.is_some_and(|key| key.starts_with("Mouse") || key.starts_with("Scroll"));
+    match (device, alias) {
+        ("mouse", Some(key)) if mouse => Ok(key.to_owned()),
+        ("key" | "keyboard", Some(key)) if !mouse => Ok(key.to_owned()),
+        // Take the spelling of wiinote, e.g. `PageUp` for `pageup`.
+        ("key" | "keyboard", None) => name
+            .parse()
+            .ok()
+            .and_then(|KeyCode(key)| mapping::key_name(key))
+            .map(str::to_owned)
+            .ok_or_else(|| anyhow!("unknown key `{}`", output)),
+        _ => bail!("unsupported output `{}`", output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn imports_glovepie_scripts() {
+        let import = Import::glovepie(include_str!("../testdata/powerpoint.PIE"));
+        let toml = import.to_toml();
+        let config: Config = toml::from_str(&toml).unwrap();
+        assert_eq!(config.bindings.len(), 9, "{}", toml);
+        assert_eq!(
+            config.bindings[&Button::A],
+            Action::Key("Right".parse().unwrap())
+        );
+        assert!(toml.contains(r#"Down = { group = ["LeftControl", "P"] }"#));
+        assert!(toml.contains(r#"Up = "MouseLeft""#));
+        assert!(toml.contains(r#"Home = { group = ["LeftAlt", "Tab"] }"#));
+        assert_eq!(config.holds.len(), 1);
+        assert_eq!(config.chords[0].buttons, [Button::Plus, Button::Minus]);
+        // One, the lights, rumble, nunchuk, pointer and control flow.
+        assert_eq!(import.skipped().len(), 8, "{:#?}", import.skipped());
+    }
+
+    #[test]
+    fn imports_wiinusoft_profiles() {
+        let import = Import::wiinusoft(include_str!("../testdata/wiimote.wsp")).unwrap();
+        let toml = import.to_toml();
+        let config: Config = toml::from_str(&toml).unwrap();
+        assert_eq!(config.bindings.len(), 8, "{}", toml);
+        assert_eq!(
+            config.bindings[&Button::A],
+            Action::Key("Enter".parse().unwrap())
+        );
+        assert_eq!(
+            config.bindings[&Button::Plus],
+            Action::Key("Enter".parse().unwrap())
+        );
+        // One, Two, Home (Guide) and the tilts.
+        assert_eq!(import.skipped().len(), 7, "{:#?}", import.skipped());
+    }
+}
@@ -0,0 +1,53 @@
+use std::fs;
+use std::path::Path;
+
+/// The prefix of the names of the input devices created by `hid-wiimote`,
+/// e.g. `Nintendo Wii Remote Nunchuk`.
+const NAME_PREFIX: &str = "Nintendo Wii Remote";
+/// The udev property telling libinput (and thus most desktops) to skip a device.
+const IGNORE_PROPERTY: &str = "E:LIBINPUT_IGNORE_DEVICE=1";
+
+/// Returns the names of the kernel input devices of the Wii Remotes
+/// that emit keys and are read by libinput, so the desktop receives
+/// the presses of both the kernel device and the virtual keyboard.
+///
+/// The devices cannot be grabbed instead, as xwiimote reads the events
+/// from them too.
+pub fn exposed_devices() -> Vec<String> {
+    let entries = match fs::read_dir("/sys/class/input") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut names: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = fs::read_to_string(path.join("device/name")).ok()?;
+            let name = name.trim();
+            (name.starts_with(NAME_PREFIX) && emits_keys(&path) && !is_ignored(&path))
+                .then(|| name.to_owned())
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Returns whether the event device supports any key, e.g. unlike the
+/// accelerometer or IR devices.
+fn emits_keys(event: &Path) -> bool {
+    fs::read_to_string(event.join("device/capabilities/key"))
+        .is_ok_and(|bits| bits.split_whitespace().any(|word| word != "0"))
+}
+
+/// Returns whether udev tagged the event device to be ignored by libinput.
+fn is_ignored(event: &Path) -> bool {
+    let numbers = match fs::read_to_string(event.join("dev")) {
+        Ok(numbers) => numbers,
+        Err(_) => return false,
+    };
+    // The udev database entry of a character device is named `c<major>:<minor>`.
+    let data = Path::new("/run/udev/data").join(format!("c{}", numbers.trim()));
+    fs::read_to_string(data).is_ok_and(|data| data.lines().any(|line| line == IGNORE_PROPERTY))
+}
@@ -0,0 +1,1064 @@
+use crate::clipboard;
+use crate::command;
+use crate::compositor;
+use crate::error::UinputError;
+use crate::mapping::{
+    self, Action, Button, Chord, CommandAction, GroupAction, KeyCode, MacroStep, MouseAction,
+    OnError, Profile,
+};
+use crate::mpris;
+use crate::stats::IgnoreReason;
+use crate::volume;
+use anyhow::{anyhow, Context, Result};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{info, warn};
+use uinput::device::Builder;
+use uinput::event;
+use uinput::event::controller::Mouse;
+use uinput::event::keyboard;
+use uinput::event::relative::{Position, Wheel};
+use xwiimote::event::{Key, KeyState};
+
+static DEV_NAME: &str = "Wiinote";
+
+/// Returns the name of a virtual device, scoped to the instance
+/// if given (e.g. `Wiinote (projector)`).
+pub fn device_name(name: &str, instance: Option<&str>) -> String {
+    match instance {
+        Some(instance) => format!("{} ({})", name, instance),
+        None => name.to_owned(),
+    }
+}
+
+/// Creates a virtual device with the given name, whose events are
+/// registered by `configure`.
+pub fn create_device(
+    name: String,
+    configure: impl FnOnce(Builder) -> uinput::Result<Builder>,
+) -> Result<uinput::Device, UinputError> {
+    let create = || configure(uinput::default()?.name(&name)?)?.create();
+    create().map_err(|source| UinputError::Create { name, source })
+}
+
+/// The media key that pauses (without toggling) the playback.
+const PAUSE: event::Keyboard = event::Keyboard::Misc(keyboard::Misc::PauseCD);
+
+/// How long a button must be held to run its hold action.
+const HOLD_TIME: Duration = Duration::from_millis(500);
+/// How long a button must be held to turn the Wii Remote off, longer
+/// to avoid doing so by accident.
+const POWER_OFF_HOLD_TIME: Duration = Duration::from_secs(3);
+/// How long the action of a button in a chord is delayed, waiting for
+/// the other buttons of the chord.
+const CHORD_WINDOW: Duration = Duration::from_millis(60);
+
+/// An action run on the Wii Remote itself rather than the virtual
+/// devices, returned by [`Keyboard::take_remote_actions`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RemoteAction {
+    /// Disconnects the remote, see [`Action::PowerOff`].
+    PowerOff,
+    /// Plays the sound file on the speaker, see [`Action::PlaySound`].
+    PlaySound(PathBuf),
+    /// Starts or stops moving the pointer, as the button of an
+    /// [`Action::Pointer`] is held and released.
+    Pointer(bool),
+    /// Pulses the rumble motor, see [`Action::Rumble`].
+    Rumble(Duration),
+    /// Tells the profile was switched to the one at the given index,
+    /// see [`Action::Profile`].
+    Profile(usize),
+    /// Opens a sync window, see [`Action::Sync`].
+    Sync,
+}
+
+/// A key or mouse button of the virtual devices.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Input {
+    Key(event::Keyboard),
+    Mouse(Mouse),
+}
+
+/// Where a [`Keyboard`] emits its events: a uinput device, or a
+/// [`Recorder`] to try the mappings without one.
+pub trait Emitter {
+    fn press(&mut self, input: Input) -> uinput::Result<()>;
+    fn release(&mut self, input: Input) -> uinput::Result<()>;
+    /// Moves the relative axis (the pointer or a scroll wheel).
+    fn move_by(&mut self, axis: event::Relative, value: i32) -> uinput::Result<()>;
+    fn synchronize(&mut self) -> uinput::Result<()>;
+}
+
+impl Emitter for uinput::Device {
+    fn press(&mut self, input: Input) -> uinput::Result<()> {
+        match input {
+            Input::Key(key) => uinput::Device::press(self, &key),
+            Input::Mouse(button) => uinput::Device::press(self, &event::Controller::Mouse(button)),
+        }
+    }
+
+    fn release(&mut self, input: Input) -> uinput::Result<()> {
+        match input {
+            Input::Key(key) => uinput::Device::release(self, &key),
+            Input::Mouse(button) => {
+                uinput::Device::release(self, &event::Controller::Mouse(button))
+            }
+        }
+    }
+
+    fn move_by(&mut self, axis: event::Relative, value: i32) -> uinput::Result<()> {
+        self.position(&axis, value)
+    }
+
+    fn synchronize(&mut self) -> uinput::Result<()> {
+        uinput::Device::synchronize(self)
+    }
+}
+
+/// An event emitted by a [`Keyboard`] into a [`Recorder`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Emitted {
+    Press(Input),
+    Release(Input),
+    Move(event::Relative, i32),
+}
+
+/// An [`Emitter`] recording the events instead of emitting them, e.g.
+/// to test the mappings. Its clones share the record.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    emitted: Rc<RefCell<Vec<Emitted>>>,
+    /// The inputs pressed and not released yet, in press order.
+    held: Rc<RefCell<Vec<Input>>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the events recorded since the last call.
+    pub fn take(&self) -> Vec<Emitted> {
+        std::mem::take(&mut self.emitted.borrow_mut())
+    }
+
+    /// Returns the inputs left pressed.
+    pub fn held(&self) -> Vec<Input> {
+        self.held.borrow().clone()
+    }
+}
+
+impl Emitter for Recorder {
+    fn press(&mut self, input: Input) -> uinput::Result<()> {
+        self.emitted.borrow_mut().push(Emitted::Press(input));
+        let mut held = self.held.borrow_mut();
+        if !held.contains(&input) {
+            held.push(input);
+        }
+        Ok(())
+    }
+
+    fn release(&mut self, input: Input) -> uinput::Result<()> {
+        self.emitted.borrow_mut().push(Emitted::Release(input));
+        self.held.borrow_mut().retain(|held| *held != input);
+        Ok(())
+    }
+
+    fn move_by(&mut self, axis: event::Relative, value: i32) -> uinput::Result<()> {
+        self.emitted.borrow_mut().push(Emitted::Move(axis, value));
+        Ok(())
+    }
+
+    fn synchronize(&mut self) -> uinput::Result<()> {
+        Ok(())
+    }
+}
+
+/// The keys of the paste shortcut, Ctrl+V.
+const CONTROL: event::Keyboard = event::Keyboard::Key(keyboard::Key::LeftControl);
+const PASTE: event::Keyboard = event::Keyboard::Key(keyboard::Key::V);
+
+pub struct Keyboard {
+    device: Box<dyn Emitter>,
+    profiles: Vec<Profile>,
+    /// The index of the profile whose mapping is used.
+    active: usize,
+    /// The keys emitted for each pressed button. The key is released
+    /// when the button is, even if the profile changed in between.
+    pressed: HashMap<Button, Pressed>,
+    /// The keys pressed regardless of the mapping, e.g. by the
+    /// steering mode.
+    held: Vec<event::Keyboard>,
+    /// The pressed buttons with a hold action or in a chord, and the
+    /// instant their hold time (or chord window) elapses. Until then,
+    /// it's unknown whether they will be tapped, held or chorded.
+    holding: HashMap<Button, Instant>,
+    /// The actions on the remote itself that ran, see
+    /// [`take_remote_actions`](Self::take_remote_actions).
+    remote_actions: Vec<RemoteAction>,
+    /// The chord being pressed. Its buttons are suppressed until
+    /// they are released.
+    chord: Option<ActiveChord>,
+    /// The steps left of the running macro, and the instant the next
+    /// one runs if waiting for a delay.
+    macro_steps: VecDeque<MacroStep>,
+    macro_resume: Option<Instant>,
+    /// The instant each command was last run, to rate limit them.
+    command_runs: HashMap<String, Instant>,
+    /// If set, key presses are dropped. Releases are still
+    /// forwarded so that no key remains pressed after muting.
+    muted: bool,
+    /// Whether the remote is moving, skipping the commands and power
+    /// offs; see [`StationaryGuard`](crate::stationary::StationaryGuard).
+    moving: bool,
+}
+
+impl Keyboard {
+    /// Creates a virtual keyboard emitting the keys bound by the
+    /// mapping of the active profile, initially the first one.
+    pub fn try_new(profiles: Vec<Profile>, instance: Option<&str>) -> Result<Self, UinputError> {
+        let device = create_device(device_name(DEV_NAME, instance), |mut builder| {
+            // Register every bindable key (including the numpad and
+            // Pause keys), so the mapping can change at runtime.
+            for event in mapping::all_keys() {
+                builder = builder.event(event)?;
+            }
+            // Used by the mouse mode and the mouse bindings.
+            for axis in [Position::X, Position::Y] {
+                builder = builder.event(event::Relative::Position(axis))?;
+            }
+            builder = builder.event(event::Relative::Wheel(Wheel::Vertical))?;
+            // Used by the Nunchuk stick.
+            builder = builder.event(event::Relative::Wheel(Wheel::Horizontal))?;
+            for button in [Mouse::Left, Mouse::Right, Mouse::Middle] {
+                builder = builder.event(event::Controller::Mouse(button))?;
+            }
+            Ok(builder)
+        })?;
+        Ok(Self::with_emitter(profiles, Box::new(device)))
+    }
+
+    /// Creates a keyboard emitting into the given emitter, e.g. a
+    /// [`Recorder`]; see [`try_new`](Self::try_new).
+    pub fn with_emitter(profiles: Vec<Profile>, device: Box<dyn Emitter>) -> Self {
+        assert!(!profiles.is_empty(), "at least one profile is required");
+        Self {
+            device,
+            profiles,
+            active: 0,
+            pressed: HashMap::new(),
+            held: Vec::new(),
+            holding: HashMap::new(),
+            remote_actions: Vec::new(),
+            chord: None,
+            macro_steps: VecDeque::new(),
+            macro_resume: None,
+            command_runs: HashMap::new(),
+            muted: false,
+            moving: false,
+        }
+    }
+
+    /// Returns the index and the active profile.
+    pub fn profile(&self) -> (usize, &Profile) {
+        (self.active, &self.profiles[self.active])
+    }
+
+    /// Activates the next profile, wrapping around after the last one.
+    pub fn cycle_profile(&mut self) -> (usize, &Profile) {
+        self.active = (self.active + 1) % self.profiles.len();
+        self.profile()
+    }
+
+    /// Activates the profile with the given name, if any.
+    pub fn select_profile(&mut self, name: &str) -> Option<(usize, &Profile)> {
+        self.active = self
+            .profiles
+            .iter()
+            .position(|profile| profile.name == name)?;
+        Some(self.profile())
+    }
+
+    /// Activates the profile of the extension (e.g. `nunchuk`), if any.
+    pub fn select_extension_profile(&mut self, extension: &str) -> Option<(usize, &Profile)> {
+        self.active = self
+            .profiles
+            .iter()
+            .position(|profile| profile.extension.as_deref() == Some(extension))?;
+        Some(self.profile())
+    }
+
+    /// Replaces the profiles, e.g. after the configuration is reloaded.
+    /// The profile with the name of the active one stays active, if
+    /// any; otherwise, the first profile is.
+    pub fn set_profiles(&mut self, profiles: Vec<Profile>) -> (usize, &Profile) {
+        assert!(!profiles.is_empty(), "at least one profile is required");
+        let name = &self.profiles[self.active].name;
+        self.active = profiles
+            .iter()
+            .position(|profile| &profile.name == name)
+            .unwrap_or(0);
+        self.profiles = profiles;
+        self.profile()
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Sets whether the remote is moving, to skip the commands and
+    /// power offs meanwhile.
+    pub fn set_moving(&mut self, moving: bool) {
+        self.moving = moving;
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Presses and releases the media Pause key, even if muted.
+    pub fn pause_playback(&mut self) -> Result<()> {
+        self.click_input(Input::Key(PAUSE))?;
+        self.device.synchronize().map_err(|err| err.into())
+    }
+
+    /// Presses and releases the key.
+    ///
+    /// # Returns
+    /// The reason the key was dropped, if no event was emitted.
+    pub fn click(&mut self, key: keyboard::Key) -> Result<Option<IgnoreReason>> {
+        if self.muted {
+            return Ok(Some(IgnoreReason::Muted));
+        }
+        self.click_input(Input::Key(event::Keyboard::Key(key)))?;
+        self.device.synchronize()?;
+        Ok(None)
+    }
+
+    /// Clicks the key bound to the gesture, or runs its action.
+    ///
+    /// # Returns
+    /// The reason the gesture was dropped, if no event was emitted.
+    pub fn gesture(&mut self, button: Button) -> Result<Option<IgnoreReason>> {
+        let action = match self.profiles[self.active].mapping.binding(button) {
+            Some(action) => action.clone(),
+            None => return Ok(Some(IgnoreReason::Unmapped)),
+        };
+        self.trigger(action)
+    }
+
+    /// Runs the action once, releasing right away the keys it presses,
+    /// e.g. for a [cross-device chord](mapping::CrossChord).
+    ///
+    /// # Returns
+    /// The reason the action was dropped, if it didn't run.
+    pub fn trigger(&mut self, action: Action) -> Result<Option<IgnoreReason>> {
+        if self.muted {
+            return Ok(Some(IgnoreReason::Muted));
+        }
+        if let Some(pressed) = self.run(action)? {
+            self.release(pressed)?;
+        }
+        Ok(None)
+    }
+
+    /// Runs the action, returning the key or mouse button left pressed.
+    /// Its failures other than the ones of the virtual devices, e.g. a
+    /// command that cannot be spawned, are logged.
+    fn run(&mut self, action: Action) -> Result<Option<Pressed>> {
+        match self.attempt(action)? {
+            Ok(pressed) => Ok(pressed),
+            Err(err) => {
+                warn!("{:#}", err);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Runs the action, see [`run`](Self::run).
+    ///
+    /// # Returns
+    /// The key or mouse button left pressed, or the failure of the
+    /// action. The errors of the virtual devices are returned as is.
+    fn attempt(&mut self, action: Action) -> Result<Result<Option<Pressed>>> {
+        let pressed = match action {
+            Action::Key(KeyCode(event)) => {
+                self.device.press(Input::Key(event))?;
+                Pressed::Key(event)
+            }
+            Action::Mouse(MouseAction::Button(button)) => {
+                self.device.press(Input::Mouse(button))?;
+                Pressed::Mouse(button)
+            }
+            Action::Mouse(MouseAction::Scroll(notches)) => {
+                self.device
+                    .move_by(event::Relative::Wheel(Wheel::Vertical), notches)?;
+                self.device.synchronize()?;
+                return Ok(Ok(None));
+            }
+            Action::Paste(paste) => {
+                // Like the commands, failures don't affect the other bindings.
+                if let Err(err) = clipboard::copy(&paste.paste) {
+                    return Ok(Err(err.context("Cannot copy to the clipboard")));
+                }
+                if paste.ctrl_v {
+                    self.paste()?;
+                }
+                return Ok(Ok(None));
+            }
+            Action::Macro(steps) => {
+                self.start_macro(steps)?;
+                return Ok(Ok(None));
+            }
+            Action::Command(_) | Action::PowerOff if self.moving => {
+                return Ok(Err(anyhow!("Action skipped, the remote is moving")));
+            }
+            Action::Command(command) => return Ok(self.run_command(&command).map(|()| None)),
+            Action::Snap(action) => {
+                let snapped = compositor::snap(action.snap);
+                return Ok(snapped.map(|()| None).context("Cannot snap the window"));
+            }
+            Action::Media(action) => {
+                let controlled = mpris::control(&action);
+                return Ok(controlled
+                    .map(|()| None)
+                    .context("Cannot control the media player"));
+            }
+            Action::Volume(action) => {
+                let changed = volume::change(&action);
+                return Ok(changed.map(|()| None).context("Cannot change the volume"));
+            }
+            Action::PowerOff => {
+                self.remote_actions.push(RemoteAction::PowerOff);
+                return Ok(Ok(None));
+            }
+            Action::PlaySound(sound) => {
+                self.remote_actions
+                    .push(RemoteAction::PlaySound(sound.play_sound));
+                return Ok(Ok(None));
+            }
+            Action::Pointer => {
+                self.remote_actions.push(RemoteAction::Pointer(true));
+                return Ok(Ok(Some(Pressed::Pointer)));
+            }
+            Action::Rumble(rumble) => {
+                let duration = Duration::from_millis(rumble.rumble_ms);
+                self.remote_actions.push(RemoteAction::Rumble(duration));
+                return Ok(Ok(None));
+            }
+            Action::Profile(action) => {
+                let ix = match self.select_profile(&action.profile) {
+                    Some((ix, _)) => ix,
+                    None => return Ok(Err(anyhow!("Unknown profile `{}`", action.profile))),
+                };
+                self.remote_actions.push(RemoteAction::Profile(ix));
+                return Ok(Ok(None));
+            }
+            Action::Sync => {
+                self.remote_actions.push(RemoteAction::Sync);
+                return Ok(Ok(None));
+            }
+            Action::Group(group) => return self.run_group(group),
+        };
+        self.device.synchronize()?;
+        Ok(Ok(Some(pressed)))
+    }
+
+    /// Runs the actions of the group in order, see [`attempt`](Self::attempt).
+    fn run_group(&mut self, group: GroupAction) -> Result<Result<Option<Pressed>>> {
+        let mut held = Vec::new();
+        for action in group.group {
+            match self.attempt(action)? {
+                Ok(pressed) => held.extend(pressed),
+                Err(err) if group.on_error == OnError::Continue => warn!("{:#}", err),
+                Err(err) => {
+                    for pressed in held.into_iter().rev() {
+                        self.release(pressed)?;
+                    }
+                    return Ok(Err(err.context("Group aborted")));
+                }
+            }
+        }
+        Ok(Ok(match held.len() {
+            0 => None,
+            1 => held.pop(),
+            _ => Some(Pressed::Group(held)),
+        }))
+    }
+
+    /// Releases a key or mouse button pressed by [`run`](Self::run).
+    fn release(&mut self, pressed: Pressed) -> Result<()> {
+        match pressed {
+            Pressed::Key(event) => self.device.release(Input::Key(event))?,
+            Pressed::Mouse(button) => self.device.release(Input::Mouse(button))?,
+            Pressed::Pointer => {
+                self.remote_actions.push(RemoteAction::Pointer(false));
+                return Ok(());
+            }
+            Pressed::Group(held) => {
+                for pressed in held.into_iter().rev() {
+                    self.release(pressed)?;
+                }
+                return Ok(());
+            }
+        }
+        self.device.synchronize().map_err(|err| err.into())
+    }
+
+    /// Releases every key and mouse button left pressed, and forgets
+    /// the held buttons, chord and running macro; e.g. once the
+    /// Wii Remote disconnects, as its buttons won't be released.
+    pub fn release_all(&mut self) -> Result<()> {
+        for (_, pressed) in std::mem::take(&mut self.pressed) {
+            self.release(pressed)?;
+        }
+        for key in std::mem::take(&mut self.held) {
+            self.device.release(Input::Key(key))?;
+        }
+        // Pressed by the mouse mode, releasing them again is harmless.
+        for button in [Mouse::Left, Mouse::Right] {
+            self.device.release(Input::Mouse(button))?;
+        }
+        self.holding.clear();
+        self.chord = None;
+        self.macro_steps.clear();
+        self.macro_resume = None;
+        self.device.synchronize().map_err(|err| err.into())
+    }
+
+    /// Runs the macro steps, replacing the running macro if any.
+    fn start_macro(&mut self, steps: Vec<MacroStep>) -> Result<()> {
+        self.macro_steps = steps.into();
+        self.macro_resume = None;
+        self.run_macro()
+    }
+
+    /// Runs the macro steps until the end or the next delay.
+    fn run_macro(&mut self) -> Result<()> {
+        while let Some(step) = self.macro_steps.pop_front() {
+            match step {
+                MacroStep::Combo(keys) => {
+                    for key in &keys {
+                        self.device.press(Input::Key(*key))?;
+                    }
+                    self.device.synchronize()?;
+                    for key in keys.iter().rev() {
+                        self.device.release(Input::Key(*key))?;
+                    }
+                    self.device.synchronize()?;
+                }
+                MacroStep::Delay(delay) => {
+                    self.macro_resume = Some(Instant::now() + delay);
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Presses and releases the input, without synchronizing.
+    fn click_input(&mut self, input: Input) -> Result<()> {
+        self.device.press(input)?;
+        self.device.release(input)?;
+        Ok(())
+    }
+
+    /// Emits Ctrl+V, pasting the text copied by a [`PasteAction`](mapping::PasteAction).
+    fn paste(&mut self) -> Result<()> {
+        self.device.press(Input::Key(CONTROL))?;
+        self.click_input(Input::Key(PASTE))?;
+        self.device.release(Input::Key(CONTROL))?;
+        self.device.synchronize()?;
+        Ok(())
+    }
+
+    /// Spawns the command unless it ran less than its minimum interval ago.
+    fn run_command(&mut self, action: &CommandAction) -> Result<()> {
+        let now = Instant::now();
+        let min_interval = Duration::from_millis(action.min_interval_ms);
+        if let Some(last) = self.command_runs.get(&action.command) {
+            if now < *last + min_interval {
+                info!("Command skipped, ran too recently: {}", action.command);
+                return Ok(());
+            }
+        }
+        self.command_runs.insert(action.command.clone(), now);
+        command::spawn(&action.command).context("Cannot run command")
+    }
+
+    /// Presses or releases the key, regardless of the mapping.
+    ///
+    /// # Returns
+    /// The reason the key was dropped, if no event was emitted.
+    pub fn set_pressed(
+        &mut self,
+        key: event::Keyboard,
+        pressed: bool,
+    ) -> Result<Option<IgnoreReason>> {
+        if pressed {
+            if self.muted {
+                return Ok(Some(IgnoreReason::Muted));
+            }
+            self.device.press(Input::Key(key))?;
+            if !self.held.contains(&key) {
+                self.held.push(key);
+            }
+        } else {
+            self.device.release(Input::Key(key))?;
+            self.held.retain(|held| *held != key);
+        }
+        self.device.synchronize()?;
+        Ok(None)
+    }
+
+    /// Moves the pointer by the given amounts.
+    pub fn move_pointer(&mut self, dx: i32, dy: i32) -> Result<Option<IgnoreReason>> {
+        if self.muted {
+            return Ok(Some(IgnoreReason::Muted));
+        }
+        self.device
+            .move_by(event::Relative::Position(Position::X), dx)?;
+        self.device
+            .move_by(event::Relative::Position(Position::Y), dy)?;
+        self.device.synchronize()?;
+        Ok(None)
+    }
+
+    /// Scrolls by the given notches, horizontally (positive rightwards)
+    /// and vertically (positive upwards).
+    pub fn scroll(&mut self, horizontal: i32, vertical: i32) -> Result<Option<IgnoreReason>> {
+        if self.muted {
+            return Ok(Some(IgnoreReason::Muted));
+        }
+        for (wheel, notches) in [(Wheel::Horizontal, horizontal), (Wheel::Vertical, vertical)] {
+            if notches != 0 {
+                self.device
+                    .move_by(event::Relative::Wheel(wheel), notches)?;
+            }
+        }
+        self.device.synchronize()?;
+        Ok(None)
+    }
+
+    /// Presses or releases the mouse button.
+    pub fn mouse_button(
+        &mut self,
+        button: Mouse,
+        state: &KeyState,
+    ) -> Result<Option<IgnoreReason>> {
+        let button = Input::Mouse(button);
+        match *state {
+            KeyState::Down if self.muted => return Ok(Some(IgnoreReason::Muted)),
+            KeyState::Down => self.device.press(button)?,
+            KeyState::Up => self.device.release(button)?,
+            _ => return Ok(None),
+        }
+        self.device.synchronize()?;
+        Ok(None)
+    }
+
+    /// Emits the key bound to the button.
+    ///
+    /// # Returns
+    /// The reason the button state was dropped, if no event was emitted.
+    pub fn update(&mut self, key: &Key, state: &KeyState) -> Result<Option<IgnoreReason>> {
+        let button = match Button::from_key(key) {
+            Some(button) => button,
+            None => return Ok(Some(IgnoreReason::Unmapped)),
+        };
+        if *state == KeyState::Up && self.release_chord(button)? {
+            return Ok(None);
+        }
+        let mapping = &self.profiles[self.active].mapping;
+        match *state {
+            KeyState::Down if mapping.hold(button).is_some() || mapping.in_chord(button) => {
+                // Wait for the release, the hold time or the rest of
+                // the chord to decide.
+                let delay = match mapping.hold(button) {
+                    Some(Action::PowerOff) => POWER_OFF_HOLD_TIME,
+                    Some(_) => HOLD_TIME,
+                    None => CHORD_WINDOW,
+                };
+                self.holding.insert(button, Instant::now() + delay);
+                let holding = &self.holding;
+                match mapping
+                    .chord(|button| holding.contains_key(&button))
+                    .cloned()
+                {
+                    Some(chord) => self.start_chord(chord),
+                    None => Ok(None),
+                }
+            }
+            KeyState::Down => match mapping.binding(button).cloned() {
+                Some(action) => self.start(button, action),
+                None => Ok(Some(IgnoreReason::Unmapped)),
+            },
+            KeyState::Up => {
+                if self.holding.remove(&button).is_some() {
+                    // Released before the hold time, a tap.
+                    let action = match mapping.binding(button).cloned() {
+                        Some(action) => action,
+                        None => return Ok(Some(IgnoreReason::Unmapped)),
+                    };
+                    if let Some(reason) = self.start(button, action)? {
+                        return Ok(Some(reason));
+                    }
+                }
+                match self.pressed.remove(&button) {
+                    Some(pressed) => self.release(pressed)?,
+                    None => return Ok(None), // the press was dropped
+                }
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Forgets the pending hold or tap of the button, e.g. once it is
+    /// used as a modifier of a built-in combination.
+    pub fn cancel_hold(&mut self, button: Button) {
+        self.holding.remove(&button);
+    }
+
+    /// Returns the actions on the Wii Remote itself that ran since the
+    /// last call, which must be run by the owner of the device.
+    pub fn take_remote_actions(&mut self) -> Vec<RemoteAction> {
+        std::mem::take(&mut self.remote_actions)
+    }
+
+    /// Waits until the hold time of a held button or a macro delay
+    /// elapses, after which [`run_timers`](Self::run_timers) must be
+    /// called. Never completes if there is nothing to wait for.
+    pub async fn timer_elapsed(&self) {
+        match self.holding.values().chain(&self.macro_resume).min() {
+            Some(deadline) => tokio::time::sleep_until(*deadline).await,
+            None => futures_util::future::pending().await,
+        }
+    }
+
+    /// Runs the hold action of the buttons held for the hold time, the
+    /// regular action of the chord buttons pressed alone, and resumes
+    /// the running macro after its delay.
+    pub fn run_timers(&mut self) -> Result<()> {
+        let now = Instant::now();
+        // Few buttons are ever held, look them up again rather than
+        // collecting the expired ones.
+        while let Some(button) = self
+            .holding
+            .iter()
+            .find(|(_, deadline)| **deadline <= now)
+            .map(|(button, _)| *button)
+        {
+            self.holding.remove(&button);
+            let mapping = &self.profiles[self.active].mapping;
+            let action = mapping.hold(button).or_else(|| mapping.binding(button));
+            if let Some(action) = action.cloned() {
+                self.start(button, action)?;
+            }
+        }
+        if self.macro_resume.is_some_and(|resume| resume <= now) {
+            self.macro_resume = None;
+            self.run_macro()?;
+        }
+        Ok(())
+    }
+
+    /// Runs the action of a chord whose buttons were all pressed,
+    /// suppressing their individual actions.
+    fn start_chord(&mut self, chord: Chord) -> Result<Option<IgnoreReason>> {
+        for button in &chord.buttons {
+            self.holding.remove(button);
+        }
+        let mut active = ActiveChord {
+            buttons: chord.buttons,
+            key: None,
+        };
+        let result = if self.muted {
+            Some(IgnoreReason::Muted)
+        } else {
+            active.key = self.run(chord.action)?;
+            None
+        };
+        self.chord = Some(active);
+        Ok(result)
+    }
+
+    /// Handles the release of a button of the active chord, releasing
+    /// its key on the first one. Returns whether the button was part
+    /// of the chord.
+    fn release_chord(&mut self, button: Button) -> Result<bool> {
+        let chord = match &mut self.chord {
+            Some(chord) if chord.buttons.contains(&button) => chord,
+            _ => return Ok(false),
+        };
+        chord.buttons.retain(|other| *other != button);
+        let key = chord.key.take();
+        if chord.buttons.is_empty() {
+            self.chord = None;
+        }
+        if let Some(pressed) = key {
+            self.release(pressed)?;
+        }
+        Ok(true)
+    }
+
+    /// Runs the action of a pressed button. A bound key or mouse button
+    /// stays pressed until the button is released.
+    fn start(&mut self, button: Button, action: Action) -> Result<Option<IgnoreReason>> {
+        if self.muted {
+            return Ok(Some(IgnoreReason::Muted));
+        }
+        // Otherwise nothing is held, the release is ignored.
+        if let Some(pressed) = self.run(action)? {
+            self.pressed.insert(button, pressed);
+        }
+        Ok(None)
+    }
+}
+
+/// A key or mouse button pressed by a binding.
+#[derive(Clone)]
+enum Pressed {
+    Key(event::Keyboard),
+    Mouse(Mouse),
+    /// The pointer of an [`Action::Pointer`].
+    Pointer,
+    /// The keys, buttons and pointer held by an [`Action::Group`], in
+    /// press order.
+    Group(Vec<Pressed>),
+}
+
+/// A chord whose buttons are pressed.
+struct ActiveChord {
+    /// The buttons of the chord not released yet.
+    buttons: Vec<Button>,
+    /// The key emitted by the chord, released with the first button.
+    key: Option<Pressed>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn keyboard(config: &str) -> (Keyboard, Recorder) {
+        let config: Config = toml::from_str(config).unwrap();
+        let recorder = Recorder::new();
+        let keyboard =
+            Keyboard::with_emitter(mapping::profiles(&config), Box::new(recorder.clone()));
+        (keyboard, recorder)
+    }
+
+    fn key(key: keyboard::Key) -> Input {
+        Input::Key(event::Keyboard::Key(key))
+    }
+
+    #[test]
+    fn tap_emits_the_binding() {
+        let (mut keyboard, recorder) = keyboard("");
+        keyboard.update(&Key::A, &KeyState::Down).unwrap();
+        keyboard.update(&Key::A, &KeyState::Up).unwrap();
+        assert_eq!(
+            recorder.take(),
+            [
+                Emitted::Press(key(keyboard::Key::Enter)),
+                Emitted::Release(key(keyboard::Key::Enter)),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hold_runs_after_the_hold_time() {
+        let (mut keyboard, recorder) = keyboard("[holds]\nA = \"X\"");
+        // Released before the hold time, a tap.
+        keyboard.update(&Key::A, &KeyState::Down).unwrap();
+        assert_eq!(recorder.take(), []);
+        tokio::time::advance(HOLD_TIME - Duration::from_millis(1)).await;
+        keyboard.run_timers().unwrap();
+        assert_eq!(recorder.take(), []);
+        keyboard.update(&Key::A, &KeyState::Up).unwrap();
+        assert_eq!(
+            recorder.take(),
+            [
+                Emitted::Press(key(keyboard::Key::Enter)),
+                Emitted::Release(key(keyboard::Key::Enter)),
+            ]
+        );
+
+        keyboard.update(&Key::A, &KeyState::Down).unwrap();
+        let start = Instant::now();
+        keyboard.timer_elapsed().await;
+        assert_eq!(start.elapsed(), HOLD_TIME);
+        keyboard.run_timers().unwrap();
+        assert_eq!(recorder.take(), [Emitted::Press(key(keyboard::Key::X))]);
+        keyboard.update(&Key::A, &KeyState::Up).unwrap();
+        assert_eq!(recorder.take(), [Emitted::Release(key(keyboard::Key::X))]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn power_off_hold_takes_longer() {
+        let (mut keyboard, recorder) = keyboard("");
+        keyboard.update(&Key::Home, &KeyState::Down).unwrap();
+        tokio::time::advance(HOLD_TIME).await;
+        keyboard.run_timers().unwrap();
+        assert!(keyboard.take_remote_actions().is_empty());
+        tokio::time::advance(POWER_OFF_HOLD_TIME - HOLD_TIME).await;
+        keyboard.run_timers().unwrap();
+        assert_eq!(keyboard.take_remote_actions(), [RemoteAction::PowerOff]);
+        keyboard.update(&Key::Home, &KeyState::Up).unwrap();
+        assert_eq!(recorder.take(), []);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn chord_suppresses_its_buttons() {
+        let (mut keyboard, recorder) =
+            keyboard("[[chords]]\nbuttons = [\"A\", \"B\"]\naction = \"Space\"");
+        keyboard.update(&Key::A, &KeyState::Down).unwrap();
+        tokio::time::advance(CHORD_WINDOW / 2).await;
+        keyboard.run_timers().unwrap();
+        keyboard.update(&Key::B, &KeyState::Down).unwrap();
+        assert_eq!(recorder.take(), [Emitted::Press(key(keyboard::Key::Space))]);
+        // The first release ends the chord, the second one is ignored.
+        keyboard.update(&Key::A, &KeyState::Up).unwrap();
+        keyboard.update(&Key::B, &KeyState::Up).unwrap();
+        assert_eq!(
+            recorder.take(),
+            [Emitted::Release(key(keyboard::Key::Space))]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn chord_button_alone_runs_after_the_window() {
+        let (mut keyboard, recorder) =
+            keyboard("[[chords]]\nbuttons = [\"A\", \"B\"]\naction = \"Space\"");
+        keyboard.update(&Key::A, &KeyState::Down).unwrap();
+        keyboard.timer_elapsed().await;
+        keyboard.run_timers().unwrap();
+        assert_eq!(recorder.take(), [Emitted::Press(key(keyboard::Key::Enter))]);
+        // Too late to complete the chord.
+        keyboard.update(&Key::B, &KeyState::Down).unwrap();
+        keyboard.timer_elapsed().await;
+        keyboard.run_timers().unwrap();
+        keyboard.update(&Key::A, &KeyState::Up).unwrap();
+        keyboard.update(&Key::B, &KeyState::Up).unwrap();
+        assert_eq!(
+            recorder.take(),
+            [
+                Emitted::Press(key(keyboard::Key::Left)),
+                Emitted::Release(key(keyboard::Key::Enter)),
+                Emitted::Release(key(keyboard::Key::Left)),
+            ]
+        );
+    }
+
+    #[test]
+    fn profile_switch_keeps_the_pressed_key() {
+        let (mut keyboard, recorder) =
+            keyboard("[[profiles]]\nname = \"media\"\nbindings = { A = \"PlayPause\" }");
+        keyboard.update(&Key::A, &KeyState::Down).unwrap();
+        assert_eq!(keyboard.cycle_profile().1.name, "media");
+        // Released with the key it pressed, not the one of the new profile.
+        keyboard.update(&Key::A, &KeyState::Up).unwrap();
+        keyboard.update(&Key::A, &KeyState::Down).unwrap();
+        let play_pause = Input::Key(event::Keyboard::Misc(keyboard::Misc::PlayPause));
+        assert_eq!(
+            recorder.take(),
+            [
+                Emitted::Press(key(keyboard::Key::Enter)),
+                Emitted::Release(key(keyboard::Key::Enter)),
+                Emitted::Press(play_pause),
+            ]
+        );
+        assert_eq!(keyboard.cycle_profile().1.name, "default");
+        assert!(keyboard.select_profile("media").is_some());
+        assert!(keyboard.select_profile("games").is_none());
+    }
+
+    #[test]
+    fn release_all_leaves_no_key_pressed() {
+        let (mut keyboard, recorder) = keyboard("");
+        keyboard.update(&Key::Up, &KeyState::Down).unwrap();
+        keyboard.update(&Key::Right, &KeyState::Down).unwrap();
+        keyboard
+            .set_pressed(event::Keyboard::Key(keyboard::Key::LeftShift), true)
+            .unwrap();
+        assert_eq!(recorder.held().len(), 3);
+        keyboard.release_all().unwrap();
+        assert_eq!(recorder.held(), []);
+    }
+
+    #[test]
+    fn muted_drops_presses_but_not_releases() {
+        let (mut keyboard, recorder) = keyboard("");
+        keyboard.update(&Key::A, &KeyState::Down).unwrap();
+        keyboard.set_muted(true);
+        assert_eq!(
+            keyboard.update(&Key::B, &KeyState::Down).unwrap(),
+            Some(IgnoreReason::Muted)
+        );
+        keyboard.update(&Key::A, &KeyState::Up).unwrap();
+        assert_eq!(recorder.held(), []);
+    }
+
+    #[test]
+    fn group_runs_its_actions_in_order() {
+        let (mut keyboard, recorder) = keyboard(
+            r#"
+            [bindings]
+            A = { group = ["LeftShift", "X", { profile = "missing" }, { rumble_ms = 200 }, { profile = "media" }] }
+
+            [[profiles]]
+            name = "media"
+            "#,
+        );
+        keyboard.update(&Key::A, &KeyState::Down).unwrap();
+        assert_eq!(
+            recorder.take(),
+            [
+                Emitted::Press(key(keyboard::Key::LeftShift)),
+                Emitted::Press(key(keyboard::Key::X)),
+            ]
+        );
+        assert_eq!(
+            keyboard.take_remote_actions(),
+            [
+                RemoteAction::Rumble(Duration::from_millis(200)),
+                RemoteAction::Profile(1),
+            ]
+        );
+        keyboard.update(&Key::A, &KeyState::Up).unwrap();
+        assert_eq!(
+            recorder.take(),
+            [
+                Emitted::Release(key(keyboard::Key::X)),
+                Emitted::Release(key(keyboard::Key::LeftShift)),
+            ]
+        );
+    }
+
+    #[test]
+    fn aborted_group_releases_its_keys() {
+        let (mut keyboard, recorder) = keyboard(
+            r#"
+            [bindings]
+            A = { group = ["X", { profile = "missing" }, { rumble_ms = 200 }], on_error = "abort" }
+            "#,
+        );
+        keyboard.update(&Key::A, &KeyState::Down).unwrap();
+        assert_eq!(
+            recorder.take(),
+            [
+                Emitted::Press(key(keyboard::Key::X)),
+                Emitted::Release(key(keyboard::Key::X)),
+            ]
+        );
+        assert!(keyboard.take_remote_actions().is_empty());
+        keyboard.update(&Key::A, &KeyState::Up).unwrap();
+        assert_eq!(recorder.take(), []);
+    }
+}
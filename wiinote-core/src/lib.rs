@@ -0,0 +1,115 @@
+//! Wii Remote device handling for the `wiinote` CLI, usable by other
+//! programs to turn Wii Remotes into virtual input devices.
+//!
+//! The main pieces are:
+//!
+//! - [`monitor`]: finds the Wii Remotes bound by the `hid-wiimote`
+//!   driver, either once or as they connect; [`pairing`] pairs new ones.
+//! - [`keyboard`]: a virtual keyboard (and mouse) emitting the keys of
+//!   the button [`mapping`]s, loaded from a [`config`] file.
+//! - [`display`] and [`feedback`]: the lights and rumble of a remote,
+//!   and [`speaker`] its sounds.
+//! - The output sinks without a mapping: [`dolphin`] (a gamepad),
+//!   [`balance`] (the Balance Board), [`output`] (JSON events) and
+//!   [`headtrack`] (the head pose tracked by the IR camera), and the
+//!   [`midi`] messages of the buttons and motion, whose axes can be
+//!   computed by [`expression`]s.
+//! - [`remote`]: the [`Remote`](remote::Remote) abstraction over a
+//!   device, implemented by scripted remotes for testing mappings and
+//!   replaying the [`scenario`]s checking their outputs; [`bridge`]
+//!   forwards the remotes to another machine.
+//! - [`import`]: translates the button assignments of GlovePIE scripts
+//!   and WiinUSoft profiles into configuration sections.
+//! - [`error`]: the errors of connecting to a remote, loading the
+//!   configuration or a script, and creating the virtual devices.
+//!
+//! The devices are not `Send`, their connections must run on a
+//! single-threaded runtime or a [`LocalSet`](tokio::task::LocalSet).
+//!
+//! # Example
+//!
+//! Emitting the mapped keys of the first connected Wii Remote:
+//!
+//! ```no_run
+//! use anyhow::Context;
+//! use futures_util::TryStreamExt;
+//! use wiinote_core::config::Config;
+//! use wiinote_core::keyboard::Keyboard;
+//! use wiinote_core::{mapping, monitor};
+//! use xwiimote::event::EventKind;
+//! use xwiimote::{Address, Channels, Device};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let config = Config::load(None)?;
+//! let mut keyboard = Keyboard::try_new(mapping::profiles(&config), None)?;
+//! let path = monitor::connected_devices()?
+//!     .into_iter()
+//!     .next()
+//!     .context("no connected Wii Remote")?;
+//! let mut device = Device::connect(&Address::from(path))?;
+//! device.open(Channels::CORE, true)?;
+//! let mut events = device.events()?;
+//! loop {
+//!     tokio::select! {
+//!         event = events.try_next() => match event?.map(|event| event.kind) {
+//!             Some(EventKind::Key(key, state)) => {
+//!                 keyboard.update(&key, &state)?;
+//!             }
+//!             Some(_) => {}
+//!             None => break, // disconnected
+//!         },
+//!         // Holds and macros are driven by timers.
+//!         _ = keyboard.timer_elapsed() => keyboard.run_timers()?,
+//!     }
+//! }
+//! keyboard.release_all()
+//! # }
+//! ```
+
+pub mod absolute;
+pub mod adapter;
+pub mod balance;
+pub mod battery;
+pub mod bridge;
+pub mod budget;
+pub mod calibration;
+pub mod clipboard;
+pub mod combinator;
+pub mod command;
+pub mod compositor;
+pub mod config;
+pub mod display;
+pub mod dolphin;
+pub mod error;
+pub mod expression;
+pub mod extension;
+pub mod fall;
+pub mod feedback;
+pub mod fusion;
+pub mod gesture;
+pub mod gyro;
+pub mod headtrack;
+pub mod idle;
+pub mod import;
+pub mod kernel;
+pub mod keyboard;
+pub mod mapping;
+pub mod midi;
+pub mod monitor;
+pub mod mpris;
+pub mod numpad;
+pub mod output;
+pub mod pairing;
+pub mod players;
+pub mod pointer;
+pub mod recording;
+pub mod remote;
+pub mod scenario;
+pub mod screen;
+pub mod smoothing;
+pub mod speaker;
+pub mod stationary;
+pub mod stats;
+pub mod steering;
+pub mod stick;
+pub mod volume;
@@ -0,0 +1,799 @@
+use crate::config::{Click, Config};
+use crate::gesture::Gesture;
+use anyhow::{anyhow, Error, Result};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use uinput::event;
+use uinput::event::controller::Mouse;
+use uinput::event::keyboard::{Key as K, Misc as M};
+use xwiimote::event::Key;
+
+/// The Wii Remote buttons (and gestures) that can be bound to a key.
+///
+/// One and Two are reserved for the light display.
+// Deserialized from a string, TOML table keys don't support enums.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Button {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+    Plus,
+    Minus,
+    Home,
+    /// Shaking the remote, which clicks the bound key.
+    Shake,
+    /// The [`Gesture`]s, which click the bound key too.
+    SwingLeft,
+    SwingRight,
+    SwingUp,
+    SwingDown,
+    Flick,
+    TwistLeft,
+    TwistRight,
+    CircleClockwise,
+    CircleCounterclockwise,
+}
+
+impl Button {
+    pub fn from_key(key: &Key) -> Option<Self> {
+        Some(match *key {
+            Key::Up => Self::Up,
+            Key::Down => Self::Down,
+            Key::Left => Self::Left,
+            Key::Right => Self::Right,
+            Key::A => Self::A,
+            Key::B => Self::B,
+            Key::Plus => Self::Plus,
+            Key::Minus => Self::Minus,
+            Key::Home => Self::Home,
+            _ => return None,
+        })
+    }
+}
+
+impl FromStr for Button {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        const BUTTONS: [(&str, Button); 19] = [
+            ("Up", Button::Up),
+            ("Down", Button::Down),
+            ("Left", Button::Left),
+            ("Right", Button::Right),
+            ("A", Button::A),
+            ("B", Button::B),
+            ("Plus", Button::Plus),
+            ("Minus", Button::Minus),
+            ("Home", Button::Home),
+            ("Shake", Button::Shake),
+            ("SwingLeft", Button::SwingLeft),
+            ("SwingRight", Button::SwingRight),
+            ("SwingUp", Button::SwingUp),
+            ("SwingDown", Button::SwingDown),
+            ("Flick", Button::Flick),
+            ("TwistLeft", Button::TwistLeft),
+            ("TwistRight", Button::TwistRight),
+            ("CircleClockwise", Button::CircleClockwise),
+            ("CircleCounterclockwise", Button::CircleCounterclockwise),
+        ];
+        BUTTONS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(_, button)| *button)
+            .ok_or_else(|| anyhow!("unknown button `{}`", s))
+    }
+}
+
+impl From<Gesture> for Button {
+    fn from(gesture: Gesture) -> Self {
+        match gesture {
+            Gesture::SwingLeft => Self::SwingLeft,
+            Gesture::SwingRight => Self::SwingRight,
+            Gesture::SwingUp => Self::SwingUp,
+            Gesture::SwingDown => Self::SwingDown,
+            Gesture::Flick => Self::Flick,
+            Gesture::TwistLeft => Self::TwistLeft,
+            Gesture::TwistRight => Self::TwistRight,
+            Gesture::CircleClockwise => Self::CircleClockwise,
+            Gesture::CircleCounterclockwise => Self::CircleCounterclockwise,
+        }
+    }
+}
+
+impl TryFrom<String> for Button {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+/// A keyboard key, deserialized from its name (e.g. `"Enter"`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeyCode(pub event::Keyboard);
+
+impl FromStr for KeyCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        KEYS.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(_, key)| Self(*key))
+            .ok_or_else(|| anyhow!("unknown key `{}`", s))
+    }
+}
+
+impl TryFrom<String> for KeyCode {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+/// A mouse button or a notch of the scroll wheel, deserialized from
+/// its name (e.g. `"MouseLeft"` or `"ScrollUp"`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MouseAction {
+    Button(Mouse),
+    /// Scrolls by the given number of notches, positive upwards.
+    Scroll(i32),
+}
+
+impl FromStr for MouseAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        const ACTIONS: [(&str, MouseAction); 5] = [
+            ("MouseLeft", MouseAction::Button(Mouse::Left)),
+            ("MouseRight", MouseAction::Button(Mouse::Right)),
+            ("MouseMiddle", MouseAction::Button(Mouse::Middle)),
+            ("ScrollUp", MouseAction::Scroll(1)),
+            ("ScrollDown", MouseAction::Scroll(-1)),
+        ];
+        ACTIONS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(_, action)| *action)
+            .ok_or_else(|| anyhow!("unknown mouse action `{}`", s))
+    }
+}
+
+/// What a bound button does, deserialized from a key name or a table.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Emits the key while the button is held.
+    Key(KeyCode),
+    /// Holds the mouse button while the button is held, or scrolls
+    /// once when it is pressed.
+    Mouse(MouseAction),
+    /// Places the text on the clipboard when the button is pressed.
+    Paste(PasteAction),
+    /// Emits a sequence of key combinations when the button is pressed.
+    Macro(Vec<MacroStep>),
+    /// Runs a shell command in the background when the button is pressed.
+    Command(CommandAction),
+    /// Tiles the active window when the button is pressed.
+    Snap(SnapAction),
+    /// Controls the active MPRIS media player when the button is pressed.
+    Media(MediaAction),
+    /// Changes the volume of an audio output when the button is pressed.
+    Volume(VolumeAction),
+    /// Disconnects the Wii Remote, turning it off, when the button is
+    /// pressed; named `PowerOff`.
+    PowerOff,
+    /// Plays a sound file on the speaker of the Wii Remote when the
+    /// button is pressed.
+    PlaySound(PlaySoundAction),
+    /// Moves the pointer with the motion of the Wii Remote while the
+    /// button is held, e.g. to highlight parts of a slide; named
+    /// `Pointer`.
+    Pointer,
+    /// Pulses the rumble motor (if enabled, see `--rumble`) when the
+    /// button is pressed.
+    Rumble(RumbleAction),
+    /// Switches to the named profile when the button is pressed.
+    Profile(ProfileAction),
+    /// Opens a sync window accepting new Wii Remotes for 30 seconds
+    /// when the button is pressed, like `wiinote ctl sync`; named `Sync`.
+    Sync,
+    /// Runs the actions in order when the button is pressed, e.g. a key
+    /// and a command. The keys and buttons they hold are released in
+    /// reverse order.
+    Group(GroupAction),
+}
+
+impl Action {
+    /// Returns whether the action is the given one, or a group
+    /// including it.
+    pub fn includes(&self, action: &Action) -> bool {
+        match self {
+            Action::Group(group) => group.group.iter().any(|member| member.includes(action)),
+            _ => self == action,
+        }
+    }
+}
+
+/// The name of the [`Action::PowerOff`] action.
+const POWER_OFF: &str = "PowerOff";
+/// The name of the [`Action::Pointer`] action.
+const POINTER: &str = "Pointer";
+/// The name of the [`Action::Sync`] action.
+const SYNC: &str = "Sync";
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PasteAction {
+    /// The text placed on the clipboard.
+    pub paste: String,
+    /// Whether to emit Ctrl+V after copying the text.
+    #[serde(default)]
+    pub ctrl_v: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandAction {
+    /// The command line, run with `sh -c`.
+    pub command: String,
+    /// The minimum time between two runs of the command, so repeated
+    /// presses don't pile up processes.
+    #[serde(default = "default_min_interval")]
+    pub min_interval_ms: u64,
+}
+
+fn default_min_interval() -> u64 {
+    500
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnapAction {
+    pub snap: Snap,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaySoundAction {
+    /// The WAV file, see [`Sound::load`](crate::speaker::Sound::load).
+    pub play_sound: PathBuf,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RumbleAction {
+    pub rumble_ms: u64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileAction {
+    /// The name of the profile.
+    pub profile: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupAction {
+    pub group: Vec<Action>,
+    /// What to do when an action fails.
+    #[serde(default)]
+    pub on_error: OnError,
+}
+
+/// What a [group](GroupAction) does when one of its actions fails, e.g.
+/// a command that cannot be spawned or an unknown profile.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnError {
+    /// Logs the failure and runs the next actions.
+    #[default]
+    Continue,
+    /// Skips the next actions and releases the keys and buttons held
+    /// by the previous ones.
+    Abort,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MediaAction {
+    pub media: Media,
+    /// The offset of `seek`, in milliseconds, negative to rewind.
+    #[serde(default = "default_seek_offset")]
+    pub offset_ms: i64,
+}
+
+fn default_seek_offset() -> i64 {
+    10_000
+}
+
+/// The MPRIS method called on the media player, see
+/// [`mpris::control`](crate::mpris::control).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Media {
+    PlayPause,
+    Next,
+    Previous,
+    Seek,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeAction {
+    pub volume: Volume,
+    /// The volume change of `up` and `down`, in percent.
+    #[serde(default = "default_volume_step")]
+    pub step: u8,
+    /// The name of the sink (e.g. from `pactl list short sinks`), the
+    /// default one if not set.
+    pub sink: Option<String>,
+}
+
+fn default_volume_step() -> u8 {
+    5
+}
+
+/// The volume change, see [`volume::change`](crate::volume::change).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Volume {
+    Up,
+    Down,
+    /// Mutes or unmutes the sink.
+    Mute,
+}
+
+/// Where the active window is tiled.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Snap {
+    Left,
+    Right,
+    Top,
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Maximize,
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Key(String),
+            Paste(PasteAction),
+            Macro(RawMacro),
+            Command(CommandAction),
+            Snap(SnapAction),
+            Media(MediaAction),
+            Volume(VolumeAction),
+            PlaySound(PlaySoundAction),
+            Rumble(RumbleAction),
+            Profile(ProfileAction),
+            Group(GroupAction),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct RawMacro {
+            #[serde(rename = "macro")]
+            steps: Vec<RawStep>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged, deny_unknown_fields)]
+        enum RawStep {
+            Combo(String),
+            Text { text: String },
+            Delay { delay_ms: u64 },
+        }
+
+        // Parse the key names here to keep their error message, untagged
+        // enums only report that no variant matched.
+        match Raw::deserialize(deserializer)? {
+            Raw::Key(name) if name.eq_ignore_ascii_case(POWER_OFF) => Ok(Self::PowerOff),
+            Raw::Key(name) if name.eq_ignore_ascii_case(POINTER) => Ok(Self::Pointer),
+            Raw::Key(name) if name.eq_ignore_ascii_case(SYNC) => Ok(Self::Sync),
+            Raw::Key(name) => match name.parse() {
+                Ok(mouse) => Ok(Self::Mouse(mouse)),
+                Err(_) => name.parse().map(Self::Key).map_err(D::Error::custom),
+            },
+            Raw::Paste(paste) => Ok(Self::Paste(paste)),
+            Raw::Command(command) => Ok(Self::Command(command)),
+            Raw::Snap(snap) => Ok(Self::Snap(snap)),
+            Raw::Media(media) => Ok(Self::Media(media)),
+            Raw::Volume(volume) => Ok(Self::Volume(volume)),
+            Raw::PlaySound(sound) => Ok(Self::PlaySound(sound)),
+            Raw::Rumble(rumble) => Ok(Self::Rumble(rumble)),
+            Raw::Profile(profile) => Ok(Self::Profile(profile)),
+            Raw::Group(group) => Ok(Self::Group(group)),
+            Raw::Macro(raw) => {
+                let mut steps = Vec::new();
+                for step in raw.steps {
+                    match step {
+                        RawStep::Combo(combo) => {
+                            steps.push(MacroStep::parse_combo(&combo).map_err(D::Error::custom)?)
+                        }
+                        RawStep::Text { text } => {
+                            for c in text.chars() {
+                                steps.push(MacroStep::from_char(c).map_err(D::Error::custom)?);
+                            }
+                        }
+                        RawStep::Delay { delay_ms } => {
+                            steps.push(MacroStep::Delay(Duration::from_millis(delay_ms)))
+                        }
+                    }
+                }
+                Ok(Self::Macro(steps))
+            }
+        }
+    }
+}
+
+/// A step of a macro.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MacroStep {
+    /// Keys pressed in order, then released in reverse order.
+    Combo(Vec<event::Keyboard>),
+    /// A pause before the next step.
+    Delay(Duration),
+}
+
+impl MacroStep {
+    /// Parses key names joined by `+`, e.g. `LeftControl+LeftAlt+T`.
+    fn parse_combo(s: &str) -> Result<Self> {
+        let keys = s
+            .split('+')
+            .map(|name| name.trim().parse().map(|KeyCode(key)| key))
+            .collect::<Result<_>>()?;
+        Ok(Self::Combo(keys))
+    }
+
+    /// Returns the combination typing the character on a US layout.
+    fn from_char(c: char) -> Result<Self> {
+        const SHIFTED: &[(char, char)] = &[
+            ('!', '1'),
+            ('@', '2'),
+            ('#', '3'),
+            ('$', '4'),
+            ('%', '5'),
+            ('^', '6'),
+            ('&', '7'),
+            ('*', '8'),
+            ('(', '9'),
+            (')', '0'),
+            ('_', '-'),
+            ('+', '='),
+            ('<', ','),
+            ('>', '.'),
+            ('?', '/'),
+        ];
+        let key = |name: &str| name.parse().map(|KeyCode(key)| key);
+        let unshifted = |c: char| -> Result<event::Keyboard> {
+            match c {
+                '\n' => key("Enter"),
+                '\t' => key("Tab"),
+                ' ' => key("Space"),
+                '-' => key("Minus"),
+                '=' => key("Equal"),
+                ',' => key("Comma"),
+                '.' => key("Dot"),
+                '/' => key("Slash"),
+                c if c.is_ascii_alphanumeric() => key(&c.to_string()),
+                _ => Err(anyhow!("cannot type `{}`", c.escape_default())),
+            }
+        };
+
+        let shifted = SHIFTED
+            .iter()
+            .find(|(shifted, _)| *shifted == c)
+            .map(|(_, base)| *base);
+        if c.is_ascii_uppercase() || shifted.is_some() {
+            let base = unshifted(shifted.unwrap_or(c))?;
+            Ok(Self::Combo(vec![key("LeftShift")?, base]))
+        } else {
+            Ok(Self::Combo(vec![unshifted(c)?]))
+        }
+    }
+}
+
+/// An action run when several buttons are pressed together.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "RawChord")]
+pub struct Chord {
+    pub buttons: Vec<Button>,
+    pub action: Action,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawChord {
+    buttons: Vec<Button>,
+    action: Action,
+}
+
+impl TryFrom<RawChord> for Chord {
+    type Error = Error;
+
+    fn try_from(raw: RawChord) -> Result<Self> {
+        if raw.buttons.len() < 2 {
+            return Err(anyhow!("a chord needs at least two buttons"));
+        }
+        Ok(Self {
+            buttons: raw.buttons,
+            action: raw.action,
+        })
+    }
+}
+
+/// An action run when the players press their buttons together, each
+/// on their own Wii Remote, e.g. A on both remotes to start a game.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "RawCrossChord")]
+pub struct CrossChord {
+    /// The button of each player, from player 1.
+    pub buttons: Vec<Button>,
+    pub action: Action,
+    /// The longest time between the first and the last press.
+    pub window: Duration,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCrossChord {
+    buttons: Vec<Button>,
+    action: Action,
+    #[serde(default = "default_cross_window")]
+    window_ms: u64,
+}
+
+fn default_cross_window() -> u64 {
+    200
+}
+
+impl TryFrom<RawCrossChord> for CrossChord {
+    type Error = Error;
+
+    fn try_from(raw: RawCrossChord) -> Result<Self> {
+        if raw.buttons.len() < 2 {
+            return Err(anyhow!("a cross-device chord needs at least two players"));
+        }
+        Ok(Self {
+            buttons: raw.buttons,
+            action: raw.action,
+            window: Duration::from_millis(raw.window_ms),
+        })
+    }
+}
+
+/// The keys that can be emitted, by name.
+const KEYS: &[(&str, event::Keyboard)] = &[
+    ("Esc", event::Keyboard::Key(K::Esc)),
+    ("Enter", event::Keyboard::Key(K::Enter)),
+    ("Space", event::Keyboard::Key(K::Space)),
+    ("Tab", event::Keyboard::Key(K::Tab)),
+    ("BackSpace", event::Keyboard::Key(K::BackSpace)),
+    ("Up", event::Keyboard::Key(K::Up)),
+    ("Down", event::Keyboard::Key(K::Down)),
+    ("Left", event::Keyboard::Key(K::Left)),
+    ("Right", event::Keyboard::Key(K::Right)),
+    ("Home", event::Keyboard::Key(K::Home)),
+    ("End", event::Keyboard::Key(K::End)),
+    ("PageUp", event::Keyboard::Key(K::PageUp)),
+    ("PageDown", event::Keyboard::Key(K::PageDown)),
+    ("Insert", event::Keyboard::Key(K::Insert)),
+    ("Delete", event::Keyboard::Key(K::Delete)),
+    ("LeftControl", event::Keyboard::Key(K::LeftControl)),
+    ("LeftShift", event::Keyboard::Key(K::LeftShift)),
+    ("LeftAlt", event::Keyboard::Key(K::LeftAlt)),
+    ("LeftMeta", event::Keyboard::Key(K::LeftMeta)),
+    ("RightControl", event::Keyboard::Key(K::RightControl)),
+    ("RightShift", event::Keyboard::Key(K::RightShift)),
+    ("RightAlt", event::Keyboard::Key(K::RightAlt)),
+    ("Minus", event::Keyboard::Key(K::Minus)),
+    ("Equal", event::Keyboard::Key(K::Equal)),
+    ("Comma", event::Keyboard::Key(K::Comma)),
+    ("Dot", event::Keyboard::Key(K::Dot)),
+    ("Slash", event::Keyboard::Key(K::Slash)),
+    ("0", event::Keyboard::Key(K::_0)),
+    ("1", event::Keyboard::Key(K::_1)),
+    ("2", event::Keyboard::Key(K::_2)),
+    ("3", event::Keyboard::Key(K::_3)),
+    ("4", event::Keyboard::Key(K::_4)),
+    ("5", event::Keyboard::Key(K::_5)),
+    ("6", event::Keyboard::Key(K::_6)),
+    ("7", event::Keyboard::Key(K::_7)),
+    ("8", event::Keyboard::Key(K::_8)),
+    ("9", event::Keyboard::Key(K::_9)),
+    ("A", event::Keyboard::Key(K::A)),
+    ("B", event::Keyboard::Key(K::B)),
+    ("C", event::Keyboard::Key(K::C)),
+    ("D", event::Keyboard::Key(K::D)),
+    ("E", event::Keyboard::Key(K::E)),
+    ("F", event::Keyboard::Key(K::F)),
+    ("G", event::Keyboard::Key(K::G)),
+    ("H", event::Keyboard::Key(K::H)),
+    ("I", event::Keyboard::Key(K::I)),
+    ("J", event::Keyboard::Key(K::J)),
+    ("K", event::Keyboard::Key(K::K)),
+    ("L", event::Keyboard::Key(K::L)),
+    ("M", event::Keyboard::Key(K::M)),
+    ("N", event::Keyboard::Key(K::N)),
+    ("O", event::Keyboard::Key(K::O)),
+    ("P", event::Keyboard::Key(K::P)),
+    ("Q", event::Keyboard::Key(K::Q)),
+    ("R", event::Keyboard::Key(K::R)),
+    ("S", event::Keyboard::Key(K::S)),
+    ("T", event::Keyboard::Key(K::T)),
+    ("U", event::Keyboard::Key(K::U)),
+    ("V", event::Keyboard::Key(K::V)),
+    ("W", event::Keyboard::Key(K::W)),
+    ("X", event::Keyboard::Key(K::X)),
+    ("Y", event::Keyboard::Key(K::Y)),
+    ("Z", event::Keyboard::Key(K::Z)),
+    ("F1", event::Keyboard::Key(K::F1)),
+    ("F2", event::Keyboard::Key(K::F2)),
+    ("F3", event::Keyboard::Key(K::F3)),
+    ("F4", event::Keyboard::Key(K::F4)),
+    ("F5", event::Keyboard::Key(K::F5)),
+    ("F6", event::Keyboard::Key(K::F6)),
+    ("F7", event::Keyboard::Key(K::F7)),
+    ("F8", event::Keyboard::Key(K::F8)),
+    ("F9", event::Keyboard::Key(K::F9)),
+    ("F10", event::Keyboard::Key(K::F10)),
+    ("F11", event::Keyboard::Key(K::F11)),
+    ("F12", event::Keyboard::Key(K::F12)),
+    ("Mute", event::Keyboard::Misc(M::Mute)),
+    ("VolumeUp", event::Keyboard::Misc(M::VolumeUp)),
+    ("VolumeDown", event::Keyboard::Misc(M::VolumeDown)),
+    ("PlayPause", event::Keyboard::Misc(M::PlayPause)),
+    ("PlayCD", event::Keyboard::Misc(M::PlayCD)),
+    ("PauseCD", event::Keyboard::Misc(M::PauseCD)),
+    ("StopCD", event::Keyboard::Misc(M::StopCD)),
+    ("NextSong", event::Keyboard::Misc(M::NextSong)),
+    ("PreviousSong", event::Keyboard::Misc(M::PreviousSong)),
+];
+
+/// Returns every key that can be bound.
+pub fn all_keys() -> impl Iterator<Item = event::Keyboard> {
+    KEYS.iter().map(|(_, key)| *key)
+}
+
+/// Returns the name of the key in the configuration, if it can be bound.
+pub fn key_name(key: event::Keyboard) -> Option<&'static str> {
+    KEYS.iter()
+        .find(|(_, bound)| *bound == key)
+        .map(|(name, _)| *name)
+}
+
+/// The actions bound to the buttons of a Wii Remote.
+///
+/// A button with a hold action runs its regular action only when
+/// tapped, i.e. released before the hold time. Likewise, the buttons
+/// of a chord run their action only if the rest of the chord isn't
+/// pressed shortly after.
+#[derive(Clone)]
+pub struct Mapping {
+    bindings: HashMap<Button, Action>,
+    holds: HashMap<Button, Action>,
+    chords: Vec<Chord>,
+}
+
+impl Default for Mapping {
+    fn default() -> Self {
+        let bindings = [
+            (Button::Up, event::Keyboard::Key(K::Up)),
+            (Button::Down, event::Keyboard::Key(K::Down)),
+            (Button::Left, event::Keyboard::Key(K::Left)),
+            (Button::Right, event::Keyboard::Key(K::Right)),
+            (Button::A, event::Keyboard::Key(K::Enter)),
+            (Button::B, event::Keyboard::Key(K::Left)),
+            (Button::Plus, event::Keyboard::Misc(M::VolumeUp)),
+            (Button::Home, event::Keyboard::Key(K::Esc)),
+            (Button::Minus, event::Keyboard::Misc(M::VolumeDown)),
+        ];
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(button, key)| (button, Action::Key(KeyCode(key))))
+                .collect(),
+            // Like the Wii, holding Home turns the remote off.
+            holds: HashMap::from([(Button::Home, Action::PowerOff)]),
+            chords: Vec::new(),
+        }
+    }
+}
+
+impl Mapping {
+    /// Creates the default mapping, overridden by the configured bindings.
+    pub fn from_config(config: &Config) -> Self {
+        let mut mapping = Self::default().with_bindings(&config.bindings, &config.holds);
+        mapping.chords = config.chords.clone();
+        mapping
+    }
+
+    fn with_bindings(
+        mut self,
+        bindings: &HashMap<Button, Action>,
+        holds: &HashMap<Button, Action>,
+    ) -> Self {
+        for (button, action) in bindings {
+            self.bindings.insert(*button, action.clone());
+        }
+        for (button, action) in holds {
+            self.holds.insert(*button, action.clone());
+        }
+        self
+    }
+
+    /// Returns the action bound to the button, if any.
+    pub fn binding(&self, button: Button) -> Option<&Action> {
+        self.bindings.get(&button)
+    }
+
+    /// Returns the action run when the button is held, if any.
+    pub fn hold(&self, button: Button) -> Option<&Action> {
+        self.holds.get(&button)
+    }
+
+    /// Returns whether the button is part of a chord.
+    pub fn in_chord(&self, button: Button) -> bool {
+        self.chords
+            .iter()
+            .any(|chord| chord.buttons.contains(&button))
+    }
+
+    /// Returns the chord whose buttons are all pressed, if any.
+    pub fn chord(&self, pressed: impl Fn(Button) -> bool) -> Option<&Chord> {
+        self.chords
+            .iter()
+            .find(|chord| chord.buttons.iter().all(|button| pressed(*button)))
+    }
+}
+
+/// A named mapping.
+pub struct Profile {
+    pub name: String,
+    pub mapping: Mapping,
+    pub click: Click,
+    /// The extension activating the profile, if any.
+    pub extension: Option<String>,
+}
+
+/// Creates the configured profiles.
+///
+/// The first profile, named `default`, uses the top-level bindings.
+/// The configured profiles follow, in order.
+pub fn profiles(config: &Config) -> Vec<Profile> {
+    let base = Mapping::from_config(config);
+    let mut profiles = vec![Profile {
+        name: "default".to_owned(),
+        mapping: base.clone(),
+        click: config.click,
+        extension: None,
+    }];
+    for profile in &config.profiles {
+        profiles.push(Profile {
+            name: profile.name.clone(),
+            mapping: base
+                .clone()
+                .with_bindings(&profile.bindings, &profile.holds),
+            click: profile.click.unwrap_or(config.click),
+            extension: profile.extension.clone(),
+        });
+    }
+    profiles
+}
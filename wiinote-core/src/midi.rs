@@ -0,0 +1,144 @@
+use crate::calibration::{self, IR_HEIGHT, IR_WIDTH};
+use crate::config::{MidiAxis, MidiAxisControl, MidiConfig, MidiControl};
+use crate::mapping::Button;
+use std::collections::HashMap;
+use xwiimote::event::{EventKind, KeyState};
+
+/// The largest value of a note, velocity or controller.
+const MAX_VALUE: u8 = 127;
+
+/// A MIDI message, on the channel of the [`MidiMapper`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MidiMessage {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8 },
+    Control { controller: u8, value: u8 },
+}
+
+/// Translates the buttons and motion of a Wii Remote into MIDI
+/// messages, as configured by a [`MidiConfig`].
+///
+/// The buttons send notes or controllers, and the accelerometer tilt
+/// and the IR pointer send continuous controllers, possibly computed
+/// from several axes. A controller is only sent when its value changes,
+/// not to flood the receivers with the motion events.
+pub struct MidiMapper {
+    /// The zero-based MIDI channel.
+    channel: u8,
+    velocity: u8,
+    buttons: HashMap<Button, MidiControl>,
+    axes: HashMap<MidiAxis, MidiAxisControl>,
+    /// The last fraction of each axis, once known.
+    fractions: HashMap<MidiAxis, f64>,
+    /// The last value sent of each controller.
+    sent: HashMap<u8, u8>,
+}
+
+impl MidiMapper {
+    pub fn new(config: &MidiConfig) -> Self {
+        Self {
+            channel: config.channel.clamp(1, 16) - 1,
+            velocity: config.velocity.clamp(1, MAX_VALUE),
+            buttons: config.buttons.clone(),
+            axes: config.axes.clone(),
+            fractions: HashMap::new(),
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Returns the zero-based MIDI channel of the messages.
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    /// Returns the messages of the event, if any.
+    pub fn update(&mut self, event: &EventKind) -> Vec<MidiMessage> {
+        match event {
+            EventKind::Key(key, state) => {
+                let control = Button::from_key(key).and_then(|button| self.buttons.get(&button));
+                match (control, state) {
+                    (Some(MidiControl::Note(note)), KeyState::Down) => vec![MidiMessage::NoteOn {
+                        note: (*note).min(MAX_VALUE),
+                        velocity: self.velocity,
+                    }],
+                    (Some(MidiControl::Note(note)), KeyState::Up) => vec![MidiMessage::NoteOff {
+                        note: (*note).min(MAX_VALUE),
+                    }],
+                    (Some(MidiControl::Cc(controller)), KeyState::Down | KeyState::Up) => {
+                        let value = if *state == KeyState::Down {
+                            MAX_VALUE
+                        } else {
+                            0
+                        };
+                        self.control(*controller, value).into_iter().collect()
+                    }
+                    // The note is held, not repeated.
+                    _ => Vec::new(),
+                }
+            }
+            EventKind::Accelerometer { x, y, z } => {
+                let (x, y, z) = (*x as f64, *y as f64, *z as f64);
+                // Gravity moves to the Y axis as the remote points up,
+                // and from the Z to the X axis as it rolls.
+                let pitch = y.atan2(x.hypot(z)).to_degrees();
+                let roll = x.atan2(z).to_degrees().clamp(-90.0, 90.0);
+                self.axes([
+                    (MidiAxis::Pitch, (pitch + 90.0) / 180.0),
+                    (MidiAxis::Roll, (roll + 90.0) / 180.0),
+                ])
+            }
+            EventKind::Ir(sources) => {
+                let sources = sources.iter().flatten().map(|s| (s.x, s.y));
+                match calibration::ir_position(sources) {
+                    // The camera sees the sources move in the opposite
+                    // direction horizontally.
+                    Some((x, y)) => self.axes([
+                        (MidiAxis::PointerX, 1.0 - x / IR_WIDTH),
+                        (MidiAxis::PointerY, y / IR_HEIGHT),
+                    ]),
+                    // Keep the last position while out of sight.
+                    None => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Updates the fractions (from 0 to 1) of the full range of the
+    /// axes, and returns the messages setting the controllers computed
+    /// from them.
+    fn axes<const N: usize>(&mut self, values: [(MidiAxis, f64); N]) -> Vec<MidiMessage> {
+        self.fractions.extend(values);
+        let fractions = &self.fractions;
+        let controls: Vec<_> = MidiAxis::ALL
+            .into_iter()
+            .filter_map(|axis| {
+                let control = self.axes.get(&axis)?;
+                // Not sent until all the axes of its value are known.
+                let fraction = match &control.value {
+                    Some(value) => value.evaluate(|name| {
+                        let axis = MidiAxis::ALL.into_iter().find(|a| a.variable() == name)?;
+                        fractions.get(&axis).copied()
+                    })?,
+                    None => *fractions.get(&axis)?,
+                };
+                let value = (fraction.clamp(0.0, 1.0) * MAX_VALUE as f64).round() as u8;
+                Some((control.cc, value))
+            })
+            .collect();
+        controls
+            .into_iter()
+            .filter_map(|(controller, value)| self.control(controller, value))
+            .collect()
+    }
+
+    /// Returns the message setting the controller, unless it already
+    /// has the value.
+    fn control(&mut self, controller: u8, value: u8) -> Option<MidiMessage> {
+        let controller = controller.min(MAX_VALUE);
+        if self.sent.insert(controller, value) == Some(value) {
+            return None;
+        }
+        Some(MidiMessage::Control { controller, value })
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{anyhow, bail, Error, Result};
+use futures_util::stream::TryStreamExt;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use xwiimote::{Address, Monitor};
+
+/// The directory listing the HID devices bound to the `hid-wiimote` driver.
+const DRIVER_PATH: &str = "/sys/bus/hid/drivers/wiimote";
+
+/// The mechanism used to find Wii Remotes.
+#[derive(Copy, Clone, Debug)]
+pub enum MonitorKind {
+    /// Use the udev-based xwiimote monitor.
+    Udev,
+    /// Scan the sysfs entries of the `hid-wiimote` driver at the
+    /// given interval, for systems without udev.
+    Poll(Duration),
+}
+
+impl FromStr for MonitorKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s == "udev" => Ok(Self::Udev),
+            Some(("poll", interval)) => Ok(Self::Poll(parse_duration(interval)?)),
+            _ => bail!("expected `udev` or `poll:<interval>`, got `{}`", s),
+        }
+    }
+}
+
+/// Parses a duration such as `500ms` or `2s`. Plain numbers are
+/// interpreted as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let invalid = || anyhow!("invalid interval `{}`", s);
+    let duration = if let Some(millis) = s.strip_suffix("ms") {
+        Duration::from_millis(millis.parse().map_err(|_| invalid())?)
+    } else {
+        let secs = s.strip_suffix('s').unwrap_or(s);
+        Duration::from_secs_f64(secs.parse().map_err(|_| invalid())?)
+    };
+    if duration.is_zero() {
+        bail!("the polling interval must be positive");
+    }
+    Ok(duration)
+}
+
+/// Finds the Wii Remotes to connect to, as they are plugged in.
+pub enum DeviceWatcher {
+    Udev(Monitor),
+    Poll {
+        interval: Duration,
+        discover: bool,
+        /// The devices found by the previous scans, which are still present.
+        seen: HashSet<PathBuf>,
+        /// The devices found by the last scan, not yet returned.
+        pending: Vec<PathBuf>,
+        scanned: bool,
+    },
+}
+
+impl DeviceWatcher {
+    /// Creates a watcher returning the plugged-in Wii Remotes and, if
+    /// `discover` is set, the ones that are plugged in later.
+    pub fn new(kind: MonitorKind, discover: bool) -> Result<Self> {
+        Ok(match kind {
+            MonitorKind::Udev => Self::Udev(Monitor::new(discover)?),
+            MonitorKind::Poll(interval) => Self::Poll {
+                interval,
+                discover,
+                seen: HashSet::new(),
+                pending: Vec::new(),
+                scanned: false,
+            },
+        })
+    }
+
+    /// Returns the address of the next Wii Remote found.
+    ///
+    /// If `discover` is not set, `None` is returned once all the
+    /// plugged-in devices have been returned.
+    pub async fn next(&mut self) -> Result<Option<Address>> {
+        match self {
+            Self::Udev(monitor) => monitor.try_next().await.map_err(|err| err.into()),
+            Self::Poll {
+                interval,
+                discover,
+                seen,
+                pending,
+                scanned,
+            } => loop {
+                if let Some(path) = pending.pop() {
+                    return Ok(Some(Address::from(path)));
+                }
+                if *scanned {
+                    if !*discover {
+                        return Ok(None);
+                    }
+                    tokio::time::sleep(*interval).await;
+                }
+
+                let paths = scan(Path::new(DRIVER_PATH))?;
+                // Forget the disconnected devices, so they are returned
+                // again when they reconnect.
+                seen.retain(|path| paths.contains(path));
+                for path in paths.into_iter().rev() {
+                    if seen.insert(path.clone()) {
+                        pending.push(path);
+                    }
+                }
+                *scanned = true;
+            },
+        }
+    }
+}
+
+/// Returns the sysfs paths of the Wii Remotes bound to the driver.
+pub fn connected_devices() -> Result<Vec<PathBuf>> {
+    scan(Path::new(DRIVER_PATH))
+}
+
+/// Reads the Bluetooth address of a HID device from its `uevent` file,
+/// e.g. `00:1F:32:AA:BB:CC`.
+pub fn bluetooth_address(path: &Path) -> Option<String> {
+    let uevent = std::fs::read_to_string(path.join("uevent")).ok()?;
+    uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("HID_UNIQ="))
+        .filter(|address| !address.is_empty())
+        .map(str::to_ascii_uppercase)
+}
+
+/// Lists the HID device entries in the driver directory, returning
+/// their canonical sysfs paths.
+fn scan(driver_path: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(driver_path) {
+        Ok(entries) => entries,
+        // The driver directory is only present once the module is loaded.
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        // HID devices are named `BUS:VENDOR:PRODUCT.ID`, the other
+        // entries are driver attributes (`bind`, `uevent`...).
+        if entry.file_name().to_string_lossy().contains(':') {
+            paths.push(entry.path().canonicalize()?);
+        }
+    }
+    // Sort to return the devices in a stable order.
+    paths.sort();
+    Ok(paths)
+}
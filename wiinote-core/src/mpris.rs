@@ -0,0 +1,84 @@
+use crate::mapping::{Media, MediaAction};
+use anyhow::{ensure, Context, Result};
+use std::process::Command;
+
+/// The prefix of the bus names of the MPRIS media players.
+const PLAYER_PREFIX: &str = "org.mpris.MediaPlayer2.";
+/// The object path of the MPRIS interfaces.
+const PLAYER_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// Calls the MPRIS method of the action on the active media player
+/// with `dbus-send`, instead of emitting a media key that the
+/// compositor may not route to it.
+///
+/// The active player is the first one playing, or else the first one
+/// on the session bus.
+pub fn control(action: &MediaAction) -> Result<()> {
+    let players = players()?;
+    let player = players
+        .iter()
+        .find(|player| is_playing(player))
+        .or_else(|| players.first())
+        .context("no MPRIS media player running")?;
+    let method = match action.media {
+        Media::PlayPause => "PlayPause",
+        Media::Next => "Next",
+        Media::Previous => "Previous",
+        Media::Seek => "Seek",
+    };
+    let method = format!("{}.{}", PLAYER_INTERFACE, method);
+    // The offset is in microseconds.
+    let offset = format!("int64:{}", action.offset_ms * 1000);
+    let args = match action.media {
+        Media::Seek => vec![offset.as_str()],
+        _ => vec![],
+    };
+    dbus_send(player, PLAYER_PATH, &method, &args)?;
+    Ok(())
+}
+
+/// Returns the bus names of the running media players.
+fn players() -> Result<Vec<String>> {
+    let reply = dbus_send(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus.ListNames",
+        &[],
+    )?;
+    Ok(reply
+        .split(|c: char| c.is_whitespace() || c == '"')
+        .filter(|name| name.starts_with(PLAYER_PREFIX))
+        .map(str::to_owned)
+        .collect())
+}
+
+fn is_playing(player: &str) -> bool {
+    let interface = format!("string:{}", PLAYER_INTERFACE);
+    let args = [interface.as_str(), "string:PlaybackStatus"];
+    dbus_send(
+        player,
+        PLAYER_PATH,
+        "org.freedesktop.DBus.Properties.Get",
+        &args,
+    )
+    .is_ok_and(|reply| reply.contains("\"Playing\""))
+}
+
+/// Calls a method on the session bus, returning the printed reply.
+fn dbus_send(dest: &str, path: &str, method: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new("dbus-send")
+        .args(["--session", "--print-reply"])
+        .arg(format!("--dest={}", dest))
+        .args([path, method])
+        .args(args)
+        .output()
+        .context("cannot run dbus-send")?;
+    ensure!(
+        output.status.success(),
+        "dbus-send exited with {}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,57 @@
+use uinput::event::keyboard::Key as KeyboardKey;
+use xwiimote::event::Key;
+
+/// The keys of the numpad, laid out as on a phone keypad.
+const GRID: [[KeyboardKey; 3]; 4] = [
+    [KeyboardKey::_1, KeyboardKey::_2, KeyboardKey::_3],
+    [KeyboardKey::_4, KeyboardKey::_5, KeyboardKey::_6],
+    [KeyboardKey::_7, KeyboardKey::_8, KeyboardKey::_9],
+    [KeyboardKey::BackSpace, KeyboardKey::_0, KeyboardKey::Enter],
+];
+
+/// A 3×4 numeric grid navigated with the D-pad, used to enter
+/// PINs and other numbers without a keyboard.
+pub struct Numpad {
+    row: usize,
+    column: usize,
+}
+
+impl Default for Numpad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Numpad {
+    /// Creates a numpad with the center key (5) selected.
+    pub fn new() -> Self {
+        Self { row: 1, column: 1 }
+    }
+
+    /// Moves the selection in the direction of the D-pad key,
+    /// wrapping around the edges.
+    ///
+    /// Returns `false` if the key is not a direction.
+    pub fn navigate(&mut self, key: &Key) -> bool {
+        let (rows, columns) = (GRID.len(), GRID[0].len());
+        match *key {
+            Key::Up => self.row = (self.row + rows - 1) % rows,
+            Key::Down => self.row = (self.row + 1) % rows,
+            Key::Left => self.column = (self.column + columns - 1) % columns,
+            Key::Right => self.column = (self.column + 1) % columns,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Returns the selected key.
+    pub fn selected(&self) -> KeyboardKey {
+        GRID[self.row][self.column]
+    }
+
+    /// Returns the position of the selected key, from 1 to 12
+    /// in row-major order.
+    pub fn position(&self) -> u8 {
+        (1 + self.row * GRID[0].len() + self.column) as u8
+    }
+}
@@ -0,0 +1,207 @@
+use crate::battery;
+use crate::stats::next_event_id;
+use anyhow::{bail, Error, Result};
+use futures_util::stream::TryStreamExt;
+use std::str::FromStr;
+use tokio::sync::{broadcast, watch};
+use tokio::time::Instant;
+use xwiimote::event::{EventKind, Key, KeyState};
+use xwiimote::{Channels, Device};
+
+/// Where the input of the Wii Remotes is published.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Output {
+    /// Virtual devices emitting the mapped keys.
+    Keyboard,
+    /// A JSON object per line on the standard output, for other
+    /// programs to consume; no virtual device is created.
+    Json,
+    /// A virtual gamepad with the unmapped buttons and motion, for
+    /// Dolphin; see [`DolphinDevice`](crate::dolphin::DolphinDevice).
+    Dolphin,
+    /// MIDI notes and controllers from a sequencer port, for DAWs and
+    /// synthesizers; see [`MidiMapper`](crate::midi::MidiMapper).
+    Midi,
+    /// JSON-RPC requests to a Kodi instance, possibly on another
+    /// machine; see [`KodiConfig`](crate::config::KodiConfig).
+    Kodi,
+}
+
+impl FromStr for Output {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "keyboard" => Ok(Self::Keyboard),
+            "json" => Ok(Self::Json),
+            "dolphin" => Ok(Self::Dolphin),
+            "midi" => Ok(Self::Midi),
+            "kodi" => Ok(Self::Kodi),
+            _ => bail!(
+                "expected `keyboard`, `json`, `dolphin`, `midi` or `kodi`, got `{}`",
+                s
+            ),
+        }
+    }
+}
+
+/// The version of the JSON format of the events, increased when a
+/// field changes; sent in the handshake of the control clients.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The events published to the subscribers, listed in the handshake of
+/// the control clients.
+pub const EVENTS: [&str; 5] = [
+    "connected",
+    "button",
+    "accelerometer",
+    "battery",
+    "disconnected",
+];
+
+/// The number of events kept for the subscribers that fall behind,
+/// about a second of motion events.
+const EVENTS_CAPACITY: usize = 128;
+
+/// Publishes the input of a Wii Remote as JSON objects to the
+/// subscribers, e.g. WebSocket clients. The events are those of
+/// [`stream`], plus `accelerometer` (with the event `id`, `x`, `y`
+/// and `z`).
+#[derive(Clone)]
+pub struct Events {
+    /// The channel to the subscribers, unless disabled.
+    sender: Option<broadcast::Sender<String>>,
+    player: usize,
+}
+
+impl Events {
+    /// Creates the channel of the events. If not `enabled`, they are dropped.
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            sender: enabled.then(|| broadcast::channel(EVENTS_CAPACITY).0),
+            player: 0,
+        }
+    }
+
+    /// Returns the channel publishing the events of the given player.
+    pub fn for_player(&self, player: usize) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            player,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Returns the channel to subscribe to, unless disabled.
+    pub fn sender(&self) -> Option<broadcast::Sender<String>> {
+        self.sender.clone()
+    }
+
+    /// Publishes the event with the given object members, formatted
+    /// only if anyone is subscribed.
+    pub fn publish(&self, event: &str, fields: impl FnOnce() -> String) {
+        if let Some(sender) = self
+            .sender
+            .as_ref()
+            .filter(|sender| sender.receiver_count() > 0)
+        {
+            let _ = sender.send(format_event(self.player, event, &fields()));
+        }
+    }
+}
+
+/// Formats an event of the player as a JSON object. The `fields` are
+/// the object members after the player number and event name, each
+/// preceded by a comma.
+pub fn format_event(player: usize, event: &str, fields: &str) -> String {
+    format!(r#"{{"player":{},"event":"{}"{}}}"#, player, event, fields)
+}
+
+/// Returns the members of a `button` event, with the identifier of
+/// the received event (see [`next_event_id`]).
+pub fn button_fields(id: u64, source: &str, key: Key, state: KeyState) -> String {
+    format!(
+        r#","id":{},"source":"{}","button":"{:?}","state":"{}""#,
+        id,
+        source,
+        key,
+        state_name(state)
+    )
+}
+
+/// The channels reporting key presses, of the remote and its extensions.
+fn key_channels() -> Channels {
+    Channels::CORE
+        | Channels::NUNCHUK
+        | Channels::CLASSIC_CONTROLLER
+        | Channels::PRO_CONTROLLER
+        | Channels::DRUMS
+        | Channels::GUITAR
+}
+
+/// Writes the key presses, battery level and extension changes of the
+/// device as JSON lines, until it disconnects or `shutdown` is set.
+///
+/// Every object has the player number and the `event` name: `connected`
+/// (with the device `kind`), `button` (with the event `id`, unique for
+/// the process lifetime, the `source`, `button` and `state`), `battery` (with the `level` percentage), `extension` (with
+/// its `name`, `none` once unplugged) and `disconnected`.
+pub async fn stream(
+    mut device: Device,
+    kind: &str,
+    player: usize,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    let emit = |event: &str, fields: String| println!("{}", format_event(player, event, &fields));
+    emit("connected", format!(r#","kind":"{}""#, kind));
+    let mut level = None;
+    let mut next_poll = Instant::now();
+    'connection: loop {
+        let channels = device.available() & key_channels();
+        device.open(channels - device.opened(), false)?;
+        emit("extension", format!(r#","name":"{}""#, device.extension()?));
+
+        let mut event_stream = device.events()?;
+        loop {
+            let event = tokio::select! {
+                res = event_stream.try_next() => match res? {
+                    Some(event) => event,
+                    None => break 'connection,
+                },
+                _ = tokio::time::sleep_until(next_poll) => {
+                    next_poll = Instant::now() + battery::DEFAULT_INTERVAL;
+                    let current = device.battery()?;
+                    if level.replace(current) != Some(current) {
+                        emit("battery", format!(r#","level":{}"#, current));
+                    }
+                    continue;
+                }
+                _ = shutdown.changed() => break 'connection,
+            };
+            let (source, key, state) = match event.kind {
+                EventKind::Key(key, state) => ("remote", key, state),
+                EventKind::NunchukKey(key, state) => ("nunchuk", key, state),
+                EventKind::ClassicControllerKey(key, state) => ("classic", key, state),
+                EventKind::ProControllerKey(key, state) => ("procontroller", key, state),
+                EventKind::DrumsKey(key, state) => ("drums", key, state),
+                EventKind::GuitarKey(key, state) => ("guitar", key, state),
+                EventKind::Watch => continue 'connection,
+                _ => continue,
+            };
+            emit("button", button_fields(next_event_id(), source, key, state));
+        }
+    }
+    emit("disconnected", String::new());
+    Ok(())
+}
+
+fn state_name(state: KeyState) -> &'static str {
+    match state {
+        KeyState::Up => "up",
+        KeyState::Down => "down",
+        KeyState::AutoRepeat => "repeat",
+    }
+}
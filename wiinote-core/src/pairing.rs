@@ -0,0 +1,425 @@
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use dbus::blocking::stdintf::org_freedesktop_dbus::ObjectManager;
+use dbus::blocking::Connection;
+use dbus::channel::Channel;
+use dbus::message::MessageType;
+use dbus::{Message, Path};
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// The prefix of the Bluetooth names of the Wii Remotes and Balance
+/// Boards, e.g. `Nintendo RVL-CNT-01-TR`.
+const NAME_PREFIX: &str = "Nintendo RVL-";
+/// How long to scan for devices in pairing mode, by default.
+pub const SCAN_TIME: Duration = Duration::from_secs(15);
+/// How long to wait for a remote to pair, including the retry of BlueZ
+/// with the PIN of the agent.
+const PAIR_TIMEOUT: Duration = Duration::from_secs(60);
+/// The timeout of the other D-Bus calls.
+const DBUS_TIMEOUT: Duration = Duration::from_secs(5);
+/// The object path of the pairing agent.
+const AGENT_PATH: &str = "/org/wiinote/agent";
+
+/// Pairs, trusts and connects the Wii Remotes found in pairing mode
+/// during the scan time whose address is accepted, so they reconnect on
+/// their own afterwards. Once connected, `hid-wiimote` binds them and
+/// they are found like any other remote.
+///
+/// The BlueZ `wiimote` plugin supplies the PIN of remotes synced with
+/// the red button, the adapter address. Remotes synced with 1 and 2
+/// expect their own address instead: once the first PIN fails, BlueZ
+/// asks the agent registered here, which replies with the address of
+/// the remote through the Bluetooth management socket, as the PIN is
+/// binary and D-Bus only carries text. That needs the `CAP_NET_ADMIN`
+/// capability; without it, the remote is connected without pairing,
+/// which it accepts until it turns off.
+pub async fn pair(accept: impl Fn(&str) -> bool, scan_time: Duration) -> Result<()> {
+    info!(
+        "Scanning for {}s, press the red sync button of the Wii Remote (or 1 and 2)",
+        scan_time.as_secs()
+    );
+    // Scanning only stops on timeout, don't check the exit status.
+    Command::new("bluetoothctl")
+        // A zero timeout would scan forever.
+        .args([
+            "--timeout",
+            &scan_time.as_secs().max(1).to_string(),
+            "scan",
+            "on",
+        ])
+        .output()
+        .await
+        .context("cannot run bluetoothctl")?;
+
+    let mut remotes = Vec::new();
+    for (address, name) in known_remotes().await? {
+        if !accept(&address) {
+            continue;
+        }
+        // Paired remotes reconnect on their own, and pairing them again
+        // fails; connected ones are already bound.
+        match bluetoothctl(&["info", &address]).await {
+            Ok(info) if info.contains("Paired: yes") || info.contains("Connected: yes") => {
+                debug!(
+                    "Skipping {} ({}), already paired or connected",
+                    name, address
+                );
+            }
+            _ => remotes.push((address, name)),
+        }
+    }
+    if remotes.is_empty() {
+        bail!("no Wii Remote found, make sure it is in pairing mode (lights blinking)");
+    }
+
+    for (address, name) in remotes {
+        info!("Pairing {} ({})", name, address);
+        // The D-Bus connection serving the agent is blocking.
+        let paired = tokio::task::spawn_blocking({
+            let address = address.clone();
+            move || pair_device(&address)
+        })
+        .await
+        .context("the pairing task panicked")?;
+        let address = address.as_str();
+        if let Err(err) = paired {
+            warn!("Cannot pair {}: {:#}", address, err);
+            info!(
+                "Connecting {} without pairing, it won't reconnect on its own",
+                address
+            );
+            if let Err(err) = bluetoothctl(&["connect", address]).await {
+                warn!("Cannot connect {}: {}", address, err);
+            }
+            continue;
+        }
+        for command in ["trust", "connect"] {
+            if let Err(err) = bluetoothctl(&[command, address]).await {
+                warn!("Cannot {} {}: {}", command, address, err);
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pairs the device through the BlueZ D-Bus API, answering the PIN
+/// requests of BlueZ with the address of the device (see [`pair`]).
+fn pair_device(address: &str) -> Result<()> {
+    let connection = Connection::new_system().context("cannot connect to the system D-Bus")?;
+    let bluez = connection.with_proxy("org.bluez", "/", DBUS_TIMEOUT);
+    let objects = bluez
+        .get_managed_objects()
+        .context("cannot list the BlueZ devices")?;
+    let device = objects
+        .into_iter()
+        .find(|(_, interfaces)| {
+            let address_of = interfaces
+                .get("org.bluez.Device1")
+                .and_then(|properties| properties.get("Address"))
+                .and_then(|value| value.0.as_str());
+            address_of == Some(address)
+        })
+        .map(|(path, _)| path)
+        .with_context(|| format!("BlueZ doesn't know {}", address))?;
+    // Device paths are `/org/bluez/hci<index>/dev_<address>`.
+    let index = device
+        .split('/')
+        .find_map(|segment| segment.strip_prefix("hci")?.parse().ok())
+        .with_context(|| format!("no adapter in the device path {}", device))?;
+
+    let manager = connection.with_proxy("org.bluez", "/org/bluez", DBUS_TIMEOUT);
+    let agent = Path::from(AGENT_PATH);
+    manager
+        .method_call::<(), _, _, _>(
+            "org.bluez.AgentManager1",
+            "RegisterAgent",
+            (agent.clone(), "KeyboardDisplay"),
+        )
+        .context("cannot register the pairing agent")?;
+    // BlueZ asks the agent of the caller of `Pair`, this one.
+    let result = run_agent(&connection, &device, index, address);
+    if let Err(err) =
+        manager.method_call::<(), _, _, _>("org.bluez.AgentManager1", "UnregisterAgent", (agent,))
+    {
+        debug!("Cannot unregister the pairing agent: {}", err);
+    }
+    result
+}
+
+/// Calls `Pair` on the device, and serves the agent requests until it
+/// returns.
+fn run_agent(connection: &Connection, device: &Path, index: u16, address: &str) -> Result<()> {
+    let channel = connection.channel();
+    let call = Message::new_method_call("org.bluez", device, "org.bluez.Device1", "Pair")
+        .map_err(|err| anyhow!(err))?;
+    let serial = channel
+        .send(call)
+        .map_err(|()| anyhow!("cannot send the pairing request"))?;
+    let deadline = Instant::now() + PAIR_TIMEOUT;
+    // The PIN request answered through the kernel, replied once paired.
+    let mut answered = None;
+    let result = 'pairing: loop {
+        let remaining = match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) => remaining,
+            None => break Err(anyhow!("timed out")),
+        };
+        if channel.read_write(Some(remaining)).is_err() {
+            break Err(anyhow!("the D-Bus connection closed"));
+        }
+        while let Some(mut message) = channel.pop_message() {
+            match message.msg_type() {
+                MessageType::MethodReturn if message.get_reply_serial() == Some(serial) => {
+                    break 'pairing Ok(());
+                }
+                MessageType::Error if message.get_reply_serial() == Some(serial) => {
+                    // e.g. `org.bluez.Error.AuthenticationFailed`
+                    let err = message.as_result().err().map(anyhow::Error::from);
+                    break 'pairing Err(err.unwrap_or_else(|| anyhow!("pairing failed")));
+                }
+                MessageType::MethodCall => {
+                    if let Some(request) = serve_agent(channel, message, device, index, address) {
+                        answered = Some(request);
+                    }
+                }
+                _ => {}
+            }
+        }
+    };
+    if let Some(request) = answered {
+        // BlueZ already got the PIN from the kernel, and ignores this.
+        reply(channel, reject(&request));
+    }
+    result
+}
+
+/// Answers a call to the agent, except the PIN request of the device
+/// answered through the kernel, which is returned.
+fn serve_agent(
+    channel: &Channel,
+    call: Message,
+    device: &Path,
+    index: u16,
+    address: &str,
+) -> Option<Message> {
+    match call.member().as_deref() {
+        Some("RequestPinCode") if call.read1::<Path>().ok().as_ref() == Some(device) => {}
+        Some("Release" | "Cancel") => {
+            reply(channel, call.method_return());
+            return None;
+        }
+        // Only legacy pairing with a PIN is expected.
+        _ => {
+            reply(channel, reject(&call));
+            return None;
+        }
+    }
+    match pin_code_reply(index, address) {
+        Ok(()) => {
+            info!(
+                "Supplied the address of {} as its PIN, it was synced with 1 and 2",
+                address
+            );
+            Some(call)
+        }
+        Err(err) => {
+            warn!("Cannot supply the PIN of {}: {:#}", address, err);
+            reply(channel, reject(&call));
+            None
+        }
+    }
+}
+
+/// Returns the error rejecting an agent request.
+fn reject(call: &Message) -> Message {
+    let message = CString::new("rejected by wiinote").unwrap();
+    call.error(&"org.bluez.Error.Rejected".into(), &message)
+}
+
+/// Sends the reply to an agent request.
+fn reply(channel: &Channel, message: Message) {
+    if channel.send(message).is_err() {
+        debug!("Cannot reply to the agent request");
+    }
+}
+
+/// The Bluetooth management API of the kernel, on the control channel
+/// of a raw HCI socket; see `doc/mgmt-api.txt` in BlueZ.
+const AF_BLUETOOTH: i32 = 31;
+const BTPROTO_HCI: i32 = 1;
+const HCI_DEV_NONE: u16 = 0xffff;
+const HCI_CHANNEL_CONTROL: u16 = 3;
+const MGMT_OP_PIN_CODE_REPLY: u16 = 0x0016;
+const MGMT_EV_CMD_COMPLETE: u16 = 0x0001;
+const MGMT_EV_CMD_STATUS: u16 = 0x0002;
+const MGMT_STATUS_PERMISSION_DENIED: u8 = 0x14;
+/// How long to wait for the kernel to answer a command.
+const MGMT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[repr(C)]
+struct SockaddrHci {
+    family: libc::sa_family_t,
+    dev: u16,
+    channel: u16,
+}
+
+/// Replies to the PIN request of the kernel for the device with its
+/// address, through the management socket of the adapter.
+fn pin_code_reply(index: u16, address: &str) -> Result<()> {
+    let command =
+        pin_code_command(index, address).with_context(|| format!("invalid address {}", address))?;
+    let fd = unsafe {
+        libc::socket(
+            AF_BLUETOOTH,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            BTPROTO_HCI,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error()).context("cannot open the management socket");
+    }
+    let mut socket = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+    let addr = SockaddrHci {
+        family: AF_BLUETOOTH as libc::sa_family_t,
+        dev: HCI_DEV_NONE,
+        channel: HCI_CHANNEL_CONTROL,
+    };
+    let timeout = libc::timeval {
+        tv_sec: MGMT_TIMEOUT.as_secs() as libc::time_t,
+        tv_usec: 0,
+    };
+    let len = std::mem::size_of::<SockaddrHci>() as libc::socklen_t;
+    let timeout_len = std::mem::size_of::<libc::timeval>() as libc::socklen_t;
+    unsafe {
+        if libc::bind(fd, &addr as *const _ as *const libc::sockaddr, len) < 0
+            || libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const _ as *const libc::c_void,
+                timeout_len,
+            ) < 0
+        {
+            return Err(io::Error::last_os_error()).context("cannot bind the management socket");
+        }
+    }
+    socket
+        .write_all(&command)
+        .context("cannot send the PIN to the kernel")?;
+
+    // Skip the events of other commands and devices.
+    let deadline = Instant::now() + MGMT_TIMEOUT;
+    let mut event = [0; 512];
+    while Instant::now() < deadline {
+        let len = socket
+            .read(&mut event)
+            .context("no answer to the PIN from the kernel")?;
+        // The header is the event code, the adapter index and the
+        // parameters length; the parameters start with the opcode
+        // and the status.
+        if len < 9 {
+            continue;
+        }
+        let code = u16::from_le_bytes([event[0], event[1]]);
+        let opcode = u16::from_le_bytes([event[6], event[7]]);
+        if !matches!(code, MGMT_EV_CMD_COMPLETE | MGMT_EV_CMD_STATUS)
+            || opcode != MGMT_OP_PIN_CODE_REPLY
+        {
+            continue;
+        }
+        return match event[8] {
+            0 => Ok(()),
+            MGMT_STATUS_PERMISSION_DENIED => {
+                bail!("the kernel refused the PIN, wiinote needs the CAP_NET_ADMIN capability")
+            }
+            status => bail!("the kernel refused the PIN with status {:#04x}", status),
+        };
+    }
+    bail!("no answer to the PIN from the kernel")
+}
+
+/// Returns the management command replying to the PIN request of the
+/// device with its address, in the byte order of the Bluetooth
+/// addresses (the reverse of the text).
+fn pin_code_command(index: u16, address: &str) -> Option<Vec<u8>> {
+    let mut bytes = [0; 6];
+    let mut octets = address.split(':').rev();
+    for byte in &mut bytes {
+        *byte = u8::from_str_radix(octets.next()?, 16).ok()?;
+    }
+    if octets.next().is_some() {
+        return None;
+    }
+    let mut pin = [0; 16];
+    pin[..6].copy_from_slice(&bytes);
+    let mut command = Vec::with_capacity(30);
+    command.extend(MGMT_OP_PIN_CODE_REPLY.to_le_bytes());
+    command.extend(index.to_le_bytes());
+    // The address, its type (BR/EDR), the PIN length and the PIN.
+    command.extend(24u16.to_le_bytes());
+    command.extend(bytes);
+    command.extend([0, bytes.len() as u8]);
+    command.extend(pin);
+    Some(command)
+}
+
+/// Returns the address and name of the Wii Remotes and Balance Boards
+/// known to BlueZ: the paired ones, and those found by recent scans.
+pub async fn known_remotes() -> Result<Vec<(String, String)>> {
+    let devices = bluetoothctl(&["devices"]).await?;
+    Ok(devices
+        .lines()
+        // Lines are `Device <address> <name>`.
+        .filter_map(|line| line.strip_prefix("Device ")?.split_once(' '))
+        .filter(|(_, name)| name.starts_with(NAME_PREFIX))
+        .map(|(address, name)| (address.to_owned(), name.to_owned()))
+        .collect())
+}
+
+/// Disconnects the device with the given address, which turns a Wii
+/// Remote off.
+pub async fn disconnect(address: &str) -> Result<()> {
+    bluetoothctl(&["disconnect", address]).await?;
+    Ok(())
+}
+
+/// Runs a `bluetoothctl` command, returning its output.
+async fn bluetoothctl(args: &[&str]) -> Result<String> {
+    let output = Command::new("bluetoothctl")
+        .args(args)
+        .output()
+        .await
+        .context("cannot run bluetoothctl")?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    // bluetoothctl reports some failures on stdout only.
+    ensure!(
+        output.status.success() && !stdout.contains("Failed"),
+        "bluetoothctl {} failed: {}",
+        args.join(" "),
+        stdout.trim()
+    );
+    Ok(stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_the_pin_code_reply() {
+        let command = pin_code_command(1, "00:1F:32:AB:CD:EF").unwrap();
+        let address = [0xef, 0xcd, 0xab, 0x32, 0x1f, 0x00];
+        assert_eq!(command[..6], [0x16, 0x00, 0x01, 0x00, 24, 0x00]);
+        assert_eq!(command[6..12], address);
+        assert_eq!(command[12..14], [0x00, 6]);
+        assert_eq!(command[14..20], address);
+        assert_eq!(command[20..], [0; 10]);
+        assert_eq!(pin_code_command(0, "00:1F:32:AB:CD"), None);
+        assert_eq!(pin_code_command(0, "00:1F:32:AB:CD:EF:01"), None);
+    }
+}
@@ -0,0 +1,81 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use tokio::sync::watch;
+
+/// Assigns player numbers to the connected Wii Remotes in connection
+/// order, reusing the numbers of the disconnected ones.
+pub struct Players {
+    /// Whether each player number is taken.
+    slots: RefCell<Vec<bool>>,
+    /// The number of connected Wii Remotes.
+    connected: watch::Sender<usize>,
+}
+
+impl Players {
+    pub fn new() -> Rc<Self> {
+        Rc::new(Self {
+            slots: RefCell::new(Vec::new()),
+            connected: watch::channel(0).0,
+        })
+    }
+
+    /// Assigns the lowest free player number to a new Wii Remote.
+    pub fn join(self: &Rc<Self>) -> Player {
+        let mut slots = self.slots.borrow_mut();
+        let ix = match slots.iter().position(|taken| !taken) {
+            Some(ix) => ix,
+            None => {
+                slots.push(false);
+                slots.len() - 1
+            }
+        };
+        slots[ix] = true;
+        drop(slots);
+        self.notify();
+
+        Player {
+            players: self.clone(),
+            number: ix + 1,
+            connected: self.connected.subscribe(),
+        }
+    }
+
+    fn notify(&self) {
+        let count = self.slots.borrow().iter().filter(|taken| **taken).count();
+        self.connected.send_replace(count);
+    }
+}
+
+/// The player number of a connected Wii Remote, freed when dropped.
+pub struct Player {
+    players: Rc<Players>,
+    number: usize,
+    connected: watch::Receiver<usize>,
+}
+
+impl Player {
+    /// The player number, starting at 1.
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Whether other Wii Remotes are connected.
+    pub fn is_shared(&self) -> bool {
+        *self.connected.borrow() > 1
+    }
+
+    /// Waits until a Wii Remote connects or disconnects.
+    pub async fn changed(&mut self) {
+        if self.connected.changed().await.is_err() {
+            // The registry outlives the players, but don't spin if not.
+            futures_util::future::pending().await
+        }
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.players.slots.borrow_mut()[self.number - 1] = false;
+        self.players.notify();
+    }
+}
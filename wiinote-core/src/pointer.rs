@@ -0,0 +1,243 @@
+use crate::calibration::{self, Homography};
+use crate::config::{MouseConfig, PointerSource};
+use crate::fusion::Fusion;
+use crate::gyro::Gyro;
+use crate::screen::Bounds;
+use crate::smoothing::Smoother;
+use std::time::Instant;
+use tracing::info;
+use xwiimote::event::EventKind;
+use xwiimote::Channels;
+
+/// The accelerometer values below which the remote is considered level.
+const DEADZONE: i32 = 10;
+/// The pointer movement per unit of Motion Plus angular rate.
+const GYRO_SCALE: f64 = 1.0 / 200.0;
+
+/// Converts the motion of the Wii Remote into relative pointer movements.
+pub struct Pointer {
+    source: PointerSource,
+    speed: f64,
+    /// Whether the mouse mode is enabled.
+    active: bool,
+    /// Whether the button of a pointer binding is held, moving the
+    /// pointer without enabling the mouse mode.
+    held: bool,
+    /// The last IR position.
+    last_ir: Option<(i32, i32)>,
+    gyro: Gyro,
+    /// The fraction of the gyroscope movement not emitted yet.
+    residual: (f64, f64),
+    /// The transformation from the IR camera to the screen, for the
+    /// absolute source.
+    calibration: Homography,
+    /// The filter of the IR positions, if enabled.
+    smoothing: Option<Smoother>,
+    /// The part of the desktop the absolute pointer moves in.
+    bounds: Bounds,
+    /// The estimate of the IR position while the sources are out of
+    /// sight, if the gyroscope fallback is enabled.
+    fusion: Option<Fusion>,
+}
+
+impl Pointer {
+    pub fn new(config: &MouseConfig, calibration: Homography) -> Self {
+        let ir = matches!(config.source, PointerSource::Ir | PointerSource::IrAbsolute);
+        Self {
+            calibration,
+            smoothing: config.smoothing.map(Smoother::new),
+            fusion: (ir && config.gyro_fallback).then(Fusion::new),
+            ..Self::with_source(config.source, config.speed)
+        }
+    }
+
+    fn with_source(source: PointerSource, speed: f64) -> Self {
+        Self {
+            source,
+            speed,
+            active: false,
+            held: false,
+            last_ir: None,
+            gyro: Gyro::new(),
+            residual: (0.0, 0.0),
+            calibration: Homography::default(),
+            smoothing: None,
+            bounds: Bounds::default(),
+            fusion: None,
+        }
+    }
+
+    /// The channel providing the motion events.
+    pub fn channel(&self) -> Channels {
+        let channel = match self.source {
+            PointerSource::Ir | PointerSource::IrAbsolute => Channels::IR,
+            PointerSource::Accelerometer => Channels::ACCELEROMETER,
+            PointerSource::MotionPlus => Channels::MOTION_PLUS,
+        };
+        match self.fusion {
+            // The accelerometer measures the roll of the remote.
+            Some(_) => channel | Channels::MOTION_PLUS | Channels::ACCELEROMETER,
+            None => channel,
+        }
+    }
+
+    /// Restarts the gyroscope calibration, if used.
+    pub fn recalibrate(&mut self) {
+        if self.source == PointerSource::MotionPlus {
+            info!("Calibrating Motion Plus, hold the remote still");
+            self.gyro.recalibrate();
+        } else if let Some(fusion) = &mut self.fusion {
+            info!("Calibrating Motion Plus, hold the remote still");
+            fusion.recalibrate();
+        }
+    }
+
+    /// Returns whether the pointer is placed at absolute positions,
+    /// see [`position`](Self::position).
+    pub fn is_absolute(&self) -> bool {
+        self.source == PointerSource::IrAbsolute
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Enables or disables the mouse mode, returning the new state.
+    pub fn toggle(&mut self) -> bool {
+        self.active = !self.active;
+        self.forget_ir();
+        self.active
+    }
+
+    /// Sets whether the button of a pointer binding is held.
+    pub fn set_held(&mut self, held: bool) {
+        self.held = held;
+        self.forget_ir();
+    }
+
+    /// Restricts the absolute pointer to the part of the desktop.
+    pub fn set_bounds(&mut self, bounds: Bounds) {
+        self.bounds = bounds;
+    }
+
+    /// Returns the desktop position the remote points at for the motion
+    /// event, in fractions of the desktop size, if the pointer is
+    /// absolute.
+    pub fn position(&mut self, event: &EventKind) -> Option<(f64, f64)> {
+        if !self.is_absolute() {
+            return None;
+        }
+        let position = self.ir_position(event)?;
+        if !self.active && !self.held {
+            return None;
+        }
+        Some(self.bounds.apply(self.calibration.apply(position)))
+    }
+
+    /// Returns the smoothed position the remote points at in the IR
+    /// camera coordinates, if the event moved it: the center of the IR
+    /// sources, or the estimate of the fusion while they are out of
+    /// sight. The history is forgotten when the sources go out of sight
+    /// without the fusion, and when they come back with it.
+    fn ir_position(&mut self, event: &EventKind) -> Option<(f64, f64)> {
+        let position = match (&mut self.fusion, event) {
+            (Some(fusion), _) => {
+                let estimate = fusion.update(event)?;
+                if estimate.recentered {
+                    self.forget_ir();
+                }
+                estimate.position
+            }
+            (None, EventKind::Ir(sources)) => {
+                let sources = sources.iter().flatten().map(|s| (s.x, s.y));
+                match calibration::ir_position(sources) {
+                    Some(position) => position,
+                    None => {
+                        self.forget_ir();
+                        return None;
+                    }
+                }
+            }
+            (None, _) => return None,
+        };
+        Some(match &mut self.smoothing {
+            Some(smoothing) => smoothing.filter(position, Instant::now()),
+            None => position,
+        })
+    }
+
+    /// Forgets the previous IR positions, which may be stale.
+    fn forget_ir(&mut self) {
+        self.last_ir = None;
+        if let Some(smoothing) = &mut self.smoothing {
+            smoothing.reset();
+        }
+    }
+
+    /// Returns the pointer movement for the motion event, if any.
+    pub fn motion(&mut self, event: &EventKind) -> Option<(i32, i32)> {
+        if let (PointerSource::MotionPlus, EventKind::MotionPlus { x, y, z }) = (self.source, event)
+        {
+            // Keep tracking the bias while the mouse mode is disabled.
+            let rates = self.gyro.update(*x, *y, *z)?;
+            if !self.active && !self.held {
+                return None;
+            }
+            // Turning left or right rotates the remote around its Z axis
+            // (yaw), pointing up or down around its X axis (pitch).
+            let (rx, ry) = self.residual;
+            let (fx, fy) = (
+                rx - rates[2] * GYRO_SCALE * self.speed,
+                ry - rates[0] * GYRO_SCALE * self.speed,
+            );
+            let (dx, dy) = (fx.trunc() as i32, fy.trunc() as i32);
+            self.residual = (fx.fract(), fy.fract());
+            return (dx != 0 || dy != 0).then_some((dx, dy));
+        }
+        // Keep tracking the IR position while the mouse mode is disabled.
+        let ir = match self.source {
+            PointerSource::Ir => self.ir_position(event),
+            _ => None,
+        };
+        if !self.active && !self.held {
+            return None;
+        }
+        let (dx, dy) = match (self.source, event) {
+            (PointerSource::Ir, _) => {
+                let (x, y) = ir?;
+                let position = (x.round() as i32, y.round() as i32);
+                // The camera sees the sensor bar move in the opposite
+                // direction of the pointer.
+                match self.last_ir.replace(position) {
+                    Some((x0, y0)) => (x0 - position.0, position.1 - y0),
+                    None => return None, // back in range
+                }
+            }
+            (PointerSource::Accelerometer, EventKind::Accelerometer { x, y, .. }) => {
+                // Tilting the remote moves the pointer at a speed
+                // proportional to the angle.
+                let tilt = |value: i32| {
+                    if value.abs() < DEADZONE {
+                        0
+                    } else {
+                        value / DEADZONE
+                    }
+                };
+                (tilt(*x), tilt(-*y))
+            }
+            _ => return None,
+        };
+
+        let scale = |delta: i32| (delta as f64 * self.speed).round() as i32;
+        let (dx, dy) = (scale(dx), scale(dy));
+        (dx != 0 || dy != 0).then_some((dx, dy))
+    }
+}
+
+impl Default for Pointer {
+    /// Creates the pointer of the pointer bindings when the mouse mode
+    /// isn't configured, following the IR camera.
+    fn default() -> Self {
+        Self::with_source(PointerSource::Ir, 1.0)
+    }
+}
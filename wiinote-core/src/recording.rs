@@ -0,0 +1,181 @@
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime};
+use xwiimote::event::{Event, EventKind, IrSource, Key, KeyState};
+
+/// The start of a recording file, with the format version.
+pub const MAGIC: &[u8; 8] = b"WIINOTE\x01";
+
+/// The Wii Remote keys, by their index in the recording.
+const KEYS: [Key; 11] = [
+    Key::Up,
+    Key::Down,
+    Key::Left,
+    Key::Right,
+    Key::A,
+    Key::B,
+    Key::Plus,
+    Key::Minus,
+    Key::Home,
+    Key::One,
+    Key::Two,
+];
+
+/// The tags of the recorded events.
+const TAG_KEY: u8 = 0;
+const TAG_ACCELEROMETER: u8 = 1;
+const TAG_IR: u8 = 2;
+const TAG_MOTION_PLUS: u8 = 3;
+
+/// Writes the button, accelerometer, IR and Motion Plus events of a
+/// Wii Remote to a recording, replayable with
+/// [`ScriptedRemote`](crate::remote::ScriptedRemote).
+///
+/// After the [`MAGIC`] header, each event is the time since the first
+/// one in microseconds (a little-endian `u64`), a tag byte and the
+/// values of the event (little-endian `i32`s, bytes for the key and
+/// its state, and whether each IR source is visible).
+pub struct Recorder<W: Write> {
+    writer: W,
+    start: Option<SystemTime>,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Starts a recording, writing its header.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        Ok(Self {
+            writer,
+            start: None,
+        })
+    }
+
+    /// Records the event, returning whether it's of a recorded kind.
+    pub fn record(&mut self, event: &Event) -> io::Result<bool> {
+        let mut values = Vec::with_capacity(37);
+        if !encode_event(&event.kind, &mut values) {
+            return Ok(false);
+        }
+        let start = *self.start.get_or_insert(event.time);
+        let at = event.time.duration_since(start).unwrap_or_default();
+        self.writer
+            .write_all(&(at.as_micros() as u64).to_le_bytes())?;
+        self.writer.write_all(&values)?;
+        Ok(true)
+    }
+
+    /// Writes the buffered events.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Returns the events of a recording, with their time since the first
+/// one, or `None` if it's malformed.
+pub fn parse(contents: &[u8]) -> Option<Vec<(Duration, EventKind)>> {
+    let mut input = contents.strip_prefix(MAGIC)?;
+    let mut events = Vec::new();
+    while let Some(at) = take(&mut input, 8) {
+        let at = Duration::from_micros(u64::from_le_bytes(at.try_into().ok()?));
+        events.push((at, decode_event(&mut input)?));
+    }
+    // A truncated event is malformed.
+    input.is_empty().then_some(events)
+}
+
+/// Appends the tag and the values of the event to the output, returning
+/// whether it's of a recorded kind.
+pub(crate) fn encode_event(event: &EventKind, output: &mut Vec<u8>) -> bool {
+    match event {
+        EventKind::Key(key, state) => match KEYS.iter().position(|k| k == key) {
+            Some(ix) => {
+                let state = match state {
+                    KeyState::Up => 0,
+                    KeyState::Down => 1,
+                    KeyState::AutoRepeat => 2,
+                };
+                output.extend([TAG_KEY, ix as u8, state]);
+            }
+            None => return false,
+        },
+        EventKind::Accelerometer { x, y, z } => {
+            output.push(TAG_ACCELEROMETER);
+            for value in [x, y, z] {
+                output.extend(value.to_le_bytes());
+            }
+        }
+        EventKind::Ir(sources) => {
+            output.push(TAG_IR);
+            for source in sources {
+                let (x, y) = source.map_or((0, 0), |s| (s.x, s.y));
+                output.push(source.is_some() as u8);
+                output.extend(x.to_le_bytes());
+                output.extend(y.to_le_bytes());
+            }
+        }
+        EventKind::MotionPlus { x, y, z } => {
+            output.push(TAG_MOTION_PLUS);
+            for value in [x, y, z] {
+                output.extend(value.to_le_bytes());
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Reads an event written by [`encode_event`] off the input, or returns
+/// `None` if it's malformed.
+pub(crate) fn decode_event(input: &mut &[u8]) -> Option<EventKind> {
+    Some(match take(input, 1)?[0] {
+        TAG_KEY => {
+            let values = take(input, 2)?;
+            let state = match values[1] {
+                0 => KeyState::Up,
+                1 => KeyState::Down,
+                2 => KeyState::AutoRepeat,
+                _ => return None,
+            };
+            EventKind::Key(*KEYS.get(values[0] as usize)?, state)
+        }
+        TAG_ACCELEROMETER => {
+            let [x, y, z] = read_axes(take(input, 12)?);
+            EventKind::Accelerometer { x, y, z }
+        }
+        TAG_MOTION_PLUS => {
+            let [x, y, z] = read_axes(take(input, 12)?);
+            EventKind::MotionPlus { x, y, z }
+        }
+        TAG_IR => {
+            let mut sources = [None; 4];
+            for source in &mut sources {
+                let values = take(input, 9)?;
+                if values[0] != 0 {
+                    *source = Some(IrSource {
+                        x: read_i32(&values[1..5]),
+                        y: read_i32(&values[5..9]),
+                    });
+                }
+            }
+            EventKind::Ir(sources)
+        }
+        _ => return None,
+    })
+}
+
+/// Splits the given number of bytes off the input, if it's long enough.
+pub(crate) fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if input.len() < len {
+        return None;
+    }
+    let (taken, rest) = input.split_at(len);
+    *input = rest;
+    Some(taken)
+}
+
+fn read_i32(bytes: &[u8]) -> i32 {
+    i32::from_le_bytes(bytes.try_into().unwrap())
+}
+
+fn read_axes(bytes: &[u8]) -> [i32; 3] {
+    [&bytes[..4], &bytes[4..8], &bytes[8..]].map(read_i32)
+}
@@ -0,0 +1,239 @@
+use crate::error::{ConnectionError, ScriptError};
+use crate::recording;
+use anyhow::Result;
+use futures_util::stream::{self, LocalBoxStream, StreamExt, TryStreamExt};
+use serde::Deserialize;
+use std::cell::Cell;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+use xwiimote::event::{Event, EventKind, Key, KeyState};
+use xwiimote::{Address, Channels, Device, Led};
+
+/// Connects to the Wii Remote (or other device of the `hid-wiimote`
+/// driver) at the given address, returning it with its kind, e.g.
+/// `balanceboard`.
+pub fn connect(address: &Address) -> Result<(Device, String), ConnectionError> {
+    let device = Device::connect(address)?;
+    let kind = device.kind()?;
+    Ok((device, kind))
+}
+
+/// Opens the given channels of the device, classifying the failures.
+pub fn open(
+    device: &mut Device,
+    channels: Channels,
+    writable: bool,
+) -> Result<(), ConnectionError> {
+    Ok(device.open(channels, writable)?)
+}
+
+/// The operations on a connected Wii Remote used while handling its
+/// events, implemented by xwiimote devices and [scripted remotes](ScriptedRemote).
+pub trait Remote {
+    /// Returns the stream of events, which ends when the remote disconnects.
+    fn events(&self) -> Result<LocalBoxStream<'_, Result<Event>>>;
+    /// Returns the battery charge, in percent.
+    fn battery(&self) -> Result<u8>;
+    fn led(&self, light: Led) -> Result<bool>;
+    fn set_led(&self, light: Led, enabled: bool) -> Result<()>;
+    fn set_rumble(&self, enabled: bool) -> Result<()>;
+}
+
+impl Remote for Device {
+    fn events(&self) -> Result<LocalBoxStream<'_, Result<Event>>> {
+        let events = Device::events(self)?;
+        Ok(events.map_err(|err| err.into()).boxed_local())
+    }
+
+    fn battery(&self) -> Result<u8> {
+        Ok(Device::battery(self)?)
+    }
+
+    fn led(&self, light: Led) -> Result<bool> {
+        Ok(Device::led(self, light)?)
+    }
+
+    fn set_led(&self, light: Led, enabled: bool) -> Result<()> {
+        Ok(Device::set_led(self, light, enabled)?)
+    }
+
+    fn set_rumble(&self, enabled: bool) -> Result<()> {
+        Ok(Device::set_rumble(self, enabled)?)
+    }
+}
+
+/// A remote replaying the events of a script or a [recording](recording::Recorder),
+/// to try out a configuration without a Wii Remote (see `--replay`). It
+/// disconnects after the last event.
+pub struct ScriptedRemote {
+    events: Vec<(Duration, EventKind)>,
+    battery: u8,
+    /// The state of the lights and the motor, as last set.
+    lights: Cell<u8>,
+    rumble: Cell<bool>,
+}
+
+/// The script file, e.g.
+///
+/// ```toml
+/// battery = 80
+///
+/// [[events]]
+/// at_ms = 0
+/// key = "A"
+/// state = "down"
+///
+/// [[events]]
+/// at_ms = 100
+/// accelerometer = [0, 0, 100]
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Script {
+    #[serde(default = "default_battery")]
+    battery: u8,
+    events: Vec<ScriptEvent>,
+}
+
+fn default_battery() -> u8 {
+    100
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScriptEvent {
+    /// The time of the event since the start of the replay.
+    at_ms: u64,
+    key: Option<String>,
+    state: Option<ScriptKeyState>,
+    accelerometer: Option<[i32; 3]>,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ScriptKeyState {
+    Down,
+    Up,
+}
+
+impl ScriptEvent {
+    fn kind(&self) -> Result<EventKind, ScriptError> {
+        match (&self.key, self.state, self.accelerometer) {
+            (Some(key), Some(state), None) => {
+                let state = match state {
+                    ScriptKeyState::Down => KeyState::Down,
+                    ScriptKeyState::Up => KeyState::Up,
+                };
+                Ok(EventKind::Key(parse_key(key)?, state))
+            }
+            (None, None, Some([x, y, z])) => Ok(EventKind::Accelerometer { x, y, z }),
+            _ => Err(ScriptError::InvalidEvent(self.at_ms)),
+        }
+    }
+}
+
+fn parse_key(name: &str) -> Result<Key, ScriptError> {
+    const KEYS: [(&str, Key); 11] = [
+        ("Up", Key::Up),
+        ("Down", Key::Down),
+        ("Left", Key::Left),
+        ("Right", Key::Right),
+        ("A", Key::A),
+        ("B", Key::B),
+        ("Plus", Key::Plus),
+        ("Minus", Key::Minus),
+        ("Home", Key::Home),
+        ("One", Key::One),
+        ("Two", Key::Two),
+    ];
+    KEYS.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, key)| *key)
+        .ok_or_else(|| ScriptError::UnknownKey(name.to_owned()))
+}
+
+impl ScriptedRemote {
+    /// Creates a remote sending the events at the given times since the
+    /// start of the replay, with the given battery percentage.
+    pub fn new(events: Vec<(Duration, EventKind)>, battery: u8) -> Self {
+        Self {
+            events,
+            battery,
+            lights: Cell::new(0),
+            rumble: Cell::new(false),
+        }
+    }
+
+    /// Loads the script or recording at the given path.
+    pub fn load(path: &Path) -> Result<Self, ScriptError> {
+        let read_error = |source| ScriptError::Read {
+            path: path.to_owned(),
+            source,
+        };
+        let contents = std::fs::read(path).map_err(read_error)?;
+        if contents.starts_with(recording::MAGIC) {
+            let events = recording::parse(&contents)
+                .ok_or_else(|| ScriptError::Recording(path.to_owned()))?;
+            return Ok(Self::new(events, default_battery()));
+        }
+        let contents = String::from_utf8(contents)
+            .map_err(|err| read_error(io::Error::new(io::ErrorKind::InvalidData, err)))?;
+        let script: Script = toml::from_str(&contents).map_err(|source| ScriptError::Parse {
+            path: path.to_owned(),
+            source,
+        })?;
+        let events = script
+            .events
+            .iter()
+            .map(|event| Ok((Duration::from_millis(event.at_ms), event.kind()?)))
+            .collect::<Result<_, ScriptError>>()?;
+        Ok(Self::new(events, script.battery))
+    }
+
+    /// Returns the lights turned on, where the first light is the
+    /// least significant bit.
+    pub fn lights(&self) -> u8 {
+        self.lights.get()
+    }
+
+    pub fn is_rumbling(&self) -> bool {
+        self.rumble.get()
+    }
+}
+
+impl Remote for ScriptedRemote {
+    fn events(&self) -> Result<LocalBoxStream<'_, Result<Event>>> {
+        let start = Instant::now();
+        let events = stream::iter(&self.events).then(move |(at, kind)| async move {
+            tokio::time::sleep_until(start + *at).await;
+            Ok(Event {
+                time: SystemTime::now(),
+                kind: kind.clone(),
+            })
+        });
+        Ok(events.boxed_local())
+    }
+
+    fn battery(&self) -> Result<u8> {
+        Ok(self.battery)
+    }
+
+    fn led(&self, light: Led) -> Result<bool> {
+        Ok(self.lights.get() & (1 << (light as u8 - 1)) != 0)
+    }
+
+    fn set_led(&self, light: Led, enabled: bool) -> Result<()> {
+        let bit = 1 << (light as u8 - 1);
+        let lights = self.lights.get();
+        self.lights
+            .set(if enabled { lights | bit } else { lights & !bit });
+        Ok(())
+    }
+
+    fn set_rumble(&self, enabled: bool) -> Result<()> {
+        self.rumble.set(enabled);
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+use crate::keyboard::{Emitted, Input};
+use crate::mapping::{self, KeyCode, MouseAction};
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// A scenario file, replaying a script or a recording through the
+/// mappings and checking what they emit, e.g.
+///
+/// ```toml
+/// script = "next-slide.toml"
+/// config = "presenter.toml"
+///
+/// [expect]
+/// emitted = ["Right down", "Right up"]
+/// lights = [1]
+/// exit = "disconnected"
+/// ```
+///
+/// The paths are relative to the scenario file. The configuration
+/// defaults to the one of `--config`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(skip)]
+    path: PathBuf,
+    script: PathBuf,
+    config: Option<PathBuf>,
+    #[serde(default)]
+    expect: Expectations,
+}
+
+/// The expected outputs, the missing ones aren't checked. The inputs
+/// must all be released at the end.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Expectations {
+    /// The presses and releases, in order (the pointer and scroll
+    /// wheel moves aren't checked).
+    emitted: Option<Vec<String>>,
+    /// The lights turned on at the end, numbered from 1.
+    lights: Option<Vec<u8>>,
+    rumble: Option<bool>,
+    /// Why the connection ended, e.g. `disconnected` or `power-off`.
+    exit: Option<String>,
+}
+
+/// The exits the scenarios can expect, see [`Outcome::exit`].
+const EXITS: [&str; 6] = [
+    "disconnected",
+    "extension-changed",
+    "released",
+    "idle",
+    "power-off",
+    "adapter-off",
+];
+
+/// What the mappings did with the events of a scenario.
+pub struct Outcome {
+    pub emitted: Vec<Emitted>,
+    /// The inputs left pressed.
+    pub held: Vec<Input>,
+    /// The lights turned on, where the first light is the least
+    /// significant bit.
+    pub lights: u8,
+    pub rumble: bool,
+    pub exit: &'static str,
+}
+
+impl Scenario {
+    /// Loads the scenario at the given path, resolving its paths.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        let mut scenario: Scenario = toml::from_str(&contents)
+            .with_context(|| format!("cannot parse {}", path.display()))?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        scenario.script = base.join(&scenario.script);
+        scenario.config = scenario.config.map(|config| base.join(config));
+        scenario.path = path.to_owned();
+        if let Some(emitted) = &scenario.expect.emitted {
+            for entry in emitted {
+                parse_emitted(entry)?;
+            }
+        }
+        if let Some(light) = scenario
+            .expect
+            .lights
+            .iter()
+            .flatten()
+            .find(|light| !(1..=4).contains(*light))
+        {
+            return Err(anyhow!("unknown light {}, expected 1 to 4", light));
+        }
+        if let Some(exit) = &scenario.expect.exit {
+            if !EXITS.contains(&exit.as_str()) {
+                return Err(anyhow!(
+                    "unknown exit `{}`, expected one of {}",
+                    exit,
+                    EXITS.join(", ")
+                ));
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Returns the path of the scenario file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the script or recording to replay.
+    pub fn script(&self) -> &Path {
+        &self.script
+    }
+
+    /// Returns the configuration to use, if not the one of `--config`.
+    pub fn config(&self) -> Option<&Path> {
+        self.config.as_deref()
+    }
+
+    /// Checks the outcome against the expectations, failing with the
+    /// list of mismatches.
+    pub fn verify(&self, outcome: &Outcome) -> Result<()> {
+        let mismatches = self.check(outcome);
+        if mismatches.is_empty() {
+            return Ok(());
+        }
+        let mut message = format!("scenario {} failed:", self.path.display());
+        for mismatch in mismatches {
+            let _ = write!(message, "\n  - {}", mismatch);
+        }
+        Err(anyhow!(message))
+    }
+
+    /// Checks the outcome against the expectations.
+    ///
+    /// # Returns
+    /// The mismatches, empty if the scenario passed.
+    fn check(&self, outcome: &Outcome) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if let Some(expected) = &self.expect.emitted {
+            let emitted: Vec<_> = outcome
+                .emitted
+                .iter()
+                .filter(|emitted| !matches!(emitted, Emitted::Move(..)))
+                .map(|emitted| describe(*emitted))
+                .collect();
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|entry| describe(parse_emitted(entry).expect("checked on load")))
+                .collect();
+            if emitted != expected {
+                mismatches.push(format!(
+                    "emitted [{}], expected [{}]",
+                    emitted.join(", "),
+                    expected.join(", ")
+                ));
+            }
+        }
+        if !outcome.held.is_empty() {
+            let held: Vec<_> = outcome.held.iter().map(|input| name(*input)).collect();
+            mismatches.push(format!("left {} pressed", held.join(", ")));
+        }
+        if let Some(lights) = &self.expect.lights {
+            let expected = lights.iter().fold(0, |bits, light| bits | 1 << (light - 1));
+            if outcome.lights != expected {
+                mismatches.push(format!(
+                    "lights {:04b}, expected {:04b}",
+                    outcome.lights, expected
+                ));
+            }
+        }
+        if let Some(rumble) = self.expect.rumble {
+            if outcome.rumble != rumble {
+                mismatches.push(format!(
+                    "rumble {}, expected {}",
+                    on_off(outcome.rumble),
+                    on_off(rumble)
+                ));
+            }
+        }
+        if let Some(exit) = &self.expect.exit {
+            if outcome.exit != exit {
+                mismatches.push(format!("exit `{}`, expected `{}`", outcome.exit, exit));
+            }
+        }
+        mismatches
+    }
+}
+
+/// Parses an expected press or release, e.g. `"Right down"` or
+/// `"MouseLeft up"`.
+fn parse_emitted(entry: &str) -> Result<Emitted> {
+    let (input, state) = entry
+        .rsplit_once(' ')
+        .ok_or_else(|| anyhow!("expected `<key> down` or `<key> up`, got `{}`", entry))?;
+    let input = match input.parse::<KeyCode>() {
+        Ok(KeyCode(key)) => Input::Key(key),
+        Err(err) => match input.parse::<MouseAction>() {
+            Ok(MouseAction::Button(button)) => Input::Mouse(button),
+            _ => return Err(err),
+        },
+    };
+    match state {
+        "down" => Ok(Emitted::Press(input)),
+        "up" => Ok(Emitted::Release(input)),
+        _ => Err(anyhow!(
+            "unknown state `{}`, expected `down` or `up`",
+            state
+        )),
+    }
+}
+
+fn describe(emitted: Emitted) -> String {
+    match emitted {
+        Emitted::Press(input) => format!("{} down", name(input)),
+        Emitted::Release(input) => format!("{} up", name(input)),
+        Emitted::Move(axis, value) => format!("{:?} {:+}", axis, value),
+    }
+}
+
+fn name(input: Input) -> String {
+    match input {
+        Input::Key(key) => mapping::key_name(key)
+            .map(str::to_owned)
+            .unwrap_or_else(|| format!("{:?}", key)),
+        Input::Mouse(button) => format!("Mouse{:?}", button),
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uinput::event::controller::Mouse;
+    use uinput::event::keyboard::Key;
+    use uinput::event::relative::Position;
+    use uinput::event::{Keyboard, Relative};
+
+    fn scenario(contents: &str) -> Scenario {
+        toml::from_str(contents).unwrap()
+    }
+
+    fn outcome(emitted: Vec<Emitted>) -> Outcome {
+        Outcome {
+            emitted,
+            held: Vec::new(),
+            lights: 0b0001,
+            rumble: false,
+            exit: "disconnected",
+        }
+    }
+
+    #[test]
+    fn parses_the_presses_and_releases() {
+        let right = Input::Key(Keyboard::Key(Key::Right));
+        assert_eq!(parse_emitted("Right down").unwrap(), Emitted::Press(right));
+        assert_eq!(
+            parse_emitted("mouseleft up").unwrap(),
+            Emitted::Release(Input::Mouse(Mouse::Left))
+        );
+        assert!(parse_emitted("Right").is_err());
+        assert!(parse_emitted("ScrollUp down").is_err());
+        assert!(parse_emitted("Right held").is_err());
+    }
+
+    #[test]
+    fn reports_the_mismatches() {
+        let scenario = scenario(
+            r#"
+            script = "script.toml"
+
+            [expect]
+            emitted = ["Right down", "Right up"]
+            lights = [1, 4]
+            exit = "power-off"
+            "#,
+        );
+        let right = Input::Key(Keyboard::Key(Key::Right));
+        let mut outcome = outcome(vec![
+            Emitted::Press(right),
+            Emitted::Move(Relative::Position(Position::X), 3),
+            Emitted::Release(right),
+        ]);
+        outcome.lights = 0b1001;
+        outcome.exit = "power-off";
+        assert!(scenario.check(&outcome).is_empty());
+
+        outcome.emitted.pop();
+        outcome.held.push(right);
+        outcome.lights = 0b0001;
+        outcome.exit = "idle";
+        assert_eq!(
+            scenario.check(&outcome),
+            [
+                "emitted [Right down], expected [Right down, Right up]",
+                "left Right pressed",
+                "lights 0001, expected 1001",
+                "exit `idle`, expected `power-off`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::{anyhow, ensure, Context, Error, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::process::Command;
+use std::str::FromStr;
+
+/// A rectangle of the desktop, in pixels.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl FromStr for Rect {
+    type Err = Error;
+
+    /// Parses an X geometry, e.g. `1920x1080+1920+0`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid geometry `{}`, expected e.g. `1920x1080+0+0`", s);
+        let (width, rest) = s.split_once('x').ok_or_else(invalid)?;
+        let mut parts = rest.splitn(3, '+');
+        let mut next = || -> Result<i32> {
+            parts
+                .next()
+                .and_then(|part| part.parse().ok())
+                .ok_or_else(invalid)
+        };
+        let width = width.parse().map_err(|_| invalid())?;
+        let rect = Self {
+            width,
+            height: next()?,
+            x: next()?,
+            y: next()?,
+        };
+        ensure!(rect.width > 0 && rect.height > 0, invalid());
+        Ok(rect)
+    }
+}
+
+/// The part of the desktop the absolute pointer is restricted to: a
+/// monitor by name (e.g. `HDMI-1`) or a geometry.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(from = "String")]
+pub enum Region {
+    Monitor(String),
+    Geometry(Rect),
+}
+
+impl From<String> for Region {
+    fn from(s: String) -> Self {
+        match s.parse() {
+            Ok(rect) => Self::Geometry(rect),
+            Err(_) => Self::Monitor(s),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Monitor(name) => f.write_str(name),
+            Self::Geometry(rect) => {
+                write!(f, "{}x{}+{}+{}", rect.width, rect.height, rect.x, rect.y)
+            }
+        }
+    }
+}
+
+/// A part of the desktop, in fractions of its size.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Default for Bounds {
+    /// The whole desktop.
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+impl Bounds {
+    /// Returns the desktop position of the position in the bounds, both
+    /// in fractions of their size.
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (self.x + x * self.width, self.y + y * self.height)
+    }
+}
+
+/// Returns the monitors and their geometry, as listed by `xrandr`.
+pub fn monitors() -> Result<Vec<(String, Rect)>> {
+    let output = Command::new("xrandr")
+        .arg("--listmonitors")
+        .output()
+        .context("cannot run xrandr")?;
+    ensure!(
+        output.status.success(),
+        "xrandr exited with {}",
+        output.status
+    );
+    // e.g. ` 1: +HDMI-1 1920/531x1080/299+1920+0  HDMI-1`, with the
+    // physical size in millimeters after the slashes.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut monitors = Vec::new();
+    for line in stdout.lines().skip(1) {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        if let [_, _, geometry, name] = fields[..] {
+            let mut pixels = String::new();
+            let mut millimeters = false;
+            for c in geometry.chars() {
+                match c {
+                    '/' => millimeters = true,
+                    'x' | '+' => {
+                        millimeters = false;
+                        pixels.push(c);
+                    }
+                    _ if !millimeters => pixels.push(c),
+                    _ => {}
+                }
+            }
+            monitors.push((name.to_owned(), pixels.parse()?));
+        }
+    }
+    Ok(monitors)
+}
+
+/// Returns the bounds of the region in the desktop, which spans every
+/// monitor.
+pub fn bounds(region: &Region) -> Result<Bounds> {
+    let monitors = monitors()?;
+    let rect = match region {
+        Region::Geometry(rect) => *rect,
+        Region::Monitor(name) => monitors
+            .iter()
+            .find(|(monitor, _)| monitor == name)
+            .map(|(_, rect)| *rect)
+            .with_context(|| format!("no monitor named `{}`", name))?,
+    };
+    let width = monitors
+        .iter()
+        .map(|(_, rect)| rect.x + rect.width)
+        .max()
+        .context("no monitors found")?;
+    let height = monitors
+        .iter()
+        .map(|(_, rect)| rect.y + rect.height)
+        .max()
+        .context("no monitors found")?;
+    Ok(Bounds {
+        x: rect.x as f64 / width as f64,
+        y: rect.y as f64 / height as f64,
+        width: rect.width as f64 / width as f64,
+        height: rect.height as f64 / height as f64,
+    })
+}
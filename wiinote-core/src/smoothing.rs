@@ -0,0 +1,83 @@
+use crate::config::SmoothingConfig;
+use std::f64::consts::PI;
+use std::time::{Duration, Instant};
+
+/// The time between two samples assumed when they arrive at once.
+const MIN_PERIOD: Duration = Duration::from_millis(1);
+
+/// Smooths the jitter of a position, e.g. of the IR sources, with the
+/// configured filter.
+pub struct Smoother {
+    config: SmoothingConfig,
+    axes: [Axis; 2],
+    /// The instant of the last sample, if any since the last reset.
+    last: Option<Instant>,
+}
+
+/// The state of the filter of an axis.
+#[derive(Copy, Clone, Default)]
+struct Axis {
+    value: f64,
+    /// The filtered rate of change, for the one euro filter.
+    rate: f64,
+}
+
+impl Smoother {
+    pub fn new(config: SmoothingConfig) -> Self {
+        Self {
+            config,
+            axes: [Axis::default(); 2],
+            last: None,
+        }
+    }
+
+    /// Returns the smoothed position of the sample taken at the given
+    /// instant.
+    pub fn filter(&mut self, (x, y): (f64, f64), now: Instant) -> (f64, f64) {
+        let period = match self.last.replace(now) {
+            Some(last) => now.duration_since(last).max(MIN_PERIOD).as_secs_f64(),
+            None => {
+                // Start from the first sample.
+                self.axes = [x, y].map(|value| Axis { value, rate: 0.0 });
+                return (x, y);
+            }
+        };
+        let config = self.config;
+        for (axis, sample) in self.axes.iter_mut().zip([x, y]) {
+            match config {
+                SmoothingConfig::Exponential { time_constant_ms } => {
+                    let tau = time_constant_ms / 1000.0;
+                    let alpha = 1.0 - (-period / tau.max(f64::EPSILON)).exp();
+                    axis.value += alpha * (sample - axis.value);
+                }
+                SmoothingConfig::OneEuro {
+                    min_cutoff,
+                    beta,
+                    derivative_cutoff,
+                } => {
+                    // The cutoff frequency grows with the speed: the
+                    // slow movements are smoothed, the fast ones follow
+                    // without lag.
+                    let rate = (sample - axis.value) / period;
+                    axis.rate += smoothing_factor(derivative_cutoff, period) * (rate - axis.rate);
+                    let cutoff = min_cutoff + beta * axis.rate.abs();
+                    axis.value += smoothing_factor(cutoff, period) * (sample - axis.value);
+                }
+            }
+        }
+        (self.axes[0].value, self.axes[1].value)
+    }
+
+    /// Forgets the previous samples, e.g. once the IR sources are out of
+    /// sight.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// Returns the smoothing factor of a low-pass filter with the given
+/// cutoff frequency (in Hz) for the sampling period (in seconds).
+fn smoothing_factor(cutoff: f64, period: f64) -> f64 {
+    let tau = 1.0 / (2.0 * PI * cutoff);
+    1.0 / (1.0 + tau / period)
+}
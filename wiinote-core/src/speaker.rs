@@ -0,0 +1,275 @@
+use anyhow::{bail, ensure, Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{debug, warn};
+
+/// The output report enabling (`0x04`) or disabling the speaker.
+const ENABLE_REPORT: u8 = 0x14;
+/// The output report muting (`0x04`) or unmuting the speaker.
+const MUTE_REPORT: u8 = 0x19;
+/// The output report writing up to 16 bytes to the memory or registers.
+const WRITE_REPORT: u8 = 0x16;
+/// The output report carrying up to 20 bytes of sound data.
+const DATA_REPORT: u8 = 0x18;
+/// The address space of the control registers, in a write report.
+const REGISTER_SPACE: u8 = 0x04;
+/// The registers of the speaker configuration.
+const SPEAKER_CONFIG: u32 = 0xa2_0001;
+const SPEAKER_ENABLE: u32 = 0xa2_0009;
+const SPEAKER_START: u32 = 0xa2_0008;
+/// The format of signed 8-bit PCM samples, in the speaker configuration.
+const FORMAT_PCM8: u8 = 0x40;
+/// The volume of the speaker, from 0 to 255. Loud enough for a beep
+/// without clipping the small speaker.
+const VOLUME: u8 = 0x40;
+/// The samples sent per data report.
+const CHUNK_LEN: usize = 20;
+/// The highest sample rate streamed reliably over Bluetooth.
+pub const MAX_SAMPLE_RATE: u32 = 4000;
+
+/// A sound of signed 8-bit mono samples, the format played by the
+/// speaker.
+#[derive(Clone, Debug)]
+pub struct Sound {
+    samples: Vec<i8>,
+    rate: u32,
+}
+
+impl Sound {
+    /// Loads a PCM WAV file with 8 or 16-bit samples, sampled at most at
+    /// [`MAX_SAMPLE_RATE`]. Only the first channel of stereo files is
+    /// kept.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("cannot read {}", path.display()))?;
+        Self::from_wav(&data).with_context(|| format!("invalid WAV file {}", path.display()))
+    }
+
+    fn from_wav(data: &[u8]) -> Result<Self> {
+        ensure!(
+            data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WAVE",
+            "not a RIFF WAVE file"
+        );
+        let mut format = None;
+        let mut chunks = &data[12..];
+        while chunks.len() >= 8 {
+            let id = &chunks[..4];
+            let len = u32::from_le_bytes(chunks[4..8].try_into().unwrap()) as usize;
+            let body = chunks.get(8..8 + len).context("truncated chunk")?;
+            match id {
+                b"fmt " => {
+                    ensure!(body.len() >= 16, "truncated format chunk");
+                    let field = |at: usize| u16::from_le_bytes([body[at], body[at + 1]]);
+                    let tag = field(0);
+                    let rate = u32::from_le_bytes(body[4..8].try_into().unwrap());
+                    let (block_align, bits) = (field(12), field(14));
+                    ensure!(tag == 1, "unsupported encoding {}, expected PCM", tag);
+                    ensure!(
+                        bits == 8 || bits == 16,
+                        "unsupported {}-bit samples, expected 8 or 16 bits",
+                        bits
+                    );
+                    ensure!(
+                        rate > 0 && rate <= MAX_SAMPLE_RATE,
+                        "unsupported sample rate of {} Hz, expected at most {} Hz",
+                        rate,
+                        MAX_SAMPLE_RATE
+                    );
+                    ensure!(block_align >= bits / 8, "invalid block alignment");
+                    format = Some((rate, block_align as usize, bits));
+                }
+                b"data" => {
+                    let (rate, block_align, bits) = format.context("missing format chunk")?;
+                    let samples = body
+                        .chunks_exact(block_align)
+                        .map(|frame| match bits {
+                            // 8-bit samples are unsigned, 16-bit ones signed.
+                            8 => (frame[0] as i16 - 128) as i8,
+                            _ => (i16::from_le_bytes([frame[0], frame[1]]) >> 8) as i8,
+                        })
+                        .collect();
+                    return Ok(Self { samples, rate });
+                }
+                _ => {}
+            }
+            // Chunks are padded to an even length.
+            chunks = chunks.get(8 + len + len % 2..).unwrap_or_default();
+        }
+        bail!("missing data chunk")
+    }
+
+    /// Returns a short beep, the click played on button presses.
+    pub fn click() -> Self {
+        const RATE: u32 = 3000;
+        // 40 ms of a 750 Hz square wave.
+        let samples = (0..RATE as usize * 40 / 1000)
+            .map(|i| if i % 4 < 2 { 64 } else { -64 })
+            .collect();
+        Self {
+            samples,
+            rate: RATE,
+        }
+    }
+
+    /// Returns how long the sound plays.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.samples.len() as f64 / self.rate as f64)
+    }
+}
+
+/// The speaker of a Wii Remote, driven through the `hidraw` node of the
+/// remote since `hid-wiimote` doesn't expose it.
+pub struct Speaker {
+    hidraw: File,
+}
+
+impl Speaker {
+    /// Opens the speaker of the Wii Remote at the given sysfs path.
+    pub fn open(path: &Path) -> Result<Self> {
+        let node = hidraw_node(path)?;
+        let hidraw = OpenOptions::new()
+            .write(true)
+            .open(&node)
+            .with_context(|| format!("cannot open {}", node.display()))?;
+        Ok(Self { hidraw })
+    }
+
+    /// Plays the sound, returning once it has been sent. The speaker
+    /// is turned off afterwards.
+    ///
+    /// The reports don't keep the rumble bit, a playing rumble stops.
+    pub async fn play(&mut self, sound: &Sound) -> Result<()> {
+        self.configure(sound.rate)?;
+        let mut interval = time::interval(Duration::from_secs_f64(
+            CHUNK_LEN as f64 / sound.rate as f64,
+        ));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut result = Ok(());
+        for chunk in sound.samples.chunks(CHUNK_LEN) {
+            interval.tick().await;
+            let mut report = [0; CHUNK_LEN + 2];
+            report[0] = DATA_REPORT;
+            report[1] = (chunk.len() as u8) << 3;
+            for (byte, sample) in report[2..].iter_mut().zip(chunk) {
+                *byte = *sample as u8;
+            }
+            result = self.send(&report);
+            if result.is_err() {
+                break;
+            }
+        }
+        // Let the last chunk play before turning the speaker off.
+        interval.tick().await;
+        self.send(&[MUTE_REPORT, 0x04])?;
+        self.send(&[ENABLE_REPORT, 0x00])?;
+        result
+    }
+
+    /// Runs the initialization sequence of the speaker for 8-bit PCM
+    /// samples at the given rate.
+    fn configure(&mut self, rate: u32) -> Result<()> {
+        let [rate_low, rate_high] = ((12_000_000 / rate) as u16).to_le_bytes();
+        self.send(&[ENABLE_REPORT, 0x04])?;
+        self.send(&[MUTE_REPORT, 0x04])?;
+        self.write_register(SPEAKER_ENABLE, &[0x01])?;
+        self.write_register(SPEAKER_CONFIG, &[0x08])?;
+        self.write_register(
+            SPEAKER_CONFIG,
+            &[0x00, FORMAT_PCM8, rate_low, rate_high, VOLUME, 0x00, 0x00],
+        )?;
+        self.write_register(SPEAKER_START, &[0x01])?;
+        self.send(&[MUTE_REPORT, 0x00])
+    }
+
+    fn write_register(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        let [_, high, middle, low] = address.to_be_bytes();
+        let mut report = [0; 22];
+        report[..6].copy_from_slice(&[
+            WRITE_REPORT,
+            REGISTER_SPACE,
+            high,
+            middle,
+            low,
+            data.len() as u8,
+        ]);
+        report[6..6 + data.len()].copy_from_slice(data);
+        self.send(&report)
+    }
+
+    fn send(&mut self, report: &[u8]) -> Result<()> {
+        self.hidraw
+            .write_all(report)
+            .with_context(|| format!("cannot send report {:#04x}", report[0]))
+    }
+}
+
+/// Returns the `/dev/hidrawN` node of the HID device at the given sysfs
+/// path.
+fn hidraw_node(path: &Path) -> Result<PathBuf> {
+    let name = fs::read_dir(path.join("hidraw"))
+        .ok()
+        .and_then(|mut entries| entries.next())
+        .and_then(|entry| entry.ok())
+        .with_context(|| format!("no hidraw node found for {}", path.display()))?
+        .file_name();
+    Ok(Path::new("/dev").join(name))
+}
+
+/// Plays sounds on the speaker of a Wii Remote in the background, one
+/// at a time.
+pub struct SoundPlayer {
+    /// The sysfs path of the remote.
+    path: PathBuf,
+    playing: Option<JoinHandle<()>>,
+}
+
+impl SoundPlayer {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            playing: None,
+        }
+    }
+
+    /// Starts playing the sound file, cutting the one playing, if any.
+    /// Failures are logged.
+    pub fn play(&mut self, file: PathBuf) {
+        let name = file.display().to_string();
+        self.spawn(name, move || Sound::load(&file));
+    }
+
+    /// Starts playing the sound, like [`play`](Self::play).
+    pub fn play_sound(&mut self, sound: Sound) {
+        self.spawn("sound".to_owned(), move || Ok(sound));
+    }
+
+    fn spawn(&mut self, name: String, load: impl FnOnce() -> Result<Sound> + Send + 'static) {
+        self.stop();
+        let path = self.path.clone();
+        self.playing = Some(tokio::spawn(async move {
+            debug!("Playing {}", name);
+            let result = async {
+                let sound = load()?;
+                Speaker::open(&path)?.play(&sound).await
+            };
+            if let Err(err) = result.await {
+                warn!("Cannot play {}: {:#}", name, err);
+            }
+        }));
+    }
+
+    fn stop(&mut self) {
+        if let Some(playing) = self.playing.take() {
+            playing.abort();
+        }
+    }
+}
+
+impl Drop for SoundPlayer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
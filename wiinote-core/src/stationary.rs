@@ -0,0 +1,92 @@
+use crate::config::StationaryConfig;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Tells whether the Wii Remote is roughly stationary from the variance
+/// of its acceleration, so that the commands and power offs bound to
+/// its buttons don't run when pressed mid-swing.
+pub struct StationaryGuard {
+    /// The acceleration magnitude of the readings within the window.
+    readings: VecDeque<(Instant, f64)>,
+    window: Duration,
+    max_variance: f64,
+}
+
+impl StationaryGuard {
+    pub fn new(config: &StationaryConfig) -> Self {
+        Self {
+            readings: VecDeque::new(),
+            window: Duration::from_millis(config.window_ms),
+            max_variance: config.max_variance,
+        }
+    }
+
+    /// Updates the guard with an accelerometer reading.
+    pub fn update(&mut self, time: Instant, x: i32, y: i32, z: i32) {
+        let magnitude = ((x * x + y * y + z * z) as f64).sqrt();
+        self.readings.push_back((time, magnitude));
+        while let Some((first, _)) = self.readings.front() {
+            if time - *first <= self.window {
+                break;
+            }
+            self.readings.pop_front();
+        }
+    }
+
+    /// Returns whether the variance of the acceleration over the window
+    /// is low enough. The remote is stationary without recent readings,
+    /// as the accelerometer only reports the changes.
+    pub fn is_stationary(&self) -> bool {
+        let count = self.readings.len() as f64;
+        if count < 2.0 {
+            return true;
+        }
+        let mean = self.readings.iter().map(|(_, m)| m).sum::<f64>() / count;
+        let variance = self
+            .readings
+            .iter()
+            .map(|(_, m)| (m - mean) * (m - mean))
+            .sum::<f64>()
+            / count;
+        variance <= self.max_variance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> StationaryGuard {
+        StationaryGuard::new(&StationaryConfig {
+            max_variance: 25.0,
+            window_ms: 250,
+        })
+    }
+
+    #[test]
+    fn resting_remote_is_stationary() {
+        let mut guard = guard();
+        let start = Instant::now();
+        for (ix, z) in [100, 102, 99, 101, 100].into_iter().enumerate() {
+            guard.update(start + Duration::from_millis(10 * ix as u64), 0, 0, z);
+        }
+        assert!(guard.is_stationary());
+    }
+
+    #[test]
+    fn swinging_remote_moves_until_the_window_passes() {
+        let mut guard = guard();
+        let start = Instant::now();
+        for (ix, x) in [0, 150, 250, 120, 30].into_iter().enumerate() {
+            guard.update(start + Duration::from_millis(10 * ix as u64), x, 0, 100);
+        }
+        assert!(!guard.is_stationary());
+        // Back at rest, the swing leaves the window.
+        for ix in 0..5 {
+            let time = start + Duration::from_millis(300 + 10 * ix);
+            guard.update(time, 0, 0, 100);
+        }
+        assert!(guard.is_stationary());
+    }
+}
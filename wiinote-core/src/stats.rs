@@ -0,0 +1,98 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::debug;
+use xwiimote::event::EventKind;
+
+/// The reasons an event is not turned into any output.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum IgnoreReason {
+    /// The button is not bound to any key.
+    Unmapped,
+    /// The event comes from a channel without a handler, e.g. an
+    /// extension when `--extension-devices` is not set.
+    UnsupportedChannel,
+    /// The keyboard output is muted.
+    Muted,
+    /// The event rate exceeds the configured budget.
+    OverBudget,
+}
+
+impl IgnoreReason {
+    const ALL: [Self; 4] = [
+        Self::Unmapped,
+        Self::UnsupportedChannel,
+        Self::Muted,
+        Self::OverBudget,
+    ];
+
+    /// A short identifier of the reason, used in logs and summaries.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Self::Unmapped => "unmapped",
+            Self::UnsupportedChannel => "unsupported-channel",
+            Self::Muted => "muted",
+            Self::OverBudget => "over-budget",
+        }
+    }
+}
+
+/// The identifier of the last received event, unique for the whole
+/// process lifetime so logs can be correlated across reconnections.
+static LAST_EVENT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a new event identifier.
+pub fn next_event_id() -> u64 {
+    LAST_EVENT_ID.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Counts the events ignored during a connection, by reason.
+pub struct EventStats {
+    received: u64,
+    ignored: [u64; IgnoreReason::ALL.len()],
+}
+
+impl Default for EventStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventStats {
+    pub fn new() -> Self {
+        Self {
+            received: 0,
+            ignored: [0; IgnoreReason::ALL.len()],
+        }
+    }
+
+    /// Counts a received event, returning its new identifier.
+    pub fn receive(&mut self) -> u64 {
+        self.received += 1;
+        next_event_id()
+    }
+
+    pub fn ignore(&mut self, event: &EventKind, reason: IgnoreReason) {
+        self.ignored[reason as usize] += 1;
+        debug!("Ignored event ({}): {:?}", reason.code(), event);
+    }
+
+    pub fn ignored(&self, reason: IgnoreReason) -> u64 {
+        self.ignored[reason as usize]
+    }
+}
+
+impl fmt::Display for EventStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total: u64 = self.ignored.iter().sum();
+        write!(f, "{} events, {} ignored", self.received, total)?;
+        if total > 0 {
+            let counts: Vec<String> = IgnoreReason::ALL
+                .iter()
+                .filter(|reason| self.ignored(**reason) > 0)
+                .map(|reason| format!("{} {}", self.ignored(*reason), reason.code()))
+                .collect();
+            write!(f, " ({})", counts.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use crate::config::SteeringConfig;
+use uinput::event;
+
+/// The direction the remote is tilted towards.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Direction {
+    Left,
+    Right,
+}
+
+/// Turns the tilt of the remote, held sideways like a steering wheel,
+/// into presses of the left and right keys.
+pub struct Steering {
+    left: event::Keyboard,
+    right: event::Keyboard,
+    deadzone: i32,
+    threshold: i32,
+    /// Whether the steering mode is enabled.
+    active: bool,
+    /// The direction whose key is pressed.
+    pressed: Option<Direction>,
+}
+
+/// The key changes caused by a tilt update.
+#[derive(Debug, Default)]
+pub struct Turn {
+    pub release: Option<event::Keyboard>,
+    pub press: Option<event::Keyboard>,
+}
+
+impl Steering {
+    pub fn new(config: &SteeringConfig) -> Self {
+        Self {
+            left: config.left.0,
+            right: config.right.0,
+            deadzone: config.deadzone,
+            threshold: config.threshold.max(config.deadzone),
+            active: false,
+            pressed: None,
+        }
+    }
+
+    /// Enables or disables the steering mode, returning the new state
+    /// and the key to release, if any.
+    pub fn toggle(&mut self) -> (bool, Turn) {
+        self.active = !self.active;
+        let turn = Turn {
+            release: self.pressed.take().map(|direction| self.key(direction)),
+            press: None,
+        };
+        (self.active, turn)
+    }
+
+    /// Updates the pressed key from the accelerometer reading along the
+    /// long axis of the remote, which points sideways while steering.
+    ///
+    /// A key is pressed once the tilt exceeds the threshold, and
+    /// released once it goes back within the deadzone.
+    pub fn update(&mut self, y: i32) -> Option<Turn> {
+        if !self.active {
+            return None;
+        }
+        let direction = match self.pressed {
+            _ if y.abs() >= self.threshold => {
+                // The D-pad end of the remote is on the left.
+                Some(if y > 0 {
+                    Direction::Left
+                } else {
+                    Direction::Right
+                })
+            }
+            Some(direction) if y.abs() > self.deadzone => Some(direction),
+            _ => None,
+        };
+        if direction == self.pressed {
+            return None;
+        }
+
+        let previous = std::mem::replace(&mut self.pressed, direction);
+        Some(Turn {
+            release: previous.map(|direction| self.key(direction)),
+            press: direction.map(|direction| self.key(direction)),
+        })
+    }
+
+    fn key(&self, direction: Direction) -> event::Keyboard {
+        match direction {
+            Direction::Left => self.left,
+            Direction::Right => self.right,
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use crate::config::{StickConfig, StickMode};
+use std::time::Duration;
+use tokio::time::Instant;
+use uinput::event::keyboard::Key;
+
+/// A direction of the stick.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// What the pushed stick emits, once and then on every repeat.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Nudge {
+    /// Clicks the key.
+    Key(Key),
+    /// Scrolls by the given notches, horizontally (positive rightwards)
+    /// and vertically (positive upwards).
+    Scroll(i32, i32),
+}
+
+/// Translates the Nunchuk stick into repeated arrow keys or scroll
+/// notches.
+///
+/// An axis is pushed once its deflection exceeds the threshold, and
+/// released once it goes back within the deadzone. While both axes are
+/// pushed, the most deflected one wins.
+pub struct Stick {
+    mode: StickMode,
+    deadzone: i32,
+    threshold: i32,
+    repeat: Duration,
+    /// Whether each axis is pushed, horizontal first.
+    pushed: [bool; 2],
+    direction: Option<Direction>,
+    /// When the nudge of the direction repeats.
+    next: Option<Instant>,
+}
+
+impl Stick {
+    pub fn new(config: &StickConfig) -> Self {
+        Self {
+            mode: config.mode,
+            deadzone: config.deadzone,
+            threshold: config.threshold.max(config.deadzone),
+            repeat: Duration::from_millis(config.repeat_ms.max(1)),
+            pushed: [false; 2],
+            direction: None,
+            next: None,
+        }
+    }
+
+    /// Updates the direction from the stick position, returning the
+    /// nudge to emit right away if it changed.
+    pub fn update(&mut self, x: i32, y: i32) -> Option<Nudge> {
+        for (pushed, value) in self.pushed.iter_mut().zip([x, y]) {
+            if value.abs() > self.threshold {
+                *pushed = true;
+            } else if value.abs() < self.deadzone {
+                *pushed = false;
+            }
+        }
+        let direction = match self.pushed {
+            [true, true] if x.abs() >= y.abs() => Some(horizontal(x)),
+            [true, true] => Some(vertical(y)),
+            [true, false] => Some(horizontal(x)),
+            [false, true] => Some(vertical(y)),
+            [false, false] => None,
+        };
+        if direction == self.direction {
+            return None;
+        }
+        self.direction = direction;
+        self.next = direction.map(|_| Instant::now() + self.repeat);
+        direction.map(|direction| self.nudge(direction))
+    }
+
+    /// Releases the stick, e.g. once the Nunchuk is unplugged.
+    pub fn release(&mut self) {
+        self.pushed = [false; 2];
+        self.direction = None;
+        self.next = None;
+    }
+
+    /// Waits until the nudge repeats, after which [`repeat`](Self::repeat)
+    /// must be called. Never completes while the stick is released.
+    pub async fn tick(&self) {
+        match self.next {
+            Some(next) => tokio::time::sleep_until(next).await,
+            None => futures_util::future::pending().await,
+        }
+    }
+
+    /// Returns the repeated nudge, scheduling the next one.
+    pub fn repeat(&mut self) -> Option<Nudge> {
+        let direction = self.direction?;
+        self.next = Some(Instant::now() + self.repeat);
+        Some(self.nudge(direction))
+    }
+
+    fn nudge(&self, direction: Direction) -> Nudge {
+        match (self.mode, direction) {
+            (StickMode::Arrows, Direction::Up) => Nudge::Key(Key::Up),
+            (StickMode::Arrows, Direction::Down) => Nudge::Key(Key::Down),
+            (StickMode::Arrows, Direction::Left) => Nudge::Key(Key::Left),
+            (StickMode::Arrows, Direction::Right) => Nudge::Key(Key::Right),
+            (StickMode::Scroll, Direction::Up) => Nudge::Scroll(0, 1),
+            (StickMode::Scroll, Direction::Down) => Nudge::Scroll(0, -1),
+            (StickMode::Scroll, Direction::Left) => Nudge::Scroll(-1, 0),
+            (StickMode::Scroll, Direction::Right) => Nudge::Scroll(1, 0),
+        }
+    }
+}
+
+fn horizontal(x: i32) -> Direction {
+    if x > 0 {
+        Direction::Right
+    } else {
+        Direction::Left
+    }
+}
+
+fn vertical(y: i32) -> Direction {
+    if y > 0 {
+        Direction::Up
+    } else {
+        Direction::Down
+    }
+}
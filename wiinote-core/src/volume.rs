@@ -0,0 +1,21 @@
+use crate::mapping::{Volume, VolumeAction};
+use anyhow::{ensure, Context, Result};
+use std::process::Command;
+
+/// Changes the volume of the sink with `pactl`, which talks to the
+/// PulseAudio or PipeWire (through `pipewire-pulse`) server directly,
+/// instead of emitting a volume key that the desktop may not handle.
+pub fn change(action: &VolumeAction) -> Result<()> {
+    let sink = action.sink.as_deref().unwrap_or("@DEFAULT_SINK@");
+    let (command, value) = match action.volume {
+        Volume::Up => ("set-sink-volume", format!("+{}%", action.step)),
+        Volume::Down => ("set-sink-volume", format!("-{}%", action.step)),
+        Volume::Mute => ("set-sink-mute", "toggle".to_owned()),
+    };
+    let status = Command::new("pactl")
+        .args([command, sink, &value])
+        .status()
+        .context("cannot run pactl")?;
+    ensure!(status.success(), "pactl exited with {}", status);
+    Ok(())
+}